@@ -0,0 +1,342 @@
+//! `ADO.md` instruction files. From the most general to the most specific:
+//!
+//! 1. `~/.config/ado/ADO.md`
+//! 2. every `ADO.md` from the git root down to the current directory
+//!
+//! A line `@include <path>` is replaced by the content of `path`, relative to
+//! the file containing it. Includes of a repository's `ADO.md` must stay
+//! within the repository. An include that can't be read is skipped with a
+//! warning, the rest of the file still applies.
+
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use log::{info, warn};
+
+pub const ADO_MD: &str = "ADO.md";
+
+const INCLUDE_DIRECTIVE: &str = "@include";
+const MAX_INCLUDE_DEPTH: usize = 5;
+
+pub struct AdoMd {
+    pub path: PathBuf,
+    /// expanded content
+    pub content: String,
+    /// files pulled in by `@include`, in order
+    pub includes: Vec<PathBuf>,
+    /// why the includes left out were skipped
+    pub skipped: Vec<String>,
+}
+
+/// Directories from the git root (or `cwd` outside of a repository) down to
+/// `cwd`
+fn repo_dirs(cwd: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    for dir in cwd.ancestors() {
+        dirs.push(dir.to_path_buf());
+
+        if dir.join(".git").exists() {
+            dirs.reverse();
+            return dirs;
+        }
+    }
+
+    vec![cwd.to_path_buf()]
+}
+
+/// Every place an `ADO.md` is looked for with the directory its includes are
+/// bound to, if any
+fn sources() -> Vec<(PathBuf, Option<PathBuf>)> {
+    let mut files = Vec::new();
+
+    if let Some(config_dir) = dirs::config_dir() {
+        files.push((config_dir.join("ado").join(ADO_MD), None));
+    }
+
+    if let Ok(cwd) = env::current_dir() {
+        let dirs = repo_dirs(&cwd);
+        let root = dirs.first().cloned();
+
+        files.extend(dirs.into_iter().map(|d| (d.join(ADO_MD), root.clone())));
+    }
+
+    files
+}
+
+/// Every place an `ADO.md` is looked for, existing or not
+#[must_use]
+pub fn candidates() -> Vec<PathBuf> {
+    sources().into_iter().map(|(path, _)| path).collect()
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// `path` of an `@include path` line
+fn include_target(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix(INCLUDE_DIRECTIVE)?;
+
+    rest.starts_with(char::is_whitespace).then(|| rest.trim())
+}
+
+/// State of the expansion of one `ADO.md`
+struct Expander {
+    /// canonical directory includes must resolve in
+    root: Option<PathBuf>,
+    seen: HashSet<PathBuf>,
+    includes: Vec<PathBuf>,
+    skipped: Vec<String>,
+}
+
+impl Expander {
+    fn include(&mut self, target: &Path, depth: usize) -> Result<String> {
+        if depth > MAX_INCLUDE_DEPTH {
+            bail!("{}: too many nested includes", target.display());
+        }
+
+        let canonical = target
+            .canonicalize()
+            .with_context(|| format!("Unable to read {}", target.display()))?;
+
+        if let Some(root) = &self.root
+            && !canonical.starts_with(root)
+        {
+            bail!("{} is outside {}", target.display(), root.display());
+        }
+
+        self.includes.push(target.to_path_buf());
+        self.expand(target, depth)
+    }
+
+    fn expand(&mut self, path: &Path, depth: usize) -> Result<String> {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+
+        if !self.seen.insert(canonical.clone()) {
+            bail!("{} includes itself", path.display());
+        }
+
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut lines = Vec::new();
+
+        for line in data.lines() {
+            let Some(target) = include_target(line) else {
+                lines.push(line.to_string());
+                continue;
+            };
+
+            let target = base.join(expand_home(target));
+
+            info!("{} includes {}", path.display(), target.display());
+
+            match self.include(&target, depth.saturating_add(1)) {
+                Ok(content) => lines.push(content),
+                Err(e) => {
+                    warn!("{}: skipping @include ({e:#})", path.display());
+                    self.skipped.push(format!("{e:#}"));
+                }
+            }
+        }
+
+        self.seen.remove(&canonical);
+
+        Ok(lines.join("\n"))
+    }
+}
+
+impl AdoMd {
+    /// Load `path`, the includes it pulls in have to be within `root` if set
+    pub fn load(path: &Path, root: Option<&Path>) -> Result<Self> {
+        let root = root
+            .map(Path::canonicalize)
+            .transpose()
+            .context("Unable to resolve the include root")?;
+
+        let mut expander = Expander {
+            root,
+            seen: HashSet::new(),
+            includes: Vec::new(),
+            skipped: Vec::new(),
+        };
+
+        let content = expander.expand(path, 0)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            content,
+            includes: expander.includes,
+            skipped: expander.skipped,
+        })
+    }
+}
+
+/// The existing `ADO.md` files, most general first. Files that fail to load
+/// are returned as errors so callers can report them.
+#[must_use]
+pub fn discover() -> Vec<Result<AdoMd>> {
+    sources()
+        .into_iter()
+        .filter(|(p, _)| p.is_file())
+        .map(|(p, root)| AdoMd::load(&p, root.as_deref()))
+        .collect()
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, data: &str) -> PathBuf {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+        path.to_path_buf()
+    }
+
+    #[test]
+    fn test_include() {
+        let td = tempfile::tempdir().unwrap();
+        let root = td.path();
+
+        let main = write(
+            &root.join(ADO_MD),
+            "# Rules\n@include docs/style.md\nthe end",
+        );
+        let style = write(
+            &root.join("docs/style.md"),
+            "style\n  @include ../shared.md",
+        );
+        write(&root.join("shared.md"), "shared");
+
+        let ado_md = AdoMd::load(&main, None).unwrap();
+
+        assert_eq!(ado_md.content, "# Rules\nstyle\nshared\nthe end");
+        assert_eq!(ado_md.includes, [style, root.join("docs/../shared.md")]);
+        assert!(ado_md.skipped.is_empty());
+
+        // the same file twice is fine, it doesn't include itself
+        write(&main, "@include shared.md\n@include shared.md");
+        assert_eq!(AdoMd::load(&main, None).unwrap().content, "shared\nshared");
+
+        // only the directive followed by a path
+        write(&main, "@includes shared.md\n@include");
+        assert_eq!(
+            AdoMd::load(&main, None).unwrap().content,
+            "@includes shared.md\n@include"
+        );
+
+        write(&main, "before\n@include missing.md\nafter");
+        let ado_md = AdoMd::load(&main, None).unwrap();
+        assert_eq!(ado_md.content, "before\nafter");
+        assert!(ado_md.includes.is_empty());
+        assert_eq!(ado_md.skipped.len(), 1);
+    }
+
+    #[test]
+    fn test_include_root() {
+        let td = tempfile::tempdir().unwrap();
+        let repo = td.path().join("repo");
+
+        let secret = write(&td.path().join("secret.md"), "secret");
+        write(&repo.join("docs/style.md"), "style");
+        let main = write(
+            &repo.join(ADO_MD),
+            &format!(
+                "@include docs/style.md\n@include ../secret.md\n@include {}",
+                secret.display()
+            ),
+        );
+
+        let ado_md = AdoMd::load(&main, Some(&repo)).unwrap();
+        assert_eq!(ado_md.content, "style");
+        assert_eq!(ado_md.includes, [repo.join("docs/style.md")]);
+        assert_eq!(ado_md.skipped.len(), 2);
+        assert!(
+            ado_md.skipped[0].contains("is outside"),
+            "{:?}",
+            ado_md.skipped
+        );
+
+        // not bound, e.g. the user's own ADO.md
+        let ado_md = AdoMd::load(&main, None).unwrap();
+        assert_eq!(ado_md.content, "style\nsecret\nsecret");
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let td = tempfile::tempdir().unwrap();
+
+        let a = write(&td.path().join("a.md"), "a\n@include b.md");
+        write(&td.path().join("b.md"), "b\n@include a.md");
+
+        let ado_md = AdoMd::load(&a, None).unwrap();
+        assert_eq!(ado_md.content, "a\nb");
+        assert!(
+            ado_md.skipped[0].contains("includes itself"),
+            "{:?}",
+            ado_md.skipped
+        );
+    }
+
+    #[test]
+    fn test_include_depth() {
+        let td = tempfile::tempdir().unwrap();
+
+        for i in 0..=MAX_INCLUDE_DEPTH {
+            write(
+                &td.path().join(format!("{i}.md")),
+                &format!("{i}\n@include {}.md", i + 1),
+            );
+        }
+
+        let last = MAX_INCLUDE_DEPTH + 1;
+        write(&td.path().join(format!("{last}.md")), "last");
+
+        let ado_md = AdoMd::load(&td.path().join("0.md"), None).unwrap();
+        assert!(!ado_md.content.ends_with("last"));
+        assert!(
+            ado_md.skipped[0].contains("too many nested includes"),
+            "{:?}",
+            ado_md.skipped
+        );
+
+        // one level less is fine
+        let ado_md = AdoMd::load(&td.path().join("1.md"), None).unwrap();
+        assert!(ado_md.content.ends_with("last"));
+        assert!(ado_md.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_repo_dirs() {
+        let td = tempfile::tempdir().unwrap();
+        let repo = td.path().join("repo");
+        let nested = repo.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+
+        // outside of a repository, only the directory itself
+        assert_eq!(repo_dirs(&nested), std::slice::from_ref(&nested));
+
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        assert_eq!(
+            repo_dirs(&nested),
+            [repo.clone(), repo.join("a"), nested.clone()]
+        );
+        assert_eq!(repo_dirs(&repo), [repo]);
+    }
+}
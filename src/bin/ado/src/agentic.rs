@@ -1,63 +1,1226 @@
-//! Execution of agentic (`partial`) artifacts — running shell commands and
-//! writing files on the host. Shared by the TUI and headless consoles so both
-//! drive the same agentic loop. Each caller passes a `notify` callback to
-//! surface the action in its own way (coloured line in the TUI, structured
-//! message in headless).
+//! Execution of agentic (`partial`) artifacts — running commands,
+//! writing files and applying diffs on the host. Shared by the TUI and headless
+//! consoles so both drive the same agentic loop. Each caller passes an
+//! [`AgenticUi`] to surface the actions in its own way (coloured lines and
+//! prompts in the TUI, structured messages in headless).
 
-use std::{fmt::Display, fs, process::Command};
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
 
-use adolib::data::types::{AdoDataArtifact, AdoDataArtifactType};
-use anyhow::{Context, Result};
-use log::error;
+use adolib::{
+    config::loader::ShellPolicy,
+    data::types::{AdoDataArtifact, AdoDataArtifactType},
+    error::Error,
+    patch::{self, FilePatch, HunkStatus},
+    tools,
+};
+use anyhow::{Context, Result, bail};
+use log::{error, info};
 
-/// Run a shell command line and return its combined stdout + stderr.
-pub fn handler_command<S>(cmd_line: S) -> Result<String>
-where
-    S: AsRef<str> + Display,
-{
-    let args = shell_words::split(cmd_line.as_ref()).with_context(|| format!("Unable to split {cmd_line}"))?;
+use crate::{memories::Memories, session::Session, skills};
 
-    let cmd = args.first().with_context(|| format!("Empty command: {cmd_line}"))?;
-    let out = Command::new(cmd)
-        .args(args.get(1..).unwrap_or_default())
+/// Shell used for command lines with pipes, redirects, `&&`...
+const SHELL: &str = "/bin/sh";
+
+/// How a command line ran, reported to the model along with its output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CommandMode {
+    /// in-process: `cd` and the file operations of [`tools`]
+    BuiltIn,
+    /// the program, without a shell
+    Exec,
+    /// through `/bin/sh -c`
+    Shell,
+}
+
+impl Display for CommandMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandMode::BuiltIn => write!(f, "built-in"),
+            CommandMode::Exec => write!(f, "exec"),
+            CommandMode::Shell => write!(f, "{SHELL} -c"),
+        }
+    }
+}
+
+/// Whether `line` has pipes, redirects, `&&`, `;`, subshells or expansions:
+/// variables outside of single quotes, globs and `~` outside of any
+fn needs_shell(line: &str) -> bool {
+    let mut chars = line.chars().peekable();
+    let mut quote = None;
+    let mut word_start = true;
+
+    while let Some(c) = chars.next() {
+        let at_word_start = word_start;
+        word_start = quote.is_none() && c.is_whitespace();
+
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                chars.next();
+            }
+            // in `grep "fn main$"` the `$` is a regex anchor, `$HOME` and `$(pwd)` expand
+            (_, '$')
+                if chars.peek().is_some_and(|n| {
+                    n.is_ascii_alphanumeric() || ['_', '{', '(', '?'].contains(n)
+                }) =>
+            {
+                return true;
+            }
+            (_, '`') | (None, '|' | '&' | ';' | '<' | '>' | '(' | ')' | '\n' | '*' | '?' | '[') => {
+                return true;
+            }
+            (None, '~') if at_word_start => return true,
+            (None, '\'' | '"') => quote = Some(c),
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// Programs that only read, run even in plan mode, with the options they may
+/// be given: others, e.g. `rg --pre` or `tree -o`, run programs or write files
+const READ_ONLY_PROGRAMS: &[(&str, &[&str])] = &[
+    ("cat", &["-A", "-b", "-E", "-n", "-s", "-T", "-v"]),
+    (
+        "date",
+        &["-d", "-I", "-R", "-u", "--date", "--iso-8601", "--rfc-3339", "--utc"],
+    ),
+    (
+        "diff",
+        &[
+            "-B",
+            "-b",
+            "-i",
+            "-N",
+            "-q",
+            "-r",
+            "-U",
+            "-u",
+            "-w",
+            "-y",
+            "--brief",
+            "--recursive",
+            "--unified",
+        ],
+    ),
+    (
+        "du",
+        &["-a", "-c", "-d", "-h", "-k", "-m", "-s", "-x", "--apparent-size", "--max-depth"],
+    ),
+    (
+        "file",
+        &["-b", "-i", "-L", "--brief", "--mime", "--mime-encoding", "--mime-type"],
+    ),
+    ("head", &["-c", "-n", "-q", "-v", "--bytes", "--lines"]),
+    ("pwd", &["-L", "-P"]),
+    (
+        "rg",
+        &[
+            "-A",
+            "-B",
+            "-C",
+            "-c",
+            "-e",
+            "-F",
+            "-g",
+            "-i",
+            "-l",
+            "-m",
+            "-N",
+            "-n",
+            "-o",
+            "-S",
+            "-s",
+            "-t",
+            "-T",
+            "-u",
+            "-v",
+            "-w",
+            "--context",
+            "--count",
+            "--files",
+            "--files-with-matches",
+            "--fixed-strings",
+            "--glob",
+            "--hidden",
+            "--ignore-case",
+            "--line-number",
+            "--max-count",
+            "--smart-case",
+            "--type",
+            "--word-regexp",
+        ],
+    ),
+    ("stat", &["-c", "-f", "-L", "-t", "--format"]),
+    ("tail", &["-c", "-n", "-q", "-v", "--bytes", "--lines"]),
+    (
+        "tree",
+        &[
+            "-a",
+            "-D",
+            "-d",
+            "-F",
+            "-f",
+            "-h",
+            "-I",
+            "-i",
+            "-L",
+            "-P",
+            "-s",
+            "--dirsfirst",
+            "--gitignore",
+            "--noreport",
+        ],
+    ),
+    ("uname", &["-a", "-m", "-n", "-o", "-p", "-r", "-s", "-v"]),
+    ("wc", &["-c", "-L", "-l", "-m", "-w"]),
+    ("which", &["-a"]),
+];
+
+/// `git` subcommands that only read, with the options they may be given:
+/// others, e.g. `git diff --output` or `git grep -O`, write files or run
+/// programs
+const READ_ONLY_GIT: &[(&str, &[&str])] = &[
+    (
+        "blame",
+        &["-C", "-e", "-L", "-l", "-M", "-s", "-w", "--date"],
+    ),
+    ("describe", &["--abbrev", "--always", "--long", "--tags"]),
+    (
+        "diff",
+        &[
+            "-M",
+            "-R",
+            "-U",
+            "-w",
+            "--cached",
+            "--check",
+            "--name-only",
+            "--name-status",
+            "--no-color",
+            "--numstat",
+            "--shortstat",
+            "--staged",
+            "--stat",
+            "--summary",
+            "--unified",
+            "--word-diff",
+        ],
+    ),
+    (
+        "grep",
+        &[
+            "-A",
+            "-B",
+            "-C",
+            "-c",
+            "-E",
+            "-e",
+            "-F",
+            "-H",
+            "-h",
+            "-i",
+            "-l",
+            "-n",
+            "-P",
+            "-v",
+            "-w",
+            "--cached",
+            "--count",
+            "--files-with-matches",
+            "--ignore-case",
+            "--line-number",
+            "--untracked",
+        ],
+    ),
+    (
+        "log",
+        &[
+            "-G",
+            "-n",
+            "-p",
+            "-S",
+            "--all",
+            "--author",
+            "--date",
+            "--decorate",
+            "--first-parent",
+            "--follow",
+            "--format",
+            "--graph",
+            "--grep",
+            "--max-count",
+            "--name-only",
+            "--name-status",
+            "--no-merges",
+            "--oneline",
+            "--patch",
+            "--pretty",
+            "--reverse",
+            "--since",
+            "--stat",
+            "--until",
+        ],
+    ),
+    (
+        "ls-files",
+        &[
+            "-c",
+            "-d",
+            "-m",
+            "-o",
+            "-s",
+            "--cached",
+            "--deleted",
+            "--exclude-standard",
+            "--modified",
+            "--others",
+            "--stage",
+        ],
+    ),
+    (
+        "rev-parse",
+        &[
+            "--abbrev-ref",
+            "--git-dir",
+            "--is-inside-work-tree",
+            "--short",
+            "--show-toplevel",
+            "--verify",
+        ],
+    ),
+    (
+        "shortlog",
+        &["-e", "-n", "-s", "--email", "--numbered", "--summary"],
+    ),
+    (
+        "show",
+        &[
+            "-p",
+            "-s",
+            "--format",
+            "--name-only",
+            "--name-status",
+            "--no-patch",
+            "--oneline",
+            "--pretty",
+            "--stat",
+        ],
+    ),
+    (
+        "status",
+        &["-b", "-s", "-u", "--branch", "--porcelain", "--short", "--untracked-files"],
+    ),
+];
+
+/// Whether every option of `args` is one of `allowed`. Short options may be
+/// grouped or followed by a number, `-la` or `-n5`, and long ones given a
+/// value, `--format=%h`. Anything after `--` is an operand.
+fn allowed_options(args: &[String], allowed: &[&str]) -> bool {
+    for arg in args {
+        if arg == "--" {
+            break;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let name = long.split_once('=').map_or(long, |(name, _)| name);
+
+            if !allowed.contains(&format!("--{name}").as_str()) {
+                return false;
+            }
+        } else if let Some(short) = arg.strip_prefix('-') {
+            // `-n5`, `-L10,20` or `-5`, once the letters are allowed options
+            let value = short
+                .char_indices()
+                .find(|&(_, c)| !allowed.contains(&format!("-{c}").as_str()))
+                .map_or("", |(i, _)| short.get(i..).unwrap_or_default());
+
+            if value.contains(|c: char| c.is_ascii_alphabetic()) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// Options allowed to `name` in `table`, `None` when it isn't read-only
+fn read_only_options(
+    table: &'static [(&str, &[&str])],
+    name: &str,
+) -> Option<&'static [&'static str]> {
+    table.iter().find(|(n, _)| *n == name).map(|(_, options)| *options)
+}
+
+/// Whether `cmd_line` can't change anything. Lines that need a shell never
+/// qualify.
+fn is_read_only_command(cmd_line: &str) -> bool {
+    if needs_shell(cmd_line) {
+        return false;
+    }
+
+    let Ok(args) = shell_words::split(cmd_line) else {
+        return false;
+    };
+
+    match args.as_slice() {
+        [program, subcommand, rest @ ..] if program == "git" => {
+            read_only_options(READ_ONLY_GIT, subcommand)
+                .is_some_and(|options| allowed_options(rest, options))
+        }
+        [program, rest @ ..] => {
+            program == "cd"
+                || tools::TOOLS.contains(&program.as_str())
+                || read_only_options(READ_ONLY_PROGRAMS, program)
+                    .is_some_and(|options| allowed_options(rest, options))
+        }
+        [] => true,
+    }
+}
+
+/// What a command line printed, and how and where it ran
+pub struct CommandOutput {
+    mode: CommandMode,
+    cwd: PathBuf,
+    /// `None` for built-ins
+    status: Option<ExitStatus>,
+    output: String,
+}
+
+impl CommandOutput {
+    fn built_in(cwd: PathBuf, output: String) -> Self {
+        Self {
+            mode: CommandMode::BuiltIn,
+            cwd,
+            status: None,
+            output,
+        }
+    }
+}
+
+fn run_process(
+    mut command: Command,
+    mode: CommandMode,
+    cwd: PathBuf,
+    cmd_line: &str,
+) -> Result<CommandOutput> {
+    let out = command
+        .current_dir(&cwd)
         .output()
         .with_context(|| format!("Unable to execute {cmd_line}"))?;
 
-    let mut data = String::from_utf8_lossy(&out.stdout).into_owned();
+    let mut output = String::from_utf8_lossy(&out.stdout).into_owned();
     if !out.stderr.is_empty() {
-        data.push_str(&String::from_utf8_lossy(&out.stderr));
+        output.push_str(&String::from_utf8_lossy(&out.stderr));
     }
 
-    Ok(data)
+    Ok(CommandOutput {
+        mode,
+        cwd,
+        status: Some(out.status),
+        output,
+    })
 }
 
-/// Execute a single `command` or `file` artifact from a `partial` response.
+/// Run a command line in the working directory of `session` and return its
+/// combined stdout + stderr. `cd` moves the working
+/// directory and the file operations of [`tools`] run in-process, other
+/// programs are executed directly. Lines that need a shell go through
+/// `/bin/sh -c` as `agent.shell` allows, asking `ui` if needed.
+pub fn handler_command(
+    cmd_line: &str,
+    ui: &dyn AgenticUi,
+    session: &Session,
+) -> Result<CommandOutput> {
+    let cwd = session.cwd();
+
+    if needs_shell(cmd_line) {
+        match session.agent().shell {
+            ShellPolicy::Never => {
+                bail!(
+                    "{cmd_line} needs a shell, which agent.shell doesn't allow. Run one program at a time, without pipes, redirects or &&"
+                )
+            }
+            ShellPolicy::Ask if !ui.confirm(&format!("Run \"{cmd_line}\" with {SHELL}?")) => {
+                bail!("The user declined running {cmd_line} through the shell")
+            }
+            ShellPolicy::Ask | ShellPolicy::Always => {}
+        }
+
+        let mut command = Command::new(SHELL);
+        command.arg("-c").arg(cmd_line);
+
+        return run_process(command, CommandMode::Shell, cwd, cmd_line);
+    }
+
+    let args =
+        shell_words::split(cmd_line).with_context(|| format!("Unable to split {cmd_line}"))?;
+
+    if args.first().is_some_and(|a| a == "cd") {
+        let dir = match args.get(1..).unwrap_or_default() {
+            [] => dirs::home_dir().context("Unable to find the home directory")?,
+            [dir] => PathBuf::from(dir),
+            _ => bail!("Usage: cd [DIR]"),
+        };
+
+        let cwd = session.change_dir(&dir)?;
+        return Ok(CommandOutput::built_in(
+            cwd,
+            "Working directory changed".into(),
+        ));
+    }
+
+    // glob, grep, read and ls don't depend on what the host has installed
+    match tools::run(&args, &cwd) {
+        // `ls -R` or `grep --include`, left to the host's program
+        Some(Err(Error::UnsupportedOption { option })) => {
+            info!("built-in {cmd_line} doesn't support {option}, executing it");
+        }
+        Some(result) => {
+            let output = result.with_context(|| format!("Unable to execute {cmd_line}"))?;
+            return Ok(CommandOutput::built_in(cwd, output));
+        }
+        None => {}
+    }
+
+    let cmd = args.first().with_context(|| format!("Empty command: {cmd_line}"))?;
+    let mut command = Command::new(cmd);
+    command.args(args.get(1..).unwrap_or_default());
+
+    run_process(command, CommandMode::Exec, cwd, cmd_line)
+}
+
+/// How a console surfaces agentic actions
+pub trait AgenticUi {
+    /// Human-readable description of an action, before it runs
+    fn notify(&self, text: &str);
+    /// A unified diff about to be applied
+    fn show_diff(&self, diff: &str);
+    /// Whether the user approves `question`
+    fn confirm(&self, question: &str) -> bool;
+}
+
+/// A file patch checked against the working tree
+struct CheckedPatch {
+    path: PathBuf,
+    /// `None` to delete the file
+    content: Option<String>,
+    hunks: usize,
+}
+
+/// Check `file` against the working tree, or on top of the patch of the same
+/// file in `checked` when the diff has several
+fn check_patch(
+    file: &FilePatch,
+    session: &Session,
+    checked: &[CheckedPatch],
+) -> Result<CheckedPatch, String> {
+    let path = session.resolve(file.path().ok_or("Diff without a file path")?);
+
+    let earlier = checked.iter().find(|c| c.path == path);
+
+    let original = if let Some(earlier) = earlier {
+        match (&earlier.content, file.is_new()) {
+            (Some(_), true) => return Err(format!("{} already exists", path.display())),
+            (Some(content), false) => content.clone(),
+            (None, true) => String::new(),
+            (None, false) => {
+                return Err(format!("{} is deleted earlier in the diff", path.display()));
+            }
+        }
+    } else if file.is_new() {
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()));
+        }
+        String::new()
+    } else {
+        fs::read_to_string(&path)
+            .map_err(|e| format!("Unable to read {}. Error: {e}", path.display()))?
+    };
+
+    let outcome = file.apply(&original);
+    let applied = outcome.applied();
+
+    let Some(content) = outcome.content else {
+        let failed: Vec<String> = outcome
+            .hunks
+            .iter()
+            .zip(&file.hunks)
+            .enumerate()
+            .filter(|(_, (status, _))| **status == HunkStatus::Failed)
+            .map(|(i, (_, hunk))| format!("hunk {} (line {})", i.saturating_add(1), hunk.old_start))
+            .collect();
+
+        return Err(format!(
+            "{}: {} of {} hunks don't match the file, {}. Read the file again and send a new diff.",
+            path.display(),
+            outcome.failed(),
+            outcome.hunks.len(),
+            failed.join(", ")
+        ));
+    };
+
+    Ok(CheckedPatch {
+        path,
+        content: if file.is_delete() {
+            None
+        } else {
+            Some(content)
+        },
+        hunks: earlier.map_or(0, |c| c.hunks).saturating_add(applied),
+    })
+}
+
+/// Validate every file of the diff against the working tree, then apply them
+/// all once the user agrees
+fn apply_diff(artifact: &AdoDataArtifact, ui: &dyn AgenticUi, session: &Session) -> String {
+    let patches = match patch::parse(&artifact.content, artifact.path.as_deref()) {
+        Ok(v) => v,
+        Err(e) => return format!("Unable to parse the diff. Error: {e}"),
+    };
+
+    let mut checked = Vec::new();
+    let mut failures = Vec::new();
+
+    for p in &patches {
+        match check_patch(p, session, &checked) {
+            Ok(c) => {
+                checked.retain(|earlier| earlier.path != c.path);
+                checked.push(c);
+            }
+            Err(e) => failures.push(e),
+        }
+    }
+
+    if !failures.is_empty() {
+        return format!("The diff was not applied. {}", failures.join(" "));
+    }
+
+    let files: Vec<String> = checked.iter().map(|c| c.path.display().to_string()).collect();
+
+    ui.show_diff(&artifact.content);
+
+    if !ui.confirm(&format!("apply diff to {}?", files.join(", "))) {
+        return "The user declined the diff, nothing was changed".to_string();
+    }
+
+    let mut results = Vec::new();
+
+    for c in checked {
+        if let Err(e) = session.record_write(&c.path) {
+            results.push(format!(
+                "{}: not changed, unable to back it up. Error: {e}",
+                c.path.display()
+            ));
+            continue;
+        }
+
+        let ret = match &c.content {
+            Some(content) => {
+                if let Some(dir) = c.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                    let _ = fs::create_dir_all(dir);
+                }
+                fs::write(&c.path, content)
+            }
+            None => fs::remove_file(&c.path),
+        };
+
+        match ret {
+            Ok(()) => {
+                info!("diff applied to {}", c.path.display());
+                results.push(format!("{}: {} hunks applied", c.path.display(), c.hunks));
+            }
+            Err(e) => results.push(format!("{}: unable to write. Error: {e}", c.path.display())),
+        }
+    }
+
+    results.join("\n")
+}
+
+/// Write a `file` artifact. An existing file is only overwritten once the
+/// user saw the changes and agreed, after a backup for `/undo`.
+fn write_file(path: &Path, content: &str, ui: &dyn AgenticUi, session: &Session) -> String {
+    let path = &session.resolve(path);
+
+    if path.is_file() {
+        let current = match fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(e) => {
+                return format!(
+                    "Unable to read {} before overwriting it. Error: {e}",
+                    path.display()
+                );
+            }
+        };
+
+        if current == content {
+            return format!(
+                "{} already has this content, nothing was written",
+                path.display()
+            );
+        }
+
+        ui.show_diff(&patch::unified_diff(path, &current, content));
+
+        if !ui.confirm(&format!("overwrite {}?", path.display())) {
+            return format!(
+                "The user declined overwriting {}, nothing was written",
+                path.display()
+            );
+        }
+    }
+
+    ui.notify(&format!(
+        "writing {} bytes to {}",
+        content.len(),
+        path.display()
+    ));
+
+    if let Err(e) = session.record_write(path) {
+        return format!(
+            "{} was not written, unable to back it up. Error: {e}",
+            path.display()
+        );
+    }
+
+    match fs::write(path, content.as_bytes()) {
+        Ok(()) => format!("{} was successfully written to disk", path.display()),
+        Err(e) => format!("Unable to write {} to disk. Error: {e}", path.display()),
+    }
+}
+
+/// 1-based line of byte `offset` in `text`
+fn line_of(text: &str, offset: usize) -> usize {
+    text.get(..offset).map_or(0, |t| t.matches('\n').count()).saturating_add(1)
+}
+
+/// Replace `old` by `new` in `path`. `old` must match exactly once, or
+/// `occurrence` tells which match to replace.
+fn edit_file(
+    path: &Path,
+    old: &str,
+    new: &str,
+    occurrence: Option<usize>,
+    ui: &dyn AgenticUi,
+    session: &Session,
+) -> String {
+    let path = &session.resolve(path);
+
+    if old.is_empty() {
+        return format!("Edit of {} has no `old` text to replace", path.display());
+    }
+
+    let current = match fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) => return format!("Unable to read {}. Error: {e}", path.display()),
+    };
+
+    let matches: Vec<usize> = current.match_indices(old).map(|(i, _)| i).collect();
+
+    let at = match (matches.as_slice(), occurrence) {
+        ([], _) => {
+            return format!(
+                "The `old` text was not found in {}. It must match exactly, whitespace and indentation included: \
+                 read the file again before retrying.",
+                path.display()
+            );
+        }
+        ([at], None | Some(1)) => *at,
+        (_, None) => {
+            let lines: Vec<String> =
+                matches.iter().map(|m| line_of(&current, *m).to_string()).collect();
+            return format!(
+                "The `old` text is found {} times in {}, at lines {}. Include more surrounding lines to make it \
+                 unique, or set `occurrence` (1 to {}).",
+                matches.len(),
+                path.display(),
+                lines.join(", "),
+                matches.len()
+            );
+        }
+        (_, Some(n)) => {
+            let Some(at) = n.checked_sub(1).and_then(|i| matches.get(i)) else {
+                return format!(
+                    "`occurrence` {n} is out of range, the `old` text is found {} time(s) in {}",
+                    matches.len(),
+                    path.display()
+                );
+            };
+            *at
+        }
+    };
+
+    let mut content = current.clone();
+    content.replace_range(at..at.saturating_add(old.len()), new);
+
+    let ret = write_file(path, &content, ui, session);
+
+    format!(
+        "Edit at line {} of {}: {ret}",
+        line_of(&current, at),
+        path.display()
+    )
+}
+
+/// Result of an executed artifact, fed back to the model
+struct ArtifactResult {
+    /// attributes tying the result to its artifact, e.g. `command` or
+    /// `exit_status`
+    tags: Vec<(&'static str, String)>,
+    output: String,
+}
+
+impl ArtifactResult {
+    fn new<S>(output: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            tags: Vec::new(),
+            output: output.into(),
+        }
+    }
+
+    fn tag<V>(mut self, name: &'static str, value: V) -> Self
+    where
+        V: Display,
+    {
+        self.tags.push((name, value.to_string()));
+        self
+    }
+
+    /// `<result index="1" type="command" ...>` block of the `index`th
+    /// artifact, 1-based
+    fn block(&self, index: usize, artifact_type: &AdoDataArtifactType) -> String {
+        let escape = |v: &str| {
+            v.replace('&', "&amp;")
+                .replace('"', "&quot;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('\n', "&#10;")
+        };
+
+        let mut attributes =
+            vec![format!("index=\"{index}\""), format!("type=\"{artifact_type}\"")];
+        attributes
+            .extend(self.tags.iter().map(|(name, value)| format!("{name}=\"{}\"", escape(value))));
+
+        format!(
+            "<result {}>\n{}\n</result>",
+            attributes.join(" "),
+            self.output.trim_end()
+        )
+    }
+}
+
+fn command_result(
+    artifact: &AdoDataArtifact,
+    ui: &dyn AgenticUi,
+    session: &Session,
+) -> ArtifactResult {
+    let ret = handler_command(&artifact.content, ui, session);
+
+    if !is_read_only_command(&artifact.content) {
+        session.record_command();
+    }
+
+    match ret {
+        Ok(out) => {
+            let result = ArtifactResult::new(out.output)
+                .tag("command", &artifact.content)
+                .tag("mode", out.mode)
+                .tag("cwd", out.cwd.display());
+
+            match out.status {
+                Some(status) => match status.code() {
+                    Some(code) => result.tag("exit_status", code),
+                    None => result.tag("exit_status", "killed by a signal"),
+                },
+                None => result,
+            }
+        }
+        Err(e) => ArtifactResult::new(format!("Error: {e:#}")).tag("command", &artifact.content),
+    }
+}
+
+/// Whether running `artifact` may change something, which plan mode defers
+fn is_change(artifact: &AdoDataArtifact) -> bool {
+    match artifact.artifact_type {
+        AdoDataArtifactType::File
+        | AdoDataArtifactType::Edit
+        | AdoDataArtifactType::Diff
+        | AdoDataArtifactType::Memory => true,
+        AdoDataArtifactType::Command => !is_read_only_command(&artifact.content),
+        _ => false,
+    }
+}
+
+/// One line summary of a planned step
+#[must_use]
+pub fn describe_step(artifact: &AdoDataArtifact) -> String {
+    let path = artifact.path.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+
+    match artifact.artifact_type {
+        AdoDataArtifactType::File => format!("write {path}"),
+        AdoDataArtifactType::Edit => format!("edit {path}"),
+        AdoDataArtifactType::Diff if path.is_empty() => "apply a diff".to_string(),
+        AdoDataArtifactType::Diff => format!("apply a diff to {path}"),
+        AdoDataArtifactType::Command => format!("run `{}`", artifact.content),
+        AdoDataArtifactType::Memory => format!("remember \"{}\"", artifact.content.trim()),
+        ref other => other.to_string(),
+    }
+}
+
+/// Execute a single `command`, `file`, `diff`, `edit`, `skill` or `memory` artifact
+/// from a `partial` response. In plan mode, artifacts that may change
+/// something are added to the plan of `session` instead.
 ///
-/// `notify` is called with a human-readable description of the action before it
-/// runs. Returns the result to feed back to the model so the loop can continue,
-/// or `None` for artifact types that aren't executable.
-pub fn execute_partial_artifact(artifact: &AdoDataArtifact, notify: &dyn Fn(&str)) -> Option<String> {
-    match &artifact.artifact_type {
+/// `ui` is notified with a human-readable description of the action before it
+/// runs, files changed are recorded in `session` so they can be undone.
+/// Returns the result to feed back to the model so the loop can continue, or
+/// `None` for artifact types that aren't executable.
+fn execute_partial_artifact(
+    artifact: &AdoDataArtifact,
+    ui: &dyn AgenticUi,
+    session: &Session,
+) -> Option<ArtifactResult> {
+    if session.plan_mode() && is_change(artifact) {
+        let step = session.add_to_plan(artifact.clone());
+        ui.notify(&format!("planned step {step}: {}", describe_step(artifact)));
+
+        let mut result = ArtifactResult::new(format!(
+            "Not executed: plan mode is on, this is step {step} of the plan the user will review"
+        ));
+
+        if matches!(artifact.artifact_type, AdoDataArtifactType::Command) {
+            result = result.tag("command", &artifact.content);
+        } else if let Some(path) = &artifact.path {
+            result = result.tag("path", path.display());
+        }
+
+        return Some(result.tag("planned_step", step));
+    }
+
+    execute_artifact(artifact, ui, session)
+}
+
+/// [`execute_partial_artifact`], plan mode aside
+fn execute_artifact(
+    artifact: &AdoDataArtifact,
+    ui: &dyn AgenticUi,
+    session: &Session,
+) -> Option<ArtifactResult> {
+    // the turn can be rewound to before its first change of the tree
+    if is_change(artifact) && !matches!(artifact.artifact_type, AdoDataArtifactType::Memory) {
+        session.checkpoint();
+    }
+
+    let result = match &artifact.artifact_type {
         AdoDataArtifactType::File => {
             let Some(path) = &artifact.path else {
-                return Some("File path is missing".into());
+                return Some(ArtifactResult::new("File path is missing"));
             };
-            notify(&format!("writing {} bytes to {}", artifact.content.len(), path.display()));
-            match fs::write(path, artifact.content.as_bytes()) {
-                Ok(()) => Some(format!("{} was successfully written to disk", path.display())),
-                Err(e) => Some(format!("Unable to write {} to disk. Error: {e}", path.display())),
-            }
+            ArtifactResult::new(write_file(path, &artifact.content, ui, session))
+                .tag("path", path.display())
         }
         AdoDataArtifactType::Command => {
-            notify(&format!("executing \"{}\"", artifact.content));
-            match handler_command(&artifact.content) {
-                Ok(v) => Some(v),
-                Err(e) => Some(format!("Unable to execute {}. Error: {e}", artifact.content)),
+            ui.notify(&format!("executing \"{}\"", artifact.content));
+            command_result(artifact, ui, session)
+        }
+        AdoDataArtifactType::Edit => {
+            let Some(path) = &artifact.path else {
+                return Some(ArtifactResult::new("File path is missing"));
+            };
+            ui.notify(&format!("editing {}", path.display()));
+            let old = artifact.old.as_deref().unwrap_or_default();
+            ArtifactResult::new(edit_file(
+                path,
+                old,
+                &artifact.content,
+                artifact.occurrence,
+                ui,
+                session,
+            ))
+            .tag("path", path.display())
+        }
+        AdoDataArtifactType::Diff => {
+            ui.notify("checking diff");
+            let result = ArtifactResult::new(apply_diff(artifact, ui, session));
+
+            match &artifact.path {
+                Some(path) => result.tag("path", path.display()),
+                None => result,
             }
         }
+        AdoDataArtifactType::Skill => {
+            let content = artifact.content.trim();
+            let (name, args) = content.split_once(char::is_whitespace).unwrap_or((content, ""));
+
+            ui.notify(&format!("loading skill {name}"));
+
+            let output = if let Some(skill) = skills::find(name) {
+                skill.render(args)
+            } else {
+                let available: Vec<String> =
+                    skills::discover().into_iter().map(|s| s.name).collect();
+                format!(
+                    "Skill {name} does not exist. Available skills: {}",
+                    available.join(", ")
+                )
+            };
+
+            ArtifactResult::new(output).tag("skill", name)
+        }
+        AdoDataArtifactType::Memory => {
+            let fact = artifact.content.trim();
+
+            if !ui.confirm(&format!("remember \"{fact}\" for future sessions?")) {
+                return Some(ArtifactResult::new(
+                    "The user declined, nothing was remembered",
+                ));
+            }
+
+            ui.notify(&format!("remembering \"{fact}\""));
+
+            let output = match Memories::from_default().and_then(|m| m.add(fact)) {
+                Ok(true) => "Remembered for future sessions".to_string(),
+                Ok(false) => "Already remembered".to_string(),
+                Err(e) => format!("Unable to remember. Error: {e}"),
+            };
+
+            ArtifactResult::new(output)
+        }
         other => {
             error!("partial artifact type not executable: {other}");
-            None
+            return None;
+        }
+    };
+
+    Some(result)
+}
+
+/// Execute the artifacts of a `partial` response, see
+/// [`execute_partial_artifact`], and gather their results for the model, each
+/// in a `<result>` block naming the artifact it comes from. `None` when none
+/// of them is executable.
+pub fn execute_partial_artifacts(
+    artifacts: &[AdoDataArtifact],
+    ui: &dyn AgenticUi,
+    session: &Session,
+) -> Option<String> {
+    let blocks: Vec<String> = artifacts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, artifact)| {
+            execute_partial_artifact(artifact, ui, session)
+                .map(|r| r.block(i.saturating_add(1), &artifact.artifact_type))
+        })
+        .collect();
+
+    if blocks.is_empty() {
+        None
+    } else {
+        Some(blocks.join("\n\n"))
+    }
+}
+
+/// Execute the steps planned in `session` now that the user approved them,
+/// emptying the plan. Returns their results for the model, as
+/// [`execute_partial_artifacts`] does.
+pub fn execute_plan(ui: &dyn AgenticUi, session: &Session) -> Option<String> {
+    let plan = session.take_plan();
+
+    let blocks: Vec<String> = plan
+        .iter()
+        .enumerate()
+        .filter_map(|(i, artifact)| {
+            ui.notify(&format!(
+                "step {}: {}",
+                i.saturating_add(1),
+                describe_step(artifact)
+            ));
+            execute_artifact(artifact, ui, session)
+                .map(|r| r.block(i.saturating_add(1), &artifact.artifact_type))
+        })
+        .collect();
+
+    if blocks.is_empty() {
+        None
+    } else {
+        Some(blocks.join("\n\n"))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestUi;
+
+    impl AgenticUi for TestUi {
+        fn notify(&self, _text: &str) {}
+        fn show_diff(&self, _diff: &str) {}
+        fn confirm(&self, _question: &str) -> bool {
+            true
+        }
+    }
+
+    fn diff_artifact(content: &str) -> AdoDataArtifact {
+        AdoDataArtifact {
+            artifact_type: AdoDataArtifactType::Diff,
+            language: None,
+            path: None,
+            content: content.to_string(),
+            old: None,
+            occurrence: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_diff_same_file_twice() {
+        let td = tempfile::tempdir().unwrap();
+        fs::write(td.path().join("f"), "one\ntwo\nthree\n").unwrap();
+
+        let session = Session::new();
+        session.change_dir(td.path()).unwrap();
+
+        let diff = "--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n-one\n+ONE\n two\n\
+                    --- a/f\n+++ b/f\n@@ -2,2 +2,2 @@\n two\n-three\n+THREE\n";
+
+        let result = apply_diff(&diff_artifact(diff), &TestUi, &session);
+
+        assert!(result.ends_with("f: 2 hunks applied"), "{result}");
+        assert_eq!(
+            fs::read_to_string(td.path().join("f")).unwrap(),
+            "ONE\ntwo\nTHREE\n"
+        );
+        assert_eq!(session.undoable(), 1);
+
+        // the second patch doesn't apply on top of the first
+        let diff = "--- a/f\n+++ b/f\n@@ -1 +1 @@\n-ONE\n+one\n\
+                    --- a/f\n+++ b/f\n@@ -1 +1 @@\n-ONE\n+1\n";
+
+        let result = apply_diff(&diff_artifact(diff), &TestUi, &session);
+
+        assert!(result.starts_with("The diff was not applied"), "{result}");
+        assert_eq!(
+            fs::read_to_string(td.path().join("f")).unwrap(),
+            "ONE\ntwo\nTHREE\n"
+        );
+    }
+
+    #[test]
+    fn test_handler_command_unsupported_option() {
+        let td = tempfile::tempdir().unwrap();
+        fs::write(td.path().join("a.txt"), "a").unwrap();
+
+        let session = Session::new();
+        session.change_dir(td.path()).unwrap();
+
+        let out = handler_command("ls -a", &TestUi, &session).unwrap();
+        assert_eq!(out.mode, CommandMode::BuiltIn);
+
+        // the host's ls
+        let out = handler_command("ls -R", &TestUi, &session).unwrap();
+        assert_eq!(out.mode, CommandMode::Exec);
+        assert!(out.output.contains("a.txt"));
+    }
+
+    #[test]
+    fn test_needs_shell() {
+        for line in [
+            "ls -la",
+            "grep \"fn main$\" src",
+            "echo '$HOME | x'",
+            "git log --format=%h",
+            "rg 'a;b'",
+            "rg -g '*.rs' foo",
+            "grep \"a*b\" src",
+            "ls a~b",
+        ] {
+            assert!(!needs_shell(line), "{line}");
+        }
+
+        for line in [
+            "ls | wc -l",
+            "cargo build && cargo test",
+            "echo a; echo b",
+            "cat < file",
+            "echo a > file",
+            "echo $HOME",
+            "echo \"$(pwd)\"",
+            "echo `pwd`",
+            "(cd src)",
+            "echo a\necho b",
+            "cat src/*.rs",
+            "ls *.toml",
+            "ls file?.txt",
+            "ls [ab].rs",
+            "cd ~/x",
+            "ls ~",
+        ] {
+            assert!(needs_shell(line), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_is_read_only_command() {
+        for line in [
+            "",
+            "cd src",
+            "ls -lh",
+            "grep -rn foo src",
+            "cat -n src/main.rs",
+            "head -n 20 Cargo.toml",
+            "head -5 Cargo.toml",
+            "tail -n5 Cargo.toml",
+            "du -sh target",
+            "rg -i -g '*.rs' foo",
+            "rg -C3 foo -- --pre",
+            "tree -L 2 src",
+            "file -b Cargo.toml",
+            "git status -s",
+            "git diff --staged --stat",
+            "git log --oneline -n 5",
+            "git log --format=%h",
+            "git grep -n foo",
+            "git blame -L10,20 src/main.rs",
+        ] {
+            assert!(is_read_only_command(line), "{line}");
+        }
+
+        for line in [
+            "rm -rf target",
+            "cargo build",
+            "git commit -m x",
+            "git",
+            "git -c core.pager=sh log",
+            "cat a > b",
+            "rg --pre sh foo",
+            "rg --pre=sh foo",
+            "rg -z foo",
+            "tree -o out",
+            "tree -ao out",
+            "git diff --output=f",
+            "git log --output f",
+            "git grep -Osh foo",
+            "git grep --open-files-in-pager foo",
+            "file -C",
+            "date -s 2024-01-01",
+            "tail -f log",
+        ] {
+            assert!(!is_read_only_command(line), "{line}");
         }
     }
 }
@@ -0,0 +1,347 @@
+//! `@path` references in a prompt, as completed by `AdoCompleter`. Each one is
+//! attached after the prompt:
+//!
+//! - `@src/main.rs` the content of the file
+//! - `@src/` a listing of the directory
+//! - `@src/**/*.rs` the content of every matching file
+//! - `@screenshot.png`, `@spec.pdf` images and PDFs, sent to the model as
+//!   such rather than as text
+//!
+//! Words starting with `@` that aren't paths, e.g. `@alice`, are left alone.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use adolib::{
+    error::Error,
+    llm::attachment::{self, LLMAttachment, MAX_DOCUMENT_SIZE},
+};
+use anyhow::{Context, Result, bail};
+use glob::MatchOptions;
+use log::info;
+
+/// Largest file that can be attached
+const MAX_FILE_SIZE: u64 = 256 * 1024;
+/// Largest total of the attached files of one prompt
+const MAX_TOTAL_SIZE: u64 = 1024 * 1024;
+/// Most files a glob can attach
+const MAX_GLOB_FILES: usize = 64;
+/// Largest total of the images and PDFs of one prompt
+const MAX_TOTAL_MEDIA_SIZE: u64 = MAX_DOCUMENT_SIZE;
+
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', ')', '"', '\''];
+
+enum Attachment {
+    File {
+        path: PathBuf,
+        content: String,
+    },
+    Dir {
+        path: PathBuf,
+        entries: Vec<String>,
+    },
+    Media {
+        path: PathBuf,
+        attachment: LLMAttachment,
+    },
+}
+
+fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// The `@` references of `input`, without the `@`
+fn references(input: &str) -> Vec<&str> {
+    input
+        .split_whitespace()
+        .filter_map(|w| w.strip_prefix('@'))
+        .filter_map(|r| {
+            if Path::new(r).exists() {
+                return Some(r);
+            }
+
+            // "look at @src/main.rs." or "ask @bob?" end a sentence, not the
+            // path
+            let r = r.trim_end_matches(TRAILING_PUNCTUATION);
+
+            (is_glob(r) || Path::new(r).exists()).then_some(r)
+        })
+        .filter(|r| !r.is_empty())
+        .collect()
+}
+
+fn read_file(path: &Path) -> Result<Attachment> {
+    if attachment::detect_media_type(path)
+        .with_context(|| format!("Unable to read {}", path.display()))?
+        .is_some()
+    {
+        let attachment = LLMAttachment::from_path(path)
+            .with_context(|| format!("Unable to attach {}", path.display()))?;
+
+        return Ok(Attachment::Media {
+            path: path.to_path_buf(),
+            attachment,
+        });
+    }
+
+    let size = fs::metadata(path)
+        .with_context(|| format!("Unable to read {}", path.display()))?
+        .len();
+
+    if size > MAX_FILE_SIZE {
+        return Err(Error::FileTooLarge {
+            size,
+            limit: MAX_FILE_SIZE,
+        })
+        .with_context(|| format!("{} is too large to attach", path.display()));
+    }
+
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Unable to attach {}", path.display()))?;
+
+    Ok(Attachment::File {
+        path: path.to_path_buf(),
+        content,
+    })
+}
+
+fn read_dir(path: &Path) -> Result<Attachment> {
+    let mut entries = Vec::new();
+
+    for entry in fs::read_dir(path).with_context(|| format!("Unable to list {}", path.display()))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if name.starts_with('.') {
+            continue;
+        }
+
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            entries.push(format!("{name}/"));
+        } else {
+            entries.push(name);
+        }
+    }
+
+    entries.sort();
+
+    Ok(Attachment::Dir {
+        path: path.to_path_buf(),
+        entries,
+    })
+}
+
+/// The files matching `pattern`, none when it isn't a valid one: like a
+/// reference to a missing file, it is then left as text
+fn glob_files(pattern: &str) -> Result<Vec<PathBuf>> {
+    // `**` shouldn't wander into .git and friends
+    let options = MatchOptions {
+        require_literal_leading_dot: true,
+        ..MatchOptions::new()
+    };
+
+    let files: Vec<PathBuf> = match glob::glob_with(pattern, options) {
+        Ok(paths) => paths.flatten().filter(|p| p.is_file()).collect(),
+        Err(e) => {
+            info!("@{pattern} isn't a pattern ({e})");
+            return Ok(Vec::new());
+        }
+    };
+
+    if files.is_empty() {
+        info!("@{pattern} doesn't match any file");
+    }
+
+    if files.len() > MAX_GLOB_FILES {
+        bail!(
+            "@{pattern} matches {} files, at most {MAX_GLOB_FILES} can be attached",
+            files.len()
+        );
+    }
+
+    Ok(files)
+}
+
+fn attachments(input: &str) -> Result<Vec<Attachment>> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    for reference in references(input) {
+        if is_glob(reference) {
+            paths.extend(glob_files(reference)?);
+        } else {
+            paths.push(PathBuf::from(reference));
+        }
+    }
+
+    let mut attachments = Vec::new();
+    let mut total: u64 = 0;
+    let mut total_media: u64 = 0;
+
+    for (i, path) in paths.iter().enumerate() {
+        // the same file referenced twice or by overlapping globs
+        if paths.get(..i).is_some_and(|before| before.contains(path)) {
+            continue;
+        }
+
+        info!("attaching {}", path.display());
+
+        let attachment = if path.is_dir() {
+            read_dir(path)?
+        } else {
+            read_file(path)?
+        };
+
+        match &attachment {
+            Attachment::File { content, .. } => {
+                total = total.saturating_add(content.len() as u64);
+
+                if total > MAX_TOTAL_SIZE {
+                    return Err(Error::FileTooLarge {
+                        size: total,
+                        limit: MAX_TOTAL_SIZE,
+                    })
+                    .context("Attachments are too large");
+                }
+            }
+            Attachment::Media { attachment, .. } => {
+                total_media = total_media.saturating_add(attachment.size());
+
+                if total_media > MAX_TOTAL_MEDIA_SIZE {
+                    return Err(Error::FileTooLarge {
+                        size: total_media,
+                        limit: MAX_TOTAL_MEDIA_SIZE,
+                    })
+                    .context("Images and documents are too large");
+                }
+            }
+            Attachment::Dir { .. } => {}
+        }
+
+        attachments.push(attachment);
+    }
+
+    Ok(attachments)
+}
+
+/// A code fence longer than any backtick run of `content`
+fn fence(content: &str) -> String {
+    let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or_default();
+    "`".repeat(longest.saturating_add(1).max(3))
+}
+
+/// `input` followed by the content of the files and directories it
+/// references, and the images and PDFs to send along
+pub fn expand(input: &str) -> Result<(String, Vec<LLMAttachment>)> {
+    let attachments = attachments(input)?;
+
+    if attachments.is_empty() {
+        return Ok((input.to_string(), Vec::new()));
+    }
+
+    let mut media = Vec::new();
+
+    let mut lines = vec![input.to_string(), String::new(), "# Attachments".to_string()];
+
+    for attachment in attachments {
+        lines.push(String::new());
+
+        match attachment {
+            Attachment::File { path, content } => {
+                let fence = fence(&content);
+                let language = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+
+                lines.push(format!("## File {}", path.display()));
+                lines.push(String::new());
+                lines.push(format!("{fence}{language}"));
+                lines.push(content.trim_end().to_string());
+                lines.push(fence);
+            }
+            Attachment::Dir { path, entries } => {
+                lines.push(format!("## Directory {}", path.display()));
+                lines.push(String::new());
+                lines.extend(entries.iter().map(|e| format!("- {e}")));
+            }
+            Attachment::Media { path, attachment } => {
+                let kind = if attachment.is_image() {
+                    "Image"
+                } else {
+                    "Document"
+                };
+
+                lines.push(format!("## {kind} {}", path.display()));
+                lines.push(String::new());
+                lines.push(format!("Attached as {}", attachment.media_type));
+
+                media.push(attachment);
+            }
+        }
+    }
+
+    Ok((lines.join("\n"), media))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references() {
+        // run from the crate directory
+        assert_eq!(references("ask @bob? about it"), Vec::<&str>::new());
+        assert_eq!(references("@alice, look at this"), Vec::<&str>::new());
+        assert_eq!(references("look at @Cargo.toml."), vec!["Cargo.toml"]);
+        assert_eq!(references("@src/*.rs and @src/"), vec!["src/*.rs", "src/"]);
+        assert_eq!(references("@missing.rs"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_expand_file() {
+        let td = tempfile::tempdir().unwrap();
+        let path = td.path().join("notes.md");
+        fs::write(&path, "some notes").unwrap();
+
+        let input = format!("summarize @{}", path.display());
+        let (expanded, media) = expand(&input).unwrap();
+
+        assert!(media.is_empty());
+        assert!(expanded.starts_with(&input));
+        assert!(expanded.contains(&format!("## File {}", path.display())));
+        assert!(expanded.contains("some notes"));
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let td = tempfile::tempdir().unwrap();
+        fs::write(td.path().join("a.rs"), "fn a() {}").unwrap();
+        fs::write(td.path().join("b.rs"), "fn b() {}").unwrap();
+        fs::write(td.path().join("c.txt"), "not rust").unwrap();
+
+        let (expanded, _) = expand(&format!("review @{}/*.rs", td.path().display())).unwrap();
+
+        assert!(expanded.contains("fn a() {}"));
+        assert!(expanded.contains("fn b() {}"));
+        assert!(!expanded.contains("not rust"));
+    }
+
+    #[test]
+    fn test_expand_left_as_text() {
+        let td = tempfile::tempdir().unwrap();
+
+        for input in [
+            "ask @bob?".to_string(),
+            format!("look at @{}/missing.rs", td.path().display()),
+            format!("look at @{}/*.rs", td.path().display()),
+        ] {
+            let (expanded, media) = expand(&input).unwrap();
+
+            assert_eq!(expanded, input);
+            assert!(media.is_empty());
+        }
+    }
+}
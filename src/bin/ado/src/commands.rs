@@ -4,30 +4,66 @@ use std::{
     fmt::Display,
     fs,
     io::{self, Write},
-    path::{Path, PathBuf},
+    path::PathBuf,
+    time::{Duration, SystemTime},
 };
 
 use adolib::{
     cache::kv::KVCache,
     config::loader::AdoConfig,
     console::ConsoleTrait,
-    llm::chain::{LLMChain, LLMRole},
+    llm::{
+        chain::{LLMChain, LLMRole},
+        message_cache::LLMMessageCache,
+    },
     search::{SearchTrait, WebSearch},
 };
 use anyhow::{Context, Result, bail};
-use log::{error, info};
-
-use crate::{intrinsics::IntrinsicPrompts, sub_commands::reddit::CommandReddit};
+use log::{error, info, warn};
+
+use crate::{
+    ado_md, attachments, custom_commands,
+    intrinsics::IntrinsicPrompts,
+    memories::Memories,
+    plugins,
+    session::Session,
+    skills,
+    sub_commands::{
+        cache::CommandCache,
+        checkpoints::{CommandCheckpoints, CommandRewind},
+        config::CommandConfig,
+        custom::CommandTemplate,
+        git::{CommandBlame, CommandCommit, CommandDiff},
+        memories::{CommandForget, CommandMemories, CommandRemember},
+        plan::CommandPlan,
+        plugin::CommandPlugin,
+        profile::CommandProfile,
+        reddit::CommandReddit,
+        reload::CommandReload,
+        undo::CommandUndo,
+    },
+};
 
 pub struct UserCommands<'a> {
     chain: LLMChain,
     commands: Vec<Box<dyn UserCommansTrait + 'a>>,
+    config: AdoConfig,
+    cache: &'a KVCache,
+    session: &'a Session,
+    watch: Option<Watch>,
 }
 
 pub trait UserCommansTrait: Send {
-    fn name(&self) -> &'static str;
-    fn desc(&self) -> &'static str;
+    fn name(&self) -> &str;
+    fn desc(&self) -> &str;
     fn callback(&mut self, input: &str, chain: &mut LLMChain, console: &dyn ConsoleTrait);
+
+    /// Configuration to switch to once `callback` returns, e.g. after
+    /// `/profile work`. Commands and chain are rebuilt from it, keeping the
+    /// conversation.
+    fn take_config(&mut self) -> Option<AdoConfig> {
+        None
+    }
 }
 
 struct CommandHelp {
@@ -37,6 +73,10 @@ struct CommandModels;
 struct CommandReset;
 struct CommandModel;
 struct CommandSkills;
+struct CommandMemory;
+struct CommandSkill<'a> {
+    session: &'a Session,
+}
 struct CommandSearch<'a> {
     gcse: WebSearch<'a>,
 }
@@ -193,39 +233,104 @@ impl UserCommansTrait for CommandSkills {
 
         output.push("# Skills".to_string());
 
-        for dir in skills_dirs() {
-            output.push(format!("\n## {}", dir.display()));
+        let dirs = skills::skills_dirs();
 
+        for dir in &dirs {
             if !dir.exists() {
-                output.push("_directory does not exist_".to_string());
-                continue;
+                output.push(format!("* {} _does not exist_", dir.display()));
             }
+        }
 
-            let glob_pattern = format!("{}/*.md", dir.display());
+        let skills = skills::discover();
 
-            let mut entries: Vec<PathBuf> = match glob::glob(&glob_pattern) {
-                Ok(paths) => paths.flatten().collect(),
-                Err(e) => {
-                    error!("Unable to list skills in {} ({e})", dir.display());
-                    output.push(format!("_unable to list skills ({e})_"));
-                    continue;
-                }
-            };
+        if skills.is_empty() {
+            output.push("\n_no skills_".to_string());
+        }
 
-            if entries.is_empty() {
-                output.push("_no skills_".to_string());
-                continue;
+        for skill in skills {
+            output.push(format!("\n## {}\n{}", skill.name, skill.description));
+            output.push(format!("* path: {}", skill.path.display()));
+
+            if !skill.triggers.is_empty() {
+                output.push(format!("* triggers: {}", skill.triggers.join(", ")));
+            }
+
+            if !skill.suggested_tools.is_empty() {
+                output.push(format!(
+                    "* suggested tools: {}",
+                    skill.suggested_tools.join(", ")
+                ));
             }
+        }
+
+        console.print_markdown(&output.join("\n"));
+    }
+}
 
-            entries.sort();
+impl UserCommansTrait for CommandSkill<'_> {
+    fn name(&self) -> &'static str {
+        "skill <name> [args]"
+    }
 
-            for entry in entries {
-                let name = entry
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("<unknown>");
+    fn desc(&self) -> &'static str {
+        "run a skill"
+    }
+
+    fn callback(&mut self, input: &str, chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        let input = input.trim();
+        let (name, args) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+
+        if name.is_empty() {
+            console.error_message("usage: /skill <name> [args]. See /skills");
+            return;
+        }
 
-                output.push(format!("* {name}"));
+        let Some(skill) = skills::find(name) else {
+            console.error_message(&format!("{name} does not exist. See /skills"));
+            return;
+        };
+
+        self.session.begin_turn(&format!("/skill {input}"), chain.turns().len());
+
+        if let Err(e) = chain.link(skill.render(args), console) {
+            error!("skill {name} failure ({e})");
+            console.error_message(&format!("{e}"));
+        }
+    }
+}
+
+impl UserCommansTrait for CommandMemory {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn desc(&self) -> &'static str {
+        "list the active ADO.md instruction files"
+    }
+
+    fn callback(&mut self, _input: &str, _chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        let mut output = vec!["# ADO.md".to_string(), String::new()];
+
+        let files = ado_md::discover();
+
+        if files.is_empty() {
+            output.push("_no ADO.md found_".to_string());
+        }
+
+        for ado_md in files {
+            match ado_md {
+                Ok(ado_md) => {
+                    output.push(format!("* {}", ado_md.path.display()));
+
+                    for include in &ado_md.includes {
+                        output.push(format!("  * {}", include.display()));
+                    }
+
+                    for skipped in &ado_md.skipped {
+                        output.push(format!("  * _skipped: {skipped}_"));
+                    }
+                }
+                Err(e) => output.push(format!("* _{e:#}_")),
             }
         }
 
@@ -258,7 +363,7 @@ impl UserCommansTrait for CommandHelp {
         lines.push(String::new());
         lines.push("## Completion".into());
         lines.push("* Type `/` to trigger command completion".into());
-        lines.push("* Type `@path` to attach a file (Tab to browse)".into());
+        lines.push("* Type `@path` to attach a file, an image, a PDF, a directory listing or a glob like `@src/**/*.rs` (Tab to browse)".into());
 
         let md = lines.join("\n");
 
@@ -276,19 +381,31 @@ fn load_intrinsics(chain: &mut LLMChain) {
     }
 }
 
-fn load_ado_md(chain: &mut LLMChain) -> Result<()> {
-    let cwd = env::current_dir().context("Unable to get current directory")?;
-
-    let ado_md = cwd.join("ADO.md");
-
-    if ado_md.exists() {
-        info!("reading {}", ado_md.display());
-        let data = fs::read_to_string(&ado_md)
-            .with_context(|| format!("Unable to read {}", ado_md.display()))?;
-        chain.add_content(LLMRole::System, data);
+fn load_ado_md(chain: &mut LLMChain) {
+    for ado_md in ado_md::discover() {
+        match ado_md {
+            Ok(ado_md) => {
+                info!("reading {}", ado_md.path.display());
+
+                // the location tells which part of a monorepo it applies to
+                let prompt = format!(
+                    "Instructions from {}:\n\n{}",
+                    ado_md.path.display(),
+                    ado_md.content
+                );
+                chain.add_content(LLMRole::System, prompt);
+            }
+            Err(e) => error!("Unable to load ADO.md ({e:#})"),
+        }
     }
+}
 
-    Ok(())
+fn load_memories(chain: &mut LLMChain) {
+    match Memories::from_default().and_then(|m| m.system_prompt()) {
+        Ok(Some(prompt)) => chain.add_content(LLMRole::System, prompt),
+        Ok(None) => {}
+        Err(e) => error!("Unable to load memories ({e:#})"),
+    }
 }
 
 fn load_useful(chain: &mut LLMChain) {
@@ -301,83 +418,137 @@ fn load_useful(chain: &mut LLMChain) {
     chain.add_content(LLMRole::User, current_os);
 }
 
-fn load_skills_from_path(chain: &mut LLMChain, path: &Path) -> Result<()> {
-    info!("Loading skills from {}", path.display());
+fn load_skills(chain: &mut LLMChain) {
+    if let Some(prompt) = skills::system_prompt(&skills::discover()) {
+        chain.add_content(LLMRole::System, prompt);
+    }
+}
 
-    let glob_pattern = format!("{}/*.md", path.display());
+/// Chain with the system prompts only, no conversation
+fn init_system_chain(config: &AdoConfig) -> Result<LLMChain> {
+    let mut chain = LLMChain::new(config)?;
 
-    for md_file in glob::glob(&glob_pattern)?.flatten() {
-        if let Ok(data) = fs::read_to_string(md_file) {
-            chain.add_content(LLMRole::System, data);
-        }
-    }
+    load_intrinsics(&mut chain);
 
-    Ok(())
+    load_ado_md(&mut chain);
+
+    load_skills(&mut chain);
+
+    load_memories(&mut chain);
+
+    Ok(chain)
 }
 
-fn skills_dirs() -> Vec<PathBuf> {
-    let mut skills_dirs = Vec::new();
+fn init_chain(config: &AdoConfig) -> Result<LLMChain> {
+    let mut chain = init_system_chain(config)?;
+
+    load_useful(&mut chain);
 
-    //
-    // Skills in the user directory
-    //
-    if let Some(config_dir) = dirs::config_dir() {
-        let config_dir = config_dir.join("ado");
-        let skills_dir = config_dir.join("skills");
+    Ok(chain)
+}
 
-        skills_dirs.push(skills_dir);
+/// Model a chain built from `config` starts with
+fn configured_model(config: &AdoConfig) -> Option<&str> {
+    match config.llm_provider() {
+        "ollama" => config.ollama().ok().map(|o| o.model.as_str()),
+        "claude" => config.claude().ok().map(|c| c.model.as_str()),
+        _ => None,
     }
+}
 
-    //
-    // Skills in the current directory
-    //
-    if let Ok(cwd) = env::current_dir() {
-        let skills_dir = cwd.join("skills");
-        skills_dirs.push(skills_dir);
+/// Files the chain and the commands are built from
+fn watched_files(config: &AdoConfig) -> Vec<PathBuf> {
+    let mut files = config.files().to_vec();
+
+    files.extend(ado_md::candidates());
+
+    if let Ok(memories) = Memories::from_default() {
+        files.push(memories.path().clone());
     }
 
-    skills_dirs
-}
+    for ado_md in ado_md::discover().into_iter().flatten() {
+        files.extend(ado_md.includes);
+    }
 
-fn load_skills(chain: &mut LLMChain) {
-    for dir in skills_dirs() {
-        if !dir.exists() {
-            info!("{} doesn't exist", dir.display());
-            continue;
+    for dir in skills::skills_dirs() {
+        if let Ok(paths) = skills::skill_files(&dir) {
+            files.extend(paths);
         }
 
-        if let Err(e) = load_skills_from_path(chain, &dir) {
-            error!("Unable to load skills in {} ({e})", dir.display());
-        }
+        // catches skills being added or removed
+        files.push(dir);
     }
-}
 
-fn init_chain(config: &AdoConfig) -> Result<LLMChain> {
-    let mut chain = LLMChain::new(config)?;
+    files
+}
 
-    load_intrinsics(&mut chain);
+/// Modification time of each watched file, `None` when missing
+fn fingerprint(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files.iter().map(|f| fs::metadata(f).and_then(|m| m.modified()).ok()).collect()
+}
 
-    if let Err(e) = load_ado_md(&mut chain) {
-        error!("Unable to load ADO.md files ({e})");
-    }
+/// `--watch`: reload before handling input when any watched file changed
+struct Watch {
+    files: Vec<PathBuf>,
+    fingerprint: Vec<Option<SystemTime>>,
+}
 
-    load_useful(&mut chain);
+impl Watch {
+    fn new(config: &AdoConfig) -> Self {
+        let files = watched_files(config);
+        let fingerprint = fingerprint(&files);
 
-    load_skills(&mut chain);
+        Self { files, fingerprint }
+    }
 
-    Ok(chain)
+    fn changed(&self, config: &AdoConfig) -> bool {
+        let files = watched_files(config);
+        files != self.files || fingerprint(&files) != self.fingerprint
+    }
 }
 
 impl<'a> UserCommands<'a> {
-    pub fn new(config: &AdoConfig, cache: &'a KVCache) -> Result<Self> {
+    pub fn new(config: &AdoConfig, cache: &'a KVCache, session: &'a Session) -> Result<Self> {
         let chain = init_chain(config).context("Unable to initialize llm chain")?;
 
+        UserCommands::with_chain(config, cache, session, chain)
+    }
+
+    fn with_chain(
+        config: &AdoConfig,
+        cache: &'a KVCache,
+        session: &'a Session,
+        mut chain: LLMChain,
+    ) -> Result<Self> {
+        if let Some(message_cache) = config.message_cache() {
+            let ttl = Duration::from_secs(message_cache.ttl);
+            chain.set_message_cache(LLMMessageCache::new(cache.clone(), ttl));
+        }
+
+        session.set_agent(config.agent());
+
         let mut commands: Vec<Box<dyn UserCommansTrait + 'a>> = vec![
             Box::new(CommandModels {}),
             Box::new(CommandReset {}),
             Box::new(CommandModel {}),
             Box::new(CommandSkills {}),
+            Box::new(CommandSkill { session }),
+            Box::new(CommandMemory {}),
+            Box::new(CommandMemories {}),
+            Box::new(CommandRemember::new(config)),
+            Box::new(CommandForget::new(config)),
             Box::new(CommandReddit::new(config, cache)),
+            Box::new(CommandDiff::new(session)),
+            Box::new(CommandCommit::new(config, session)),
+            Box::new(CommandBlame::new(session)),
+            Box::new(CommandCache::new(cache)),
+            Box::new(CommandConfig::new(config)?),
+            Box::new(CommandProfile::new(config)),
+            Box::new(CommandReload::new(config)),
+            Box::new(CommandUndo::new(session)),
+            Box::new(CommandPlan::new(session)),
+            Box::new(CommandCheckpoints::new(session)),
+            Box::new(CommandRewind::new(session)),
         ];
 
         let mut help = CommandHelp::new();
@@ -386,13 +557,104 @@ impl<'a> UserCommands<'a> {
             commands.push(Box::new(search));
         }
 
+        for template in custom_commands::discover() {
+            let name = template.name.as_str();
+
+            if name == "help"
+                || commands.iter().any(|c| c.name().split_whitespace().next() == Some(name))
+            {
+                warn!(
+                    "{} ignored, /{name} is a built-in command",
+                    template.path.display()
+                );
+                continue;
+            }
+
+            commands.push(Box::new(CommandTemplate::new(template, session)));
+        }
+
+        for plugin in plugins::discover() {
+            let name = plugin.name.as_str();
+
+            if name == "help"
+                || commands.iter().any(|c| c.name().split_whitespace().next() == Some(name))
+            {
+                info!(
+                    "{} ignored, /{name} is already a command",
+                    plugin.path.display()
+                );
+                continue;
+            }
+
+            commands.push(Box::new(CommandPlugin::new(plugin, session)));
+        }
+
         for c in &commands {
             help.add(c.name(), c.desc());
         }
 
         commands.push(Box::new(help));
 
-        Ok(Self { chain, commands })
+        Ok(Self {
+            chain,
+            commands,
+            config: config.clone(),
+            cache,
+            session,
+            watch: None,
+        })
+    }
+
+    /// Reload whenever the config, ADO.md or a skill changes on disk
+    pub fn set_watch(&mut self, watch: bool) {
+        self.watch = watch.then(|| Watch::new(&self.config));
+    }
+
+    /// Rebuild the chain and the commands from `config`, re-reading ADO.md
+    /// and the skills. The conversation, the token usage and the model picked
+    /// with /model carry over.
+    fn reconfigure(&mut self, config: &AdoConfig) -> Result<()> {
+        let mut chain = init_system_chain(config).context("Unable to initialize llm chain")?;
+        chain.restore_turns(self.chain.turns());
+        chain.restore_usage(self.chain.usage());
+
+        // a /model switch stands unless the configuration picks another model
+        if config.llm_provider() == self.config.llm_provider()
+            && configured_model(config) == configured_model(&self.config)
+            && chain.model() != self.chain.model()
+            && let Err(e) = chain.change_model(self.chain.model())
+        {
+            error!("Unable to keep model {} ({e})", self.chain.model());
+        }
+
+        let watch = self.watch.is_some();
+
+        *self = UserCommands::with_chain(config, self.cache, self.session, chain)?;
+        self.set_watch(watch);
+
+        Ok(())
+    }
+
+    fn reload_if_changed<C>(&mut self, console: &C) -> Result<()>
+    where
+        C: ConsoleTrait + Send + Sync,
+    {
+        if !self.watch.as_ref().is_some_and(|w| w.changed(&self.config)) {
+            return Ok(());
+        }
+
+        info!("configuration changed on disk, reloading");
+
+        match self.config.reload() {
+            Ok(config) => self.reconfigure(&config),
+            Err(e) => {
+                // keep going with what we have, and don't report it again
+                // until the files change again
+                self.set_watch(true);
+                console.error_message(&format!("Unable to reload ({e})"));
+                Ok(())
+            }
+        }
     }
 
     #[must_use]
@@ -400,6 +662,16 @@ impl<'a> UserCommands<'a> {
         self.chain.model().to_string()
     }
 
+    #[must_use]
+    pub fn current_profile(&self) -> Option<&str> {
+        self.config.profile()
+    }
+
+    #[must_use]
+    pub fn session(&self) -> &'a Session {
+        self.session
+    }
+
     pub fn command_models<C>(&self, console: &C) -> Result<()>
     where
         C: ConsoleTrait + Send + Sync,
@@ -430,10 +702,20 @@ impl<'a> UserCommands<'a> {
     {
         info!("input: {input}");
 
+        self.reload_if_changed(console)?;
+
         if let Some(command) = input.as_ref().strip_prefix("/") {
+            let (name, args) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+
             for c in &mut self.commands {
-                if let Some(args) = command.strip_prefix(c.name()) {
+                // names may document their arguments, e.g. "model [name]"
+                if c.name().split_whitespace().next() == Some(name) {
                     c.callback(args, &mut self.chain, console);
+
+                    if let Some(config) = c.take_config() {
+                        self.reconfigure(&config)?;
+                    }
+
                     return Ok(());
                 }
             }
@@ -444,7 +726,9 @@ impl<'a> UserCommands<'a> {
         //
         // forward to
         //
-        self.chain.link(input.as_ref(), console)?;
+        let (message, media) = attachments::expand(input.as_ref())?;
+        self.session.begin_turn(input.as_ref(), self.chain.turns().len());
+        self.chain.link_with_attachments(message, &media, console)?;
         Ok(())
     }
 
@@ -0,0 +1,258 @@
+//! Custom slash commands are Markdown templates, `review.md` becoming
+//! `/review`. From the most general to the most specific:
+//!
+//! 1. `~/.config/ado/commands/*.md`
+//! 2. `.ado/commands/*.md` of the project, searched from the current directory
+//!    up to the git root
+//!
+//! A project template overrides a user one of the same name. The template is
+//! sent as a prompt, `$ARGUMENTS` replaced by what follows the command. Like
+//! skills, it may start with a YAML front-matter:
+//!
+//! ```markdown
+//! ---
+//! description: Review a module for error handling
+//! ---
+//! Review how errors are handled in $ARGUMENTS ...
+//! ```
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use adolib::config::layers::PROJECT_CONFIG_DIR;
+use anyhow::{Context, Result, bail};
+use log::{error, info};
+use serde::Deserialize;
+
+use crate::skills::{self, ARGUMENTS_PLACEHOLDER};
+
+const COMMANDS_DIR: &str = "commands";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TemplateFrontMatter {
+    name: Option<String>,
+    description: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    pub description: String,
+    pub path: PathBuf,
+    body: String,
+}
+
+impl Template {
+    pub fn parse(path: &Path, text: &str) -> Result<Self> {
+        let (front_matter, body) = skills::split_front_matter(text);
+
+        let front_matter: TemplateFrontMatter = match front_matter {
+            Some(yaml) => serde_yaml_ng::from_str(yaml)
+                .with_context(|| format!("Invalid front-matter in {}", path.display()))?,
+            None => TemplateFrontMatter::default(),
+        };
+
+        let name = match front_matter.name {
+            Some(name) => name,
+            None => path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .with_context(|| format!("Unable to name command {}", path.display()))?
+                .to_string(),
+        };
+
+        // typed as `/name args`
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            bail!("Invalid command name \"{name}\" in {}", path.display());
+        }
+
+        let description = front_matter.description.unwrap_or_else(|| {
+            body.lines()
+                .map(|l| l.trim().trim_start_matches('#').trim())
+                .find(|l| !l.is_empty())
+                .unwrap_or_default()
+                .to_string()
+        });
+
+        Ok(Self {
+            name,
+            description,
+            path: path.to_path_buf(),
+            body: body.trim().to_string(),
+        })
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        Template::parse(path, &text)
+    }
+
+    /// The prompt, `args` replacing `$ARGUMENTS` or appended when the
+    /// template doesn't use it
+    #[must_use]
+    pub fn render(&self, args: &str) -> String {
+        let args = args.trim();
+
+        if self.body.contains(ARGUMENTS_PLACEHOLDER) {
+            return self.body.replace(ARGUMENTS_PLACEHOLDER, args);
+        }
+
+        if args.is_empty() {
+            self.body.clone()
+        } else {
+            format!("{}\n\n{args}", self.body)
+        }
+    }
+}
+
+/// Closest `.ado/commands` walking up from `start`, stopping at the git root.
+/// Outside a repository there are no project commands.
+fn project_commands_dir(start: &Path) -> Option<PathBuf> {
+    let root = start.ancestors().find(|dir| dir.join(".git").exists())?;
+
+    start
+        .ancestors()
+        .take_while(|dir| dir.starts_with(root))
+        .map(|dir| dir.join(PROJECT_CONFIG_DIR).join(COMMANDS_DIR))
+        .find(|candidate| candidate.is_dir())
+}
+
+/// Directories templates are loaded from, existing or not, the most general
+/// first
+#[must_use]
+pub fn commands_dirs() -> Vec<PathBuf> {
+    let mut commands_dirs = Vec::new();
+
+    if let Some(config_dir) = dirs::config_dir() {
+        commands_dirs.push(config_dir.join("ado").join(COMMANDS_DIR));
+    }
+
+    if let Ok(cwd) = env::current_dir()
+        && let Some(dir) = project_commands_dir(&cwd)
+    {
+        commands_dirs.push(dir);
+    }
+
+    commands_dirs
+}
+
+/// Every template, a project one overriding a user one of the same name.
+/// Invalid templates are logged and skipped.
+#[must_use]
+pub fn discover() -> Vec<Template> {
+    let mut templates: Vec<Template> = Vec::new();
+
+    for dir in commands_dirs() {
+        if !dir.exists() {
+            info!("{} doesn't exist", dir.display());
+            continue;
+        }
+
+        info!("Loading custom commands from {}", dir.display());
+
+        let files = match skills::skill_files(&dir) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Unable to list custom commands in {} ({e})", dir.display());
+                continue;
+            }
+        };
+
+        for file in files {
+            match Template::from_path(&file) {
+                Ok(template) => {
+                    templates.retain(|t| t.name != template.name);
+                    templates.push(template);
+                }
+                Err(e) => error!("{e:#}"),
+            }
+        }
+    }
+
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    templates
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let template = Template::parse(
+            Path::new(".ado/commands/review.md"),
+            "---\ndescription: Review a module\n---\nReview $ARGUMENTS\n",
+        )
+        .unwrap();
+
+        assert_eq!(template.name, "review");
+        assert_eq!(template.description, "Review a module");
+        assert_eq!(template.body, "Review $ARGUMENTS");
+
+        let template = Template::parse(
+            Path::new("fix.md"),
+            "---\nname: fix-tests\n---\n\n## Fix the failing tests\n\nRun them first.",
+        )
+        .unwrap();
+
+        assert_eq!(template.name, "fix-tests");
+        assert_eq!(template.description, "Fix the failing tests");
+
+        assert!(Template::parse(Path::new("a.md"), "---\nname: two words\n---\nbody").is_err());
+        assert!(Template::parse(Path::new("a.md"), "---\nname: [a\n---\nbody").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let template = Template::parse(
+            Path::new("review.md"),
+            "Review $ARGUMENTS, then $ARGUMENTS again",
+        )
+        .unwrap();
+
+        assert_eq!(
+            template.render(" src/main.rs "),
+            "Review src/main.rs, then src/main.rs again"
+        );
+
+        let template = Template::parse(Path::new("tests.md"), "Run the tests.\n").unwrap();
+
+        assert_eq!(template.render(""), "Run the tests.");
+        assert_eq!(
+            template.render("only the parser"),
+            "Run the tests.\n\nonly the parser"
+        );
+    }
+
+    #[test]
+    fn test_project_commands_dir() {
+        let td = tempfile::tempdir().unwrap();
+        let repo = td.path().join("repo");
+        let nested = repo.join("a");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(repo.join(".git")).unwrap();
+
+        assert_eq!(project_commands_dir(&nested), None);
+
+        let commands = repo.join(PROJECT_CONFIG_DIR).join(COMMANDS_DIR);
+        fs::create_dir_all(&commands).unwrap();
+        assert_eq!(project_commands_dir(&nested), Some(commands));
+
+        // above the git root
+        fs::create_dir_all(td.path().join(PROJECT_CONFIG_DIR).join(COMMANDS_DIR)).unwrap();
+        fs::remove_dir_all(repo.join(PROJECT_CONFIG_DIR)).unwrap();
+        assert_eq!(project_commands_dir(&nested), None);
+
+        // outside a repository
+        fs::remove_dir_all(repo.join(".git")).unwrap();
+        assert_eq!(project_commands_dir(&nested), None);
+    }
+}
@@ -5,10 +5,14 @@ use adolib::{
     data::types::{AdoData, AdoDataStatus},
 };
 use anyhow::Result;
-use log::error;
+use log::{error, info};
 use serde::Serialize;
 
-use crate::{agentic, commands::UserCommands};
+use crate::{
+    agentic::{self, AgenticUi},
+    commands::UserCommands,
+    session::Session,
+};
 
 /// Newline-delimited JSON protocol spoken over stdout in headless mode.
 ///
@@ -38,6 +42,10 @@ enum HeadlessMessage<'a> {
     Action {
         text: &'a str,
     },
+    /// A unified diff about to be applied.
+    Diff {
+        text: &'a str,
+    },
     /// An error message.
     Error {
         message: &'a str,
@@ -62,9 +70,29 @@ impl HeadlessMessage<'_> {
     }
 }
 
-struct HeadlessConsole {}
+struct HeadlessConsole<'a> {
+    session: &'a Session,
+}
 
-impl ConsoleTrait for HeadlessConsole {
+impl AgenticUi for HeadlessConsole<'_> {
+    fn notify(&self, text: &str) {
+        HeadlessMessage::Action { text }.emit();
+    }
+
+    fn show_diff(&self, diff: &str) {
+        HeadlessMessage::Diff { text: diff }.emit();
+    }
+
+    /// stdin carries prompts, not answers: like commands, diffs are applied
+    /// and shell lines run under `ShellPolicy::Ask` without asking, the
+    /// container being the sandbox
+    fn confirm(&self, question: &str) -> bool {
+        info!("headless, auto-approving: {question}");
+        true
+    }
+}
+
+impl ConsoleTrait for HeadlessConsole<'_> {
     fn error_message(&self, message: &str) {
         HeadlessMessage::Error { message }.emit();
     }
@@ -76,18 +104,8 @@ impl ConsoleTrait for HeadlessConsole {
         // execute inside the container; run them and feed the results back so
         // the agentic loop continues.
         if matches!(data.meta.status, AdoDataStatus::Partial) {
-            let mut results = Vec::new();
-            if let Some(artifacts) = &data.response.artifacts {
-                for artifact in artifacts {
-                    let notify = |text: &str| HeadlessMessage::Action { text }.emit();
-                    if let Some(r) = agentic::execute_partial_artifact(artifact, &notify) {
-                        results.push(r);
-                    }
-                }
-            }
-            if !results.is_empty() {
-                return Some(results.join(" "));
-            }
+            let artifacts = data.response.artifacts.as_deref().unwrap_or_default();
+            return agentic::execute_partial_artifacts(artifacts, self, self.session);
         }
 
         None
@@ -106,7 +124,9 @@ impl ConsoleTrait for HeadlessConsole {
 }
 
 pub fn headless_run(mut commands: UserCommands) -> Result<()> {
-    let console = HeadlessConsole {};
+    let console = HeadlessConsole {
+        session: commands.session(),
+    };
     let stdin = io::stdin();
 
     HeadlessMessage::Version {
@@ -1,4 +1,4 @@
-use std::{borrow::Cow, env, fs, path::Path};
+use std::{borrow::Cow, collections::HashMap, env, fs, path::Path};
 
 use anyhow::Result;
 use crossterm::style::Stylize;
@@ -20,24 +20,29 @@ pub enum InputResult {
 
 pub struct AdoCompleter {
     commands: Vec<String>,
+    /// of the commands `command_description` doesn't know, e.g. custom ones
+    descriptions: HashMap<String, String>,
 }
 
 impl AdoCompleter {
+    /// `commands` as names, possibly documenting their arguments like
+    /// "model [name]", and descriptions
     #[must_use]
-    pub fn new(commands: Vec<String>) -> Self {
-        Self { commands }
-    }
-}
+    pub fn new(commands: Vec<(String, String)>) -> Self {
+        let mut names = Vec::new();
+        let mut descriptions = HashMap::new();
+
+        for (name, desc) in commands {
+            let name = name.split_whitespace().next().unwrap_or_default().to_string();
+            names.push(name.clone());
+            descriptions.insert(name, desc);
+        }
 
-fn command_description(name: &str) -> Option<String> {
-    let desc = match name {
-        "help" => "Show available commands",
-        "model" => "Switch or show the current model",
-        "models" => "List all available models",
-        "reset" => "Clear the terminal screen",
-        _ => return None,
-    };
-    Some(desc.to_string())
+        Self {
+            commands: names,
+            descriptions,
+        }
+    }
 }
 
 impl Completer for AdoCompleter {
@@ -72,7 +77,7 @@ impl Completer for AdoCompleter {
                 return matches
                     .into_iter()
                     .map(|cmd| {
-                        let description = command_description(&cmd);
+                        let description = self.descriptions.get(&cmd).cloned();
                         Suggestion {
                             value: format!("/{cmd}"),
                             description,
@@ -218,7 +223,7 @@ fn find_matching_commands(partial: &str, commands: &[String]) -> Vec<String> {
         .collect()
 }
 
-fn build_editor(history_file: &Path, commands: Vec<String>) -> Result<Reedline> {
+fn build_editor(history_file: &Path, commands: Vec<(String, String)>) -> Result<Reedline> {
     let completer = Box::new(AdoCompleter::new(commands));
 
     let completion_menu = Box::new(
@@ -276,23 +281,34 @@ fn build_editor(history_file: &Path, commands: Vec<String>) -> Result<Reedline>
     Ok(editor)
 }
 
-pub fn create_editor(history_file: &Path, commands: Vec<String>) -> Result<Reedline> {
+pub fn create_editor(history_file: &Path, commands: Vec<(String, String)>) -> Result<Reedline> {
     build_editor(history_file, commands)
 }
 
 pub struct AdoPrompt {
     model: String,
+    profile: Option<String>,
 }
 
 impl AdoPrompt {
-    pub fn new(model: impl Into<String>) -> Self {
-        Self { model: model.into() }
+    pub fn new(model: impl Into<String>, profile: Option<&str>) -> Self {
+        Self {
+            model: model.into(),
+            profile: profile.map(ToString::to_string),
+        }
     }
 }
 
 impl Prompt for AdoPrompt {
     fn render_prompt_left(&self) -> Cow<'_, str> {
-        Cow::Owned(format!("{}", format!("[{}]", self.model).dark_grey()))
+        match &self.profile {
+            Some(profile) => Cow::Owned(format!(
+                "{} {}",
+                profile.as_str().cyan(),
+                format!("[{}]", self.model).dark_grey()
+            )),
+            None => Cow::Owned(format!("{}", format!("[{}]", self.model).dark_grey())),
+        }
     }
 
     fn render_prompt_right(&self) -> Cow<'_, str> {
@@ -315,8 +331,8 @@ impl Prompt for AdoPrompt {
     }
 }
 
-pub fn read_line(editor: &mut Reedline, model: &str) -> Result<InputResult> {
-    let prompt = AdoPrompt::new(model);
+pub fn read_line(editor: &mut Reedline, model: &str, profile: Option<&str>) -> Result<InputResult> {
+    let prompt = AdoPrompt::new(model, profile);
     match editor.read_line(&prompt) {
         Ok(Signal::Success(line)) => Ok(InputResult::Line(line)),
         Ok(_) => Ok(InputResult::Eof),
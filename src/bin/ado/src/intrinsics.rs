@@ -3,3 +3,9 @@ use rust_embed::Embed;
 #[derive(Embed)]
 #[folder = "prompts/"]
 pub struct IntrinsicPrompts;
+
+/// Prompts of the built-in commands, sent along their requests rather than as
+/// system prompts of the conversation
+#[derive(Embed)]
+#[folder = "command_prompts/"]
+pub struct CommandPrompts;
@@ -1,12 +1,19 @@
+pub mod ado_md;
 pub mod agentic;
+pub mod attachments;
 pub(crate) mod banner;
 pub mod commands;
+pub mod custom_commands;
 pub mod headless;
 pub mod input;
 pub mod intrinsics;
+pub mod memories;
+pub mod plugins;
+pub mod session;
+pub mod skills;
 pub mod spinner;
 pub mod sub_commands;
 pub mod terminal;
 pub mod tui_app;
 pub(crate) mod ui;
-
+pub mod wizard;
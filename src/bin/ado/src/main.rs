@@ -1,11 +1,40 @@
-use std::fs;
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::PathBuf,
+};
 
-use ado::{commands::UserCommands, headless::headless_run};
-use adolib::{cache::kv::KVCache, config::loader::AdoConfig, error::Error};
-use anyhow::{Context, Result};
-use clap::Parser;
+use ado::{commands::UserCommands, headless::headless_run, session::Session, wizard};
+use adolib::{
+    cache::kv::KVCache,
+    config::{
+        check::{check_config, check_layer_config},
+        layers::{SYSTEM_CONFIG_PATH, find_project_config},
+        loader::AdoConfig,
+    },
+    error::Error,
+};
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand};
 use log::LevelFilter;
 
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// validate the config files and report every problem found
+    Check,
+}
+
+#[derive(Subcommand, Debug)]
+enum AdoCommand {
+    /// configuration helpers
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+// command line flags are bools
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct UserArgs {
@@ -20,14 +49,95 @@ struct UserArgs {
     /// config file path
     #[arg(short, long)]
     config_file: Option<String>,
+
+    /// apply the [profile.<name>] section of the config
+    #[arg(short, long)]
+    profile: Option<String>,
+
+    /// reload when the config, ADO.md or skills change on disk
+    #[arg(long)]
+    watch: bool,
+
+    /// plan mode: the agent only reads, its changes wait for `/plan approve`
+    #[arg(long)]
+    plan: bool,
+
+    /// override a config value, e.g. --set llm.claude.model=claude-opus-4-1
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    set: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<AdoCommand>,
 }
 
-fn load_config_local(local_config: Option<&String>) -> Result<AdoConfig> {
-    match local_config {
-        Some(v) => AdoConfig::from_path(v),
-        None => AdoConfig::from_default(),
+fn load_config_local(args: &UserArgs, interactive: bool) -> Result<AdoConfig> {
+    let profile = args.profile.as_deref();
+
+    if let Some(v) = &args.config_file {
+        return AdoConfig::load(v, profile, &args.set).context("Unable to load local config");
+    }
+
+    let path = AdoConfig::default_path()?;
+
+    if !path.exists() && interactive {
+        wizard::run(&path)?;
+    }
+
+    AdoConfig::load(path, profile, &args.set).context("Unable to load local config")
+}
+
+/// Check every layer file: the system config, the user config and the
+/// project's, each issue labelled with its file
+fn config_check(local_config: Option<&String>) -> Result<()> {
+    let user = match local_config {
+        Some(v) => PathBuf::from(v),
+        None => AdoConfig::default_path()?,
+    };
+
+    // (path, only a layer of the configuration)
+    let mut files = Vec::new();
+
+    let system = PathBuf::from(SYSTEM_CONFIG_PATH);
+    if system.is_file() {
+        files.push((system, true));
+    }
+
+    files.push((user, false));
+
+    if let Some(project) = find_project_config(&env::current_dir()?) {
+        files.push((project, true));
+    }
+
+    let mut errors = 0usize;
+
+    for (path, layer) in &files {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+
+        let issues = if *layer {
+            check_layer_config(&data)
+        } else {
+            check_config(&data)
+        };
+
+        for issue in &issues {
+            println!("{}: {issue}", path.display());
+        }
+
+        let file_errors = issues.iter().filter(|i| i.is_error()).count();
+
+        if file_errors == 0 {
+            println!("{}: ok", path.display());
+        }
+
+        errors = errors.saturating_add(file_errors);
     }
-    .context("Unable to load local config")
+
+    if errors > 0 {
+        bail!("the configuration has {errors} error(s)");
+    }
+
+    Ok(())
 }
 
 fn init_logging(verbose: bool) {
@@ -44,10 +154,26 @@ fn main() -> Result<()> {
 
     init_logging(true);
 
-    let config = load_config_local(args.config_file.as_ref())?;
+    if let Some(AdoCommand::Config {
+        action: ConfigAction::Check,
+    }) = &args.command
+    {
+        return config_check(args.config_file.as_ref());
+    }
+
+    let interactive = !args.headless && io::stdin().is_terminal();
+
+    let config = load_config_local(&args, interactive)?;
     let cache = KVCache::default_path().context("Unable to initialize kv cache")?;
 
-    let commands = UserCommands::new(&config, &cache)?;
+    // drop expired entries without holding up startup
+    let _sweep = cache.sweep_in_background();
+
+    let session = Session::new();
+    session.set_plan_mode(args.plan);
+
+    let mut commands = UserCommands::new(&config, &cache, &session)?;
+    commands.set_watch(args.watch);
 
     if args.headless {
         // Headless has no config dir dependency: config comes from
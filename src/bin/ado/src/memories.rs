@@ -0,0 +1,208 @@
+//! Facts remembered across sessions, one Markdown bullet per fact in
+//! `~/.config/ado/memories.md` so they can be reviewed and edited by hand.
+//! Written by `/remember` and by the model with `memory` artifacts, injected
+//! in the system prompt of every new chain.
+
+use std::{fs, path::PathBuf};
+
+use adolib::error::Error;
+use anyhow::{Context, Result};
+
+const MEMORIES_FILE_NAME: &str = "memories.md";
+const MEMORIES_HEADER: &str = "# ado memories";
+const BULLET: &str = "- ";
+
+pub struct Memories {
+    path: PathBuf,
+}
+
+impl Memories {
+    #[must_use]
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn from_default() -> Result<Self> {
+        let config_dir = dirs::config_dir().ok_or(Error::ConfigNotFound)?;
+        Ok(Memories::new(
+            config_dir.join("ado").join(MEMORIES_FILE_NAME),
+        ))
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn list(&self) -> Result<Vec<String>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let data = fs::read_to_string(&self.path)
+            .with_context(|| format!("Unable to read {}", self.path.display()))?;
+
+        Ok(data
+            .lines()
+            .filter_map(|l| l.strip_prefix(BULLET))
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(ToString::to_string)
+            .collect())
+    }
+
+    fn write(&self, memories: &[String]) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut lines = vec![MEMORIES_HEADER.to_string(), String::new()];
+        lines.extend(memories.iter().map(|m| format!("{BULLET}{m}")));
+        lines.push(String::new());
+
+        fs::write(&self.path, lines.join("\n"))
+            .with_context(|| format!("Unable to write {}", self.path.display()))
+    }
+
+    /// Remember `fact`, returns `false` if it already was
+    pub fn add(&self, fact: &str) -> Result<bool> {
+        // one bullet per fact
+        let fact = fact.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        if fact.is_empty() {
+            return Ok(false);
+        }
+
+        let mut memories = self.list()?;
+
+        if memories.contains(&fact) {
+            return Ok(false);
+        }
+
+        memories.push(fact);
+        self.write(&memories)?;
+
+        Ok(true)
+    }
+
+    /// Forget by number as listed by `/memories`, or every memory containing
+    /// `query`, e.g. `2024` when there aren't that many memories. Returns
+    /// what was forgotten.
+    pub fn forget(&self, query: &str) -> Result<Vec<String>> {
+        let query = query.trim();
+
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let memories = self.list()?;
+
+        let number = query.parse::<usize>().ok().filter(|n| (1..=memories.len()).contains(n));
+
+        let mut forgotten = Vec::new();
+        let mut kept = Vec::new();
+
+        for (i, m) in memories.into_iter().enumerate() {
+            let matches = match number {
+                Some(n) => i.saturating_add(1) == n,
+                None => m.contains(query),
+            };
+
+            if matches {
+                forgotten.push(m);
+            } else {
+                kept.push(m);
+            }
+        }
+
+        if !forgotten.is_empty() {
+            self.write(&kept)?;
+        }
+
+        Ok(forgotten)
+    }
+
+    /// System prompt listing the memories, `None` when there are none
+    pub fn system_prompt(&self) -> Result<Option<String>> {
+        let memories = self.list()?;
+
+        if memories.is_empty() {
+            return Ok(None);
+        }
+
+        let mut lines = vec![
+            "# Memories".to_string(),
+            String::new(),
+            "Facts remembered from previous sessions:".to_string(),
+            String::new(),
+        ];
+        lines.extend(memories.iter().map(|m| format!("{BULLET}{m}")));
+
+        Ok(Some(lines.join("\n")))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_memories() -> (tempfile::TempDir, Memories) {
+        let td = tempfile::tempdir().unwrap();
+        let memories = Memories::new(td.path().join("ado").join(MEMORIES_FILE_NAME));
+        (td, memories)
+    }
+
+    #[test]
+    fn test_add() {
+        let (_td, memories) = make_memories();
+
+        assert!(memories.list().unwrap().is_empty());
+        assert!(memories.add("the project uses\n  cargo nextest").unwrap());
+        assert!(!memories.add("the project uses cargo nextest").unwrap());
+        assert!(!memories.add("  ").unwrap());
+        assert!(memories.add("answers in French").unwrap());
+
+        assert_eq!(
+            memories.list().unwrap(),
+            vec!["the project uses cargo nextest", "answers in French"]
+        );
+
+        let text = fs::read_to_string(memories.path()).unwrap();
+        assert!(text.starts_with(MEMORIES_HEADER));
+        assert!(text.contains("- answers in French"));
+    }
+
+    #[test]
+    fn test_forget() {
+        let (_td, memories) = make_memories();
+
+        for fact in ["uses tokio", "released in 2024", "uses serde"] {
+            memories.add(fact).unwrap();
+        }
+
+        assert_eq!(memories.forget("2").unwrap(), vec!["released in 2024"]);
+        assert_eq!(
+            memories.forget("uses").unwrap(),
+            vec!["uses tokio", "uses serde"]
+        );
+        assert!(memories.forget("nothing").unwrap().is_empty());
+        assert!(memories.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_forget_number_out_of_range() {
+        let (_td, memories) = make_memories();
+
+        memories.add("released in 2024").unwrap();
+        memories.add("uses serde").unwrap();
+
+        // not the 2024th memory
+        assert_eq!(memories.forget("2024").unwrap(), vec!["released in 2024"]);
+        assert!(memories.forget("0").unwrap().is_empty());
+        assert_eq!(memories.list().unwrap(), vec!["uses serde"]);
+    }
+}
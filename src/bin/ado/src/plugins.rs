@@ -0,0 +1,201 @@
+//! Plugin commands are executables named `ado-<name>`, `ado-jira` becoming
+//! `/jira`, found in `~/.config/ado/plugins` then on `PATH`, the first one of
+//! a name winning.
+//!
+//! A plugin runs in the agent's working directory with the arguments of the
+//! command, split like a shell would, and reads a JSON [`PluginContext`] on
+//! stdin. What it prints on stdout is shown as Markdown, or as a response of
+//! the model when it is an `AdoData` JSON object, its artifacts executed like
+//! the model's.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+};
+
+use adolib::{data::types::AdoData, llm::chain::LLMTurn};
+use anyhow::{Context, Result, bail};
+use log::info;
+use serde::Serialize;
+
+const PLUGIN_PREFIX: &str = "ado-";
+const PLUGINS_DIR: &str = "plugins";
+
+/// Turns of the conversation sent to a plugin, the most recent ones
+const CONTEXT_TURNS: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct Plugin {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Serialize)]
+pub struct PluginMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// What a plugin reads on stdin
+#[derive(Serialize)]
+pub struct PluginContext {
+    pub command: String,
+    pub arguments: Vec<String>,
+    pub model: String,
+    pub cwd: PathBuf,
+    /// the last turns of the conversation, attachments left out
+    pub messages: Vec<PluginMessage>,
+}
+
+/// What a plugin printed
+pub enum PluginOutput {
+    Markdown(String),
+    Data(AdoData),
+}
+
+impl PluginContext {
+    #[must_use]
+    pub fn new(
+        command: &str,
+        arguments: Vec<String>,
+        model: &str,
+        cwd: PathBuf,
+        turns: Vec<LLMTurn>,
+    ) -> Self {
+        let skip = turns.len().saturating_sub(CONTEXT_TURNS);
+
+        let messages = turns
+            .into_iter()
+            .skip(skip)
+            .map(|t| PluginMessage {
+                role: t.role.into(),
+                content: t.content,
+            })
+            .collect();
+
+        Self {
+            command: command.to_string(),
+            arguments,
+            model: model.to_string(),
+            cwd,
+            messages,
+        }
+    }
+}
+
+impl Plugin {
+    /// Run the plugin with `context`, failing when it exits with an error
+    pub fn run(&self, context: &PluginContext) -> Result<PluginOutput> {
+        let input = serde_json::to_vec(context)?;
+
+        let mut child = Command::new(&self.path)
+            .args(&context.arguments)
+            .current_dir(&context.cwd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Unable to execute {}", self.path.display()))?;
+
+        // written aside so a plugin printing before it reads can't block
+        if let Some(mut stdin) = child.stdin.take() {
+            thread::spawn(move || {
+                // plugins are free not to read it
+                if let Err(e) = stdin.write_all(&input) {
+                    info!("plugin context not read ({e})");
+                }
+            });
+        }
+
+        let out = child.wait_with_output()?;
+
+        if !out.status.success() {
+            bail!(
+                "{} failed ({}): {}",
+                self.path.display(),
+                out.status,
+                String::from_utf8_lossy(&out.stderr).trim()
+            );
+        }
+
+        let stdout = String::from_utf8_lossy(&out.stdout).trim().to_string();
+
+        if stdout.starts_with('{')
+            && let Ok(data) = serde_json::from_str::<AdoData>(&stdout)
+        {
+            return Ok(PluginOutput::Data(data));
+        }
+
+        Ok(PluginOutput::Markdown(stdout))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Directories plugins are looked for in, the first ones winning
+#[must_use]
+pub fn plugins_dirs() -> Vec<PathBuf> {
+    let mut plugins_dirs = Vec::new();
+
+    if let Some(config_dir) = dirs::config_dir() {
+        plugins_dirs.push(config_dir.join("ado").join(PLUGINS_DIR));
+    }
+
+    if let Some(path) = env::var_os("PATH") {
+        plugins_dirs.extend(env::split_paths(&path));
+    }
+
+    plugins_dirs
+}
+
+/// Every plugin, sorted by name
+#[must_use]
+pub fn discover() -> Vec<Plugin> {
+    let mut plugins: Vec<Plugin> = Vec::new();
+
+    for dir in plugins_dirs() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+
+            let Some(name) = file_name.strip_prefix(PLUGIN_PREFIX) else {
+                continue;
+            };
+
+            // typed as `/name args`
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                continue;
+            }
+
+            if plugins.iter().any(|p| p.name == name) || !is_executable(&entry.path()) {
+                continue;
+            }
+
+            info!("plugin /{name} is {}", entry.path().display());
+
+            plugins.push(Plugin {
+                name: name.to_string(),
+                path: entry.path(),
+            });
+        }
+    }
+
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    plugins
+}
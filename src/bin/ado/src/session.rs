@@ -0,0 +1,468 @@
+//! State of the agent for the lifetime of the process, shared by the commands
+//! and the consoles executing artifacts, e.g. the files written by the agent so
+//! `/undo` can restore them, the directory its commands run in, the changes it
+//! planned in plan mode, or the checkpoints `/rewind` goes back to.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+    sync::{
+        Mutex, MutexGuard,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use adolib::{config::loader::ConfigAgent, data::types::AdoDataArtifact};
+use anyhow::{Context, Result, bail};
+use log::{error, info};
+
+/// A file written by the agent
+struct FileWrite {
+    path: PathBuf,
+    /// copy of the file before the write, `None` if it didn't exist
+    backup: Option<PathBuf>,
+}
+
+/// The tracked files of a repository, as a commit of `git stash create`
+#[derive(Clone)]
+struct GitSnapshot {
+    top: PathBuf,
+    commit: String,
+    /// tracked files changed by the commands of the agent since, relative to
+    /// `top`: the only ones restored, not to lose changes made by the user
+    touched: Vec<String>,
+}
+
+/// Where `/rewind` goes back to, taken before the first change of a turn
+#[derive(Clone)]
+pub struct Checkpoint {
+    /// prompt of the turn
+    pub prompt: String,
+    pub taken: SystemTime,
+    /// turns of the conversation before the prompt
+    pub turns: usize,
+    /// length of the write journal
+    pub writes: usize,
+    snapshot: Option<GitSnapshot>,
+}
+
+pub struct Session {
+    backup_dir: PathBuf,
+    writes: Mutex<Vec<FileWrite>>,
+    /// where `command` artifacts run, moved by `cd`
+    cwd: Mutex<PathBuf>,
+    /// `[agent]` of the active config
+    agent: Mutex<ConfigAgent>,
+    /// plan mode: only read-only artifacts run, the others are collected
+    /// into `plan` for the user to approve
+    planning: AtomicBool,
+    plan: Mutex<Vec<AdoDataArtifact>>,
+    /// prompt and conversation length of the turn in progress, until its
+    /// first change takes a checkpoint
+    turn: Mutex<Option<(String, usize)>>,
+    checkpoints: Mutex<Vec<Checkpoint>>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    #[must_use]
+    pub fn new() -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            backup_dir: env::temp_dir().join("ado").join(format!("{started}-{}", process::id())),
+            writes: Mutex::new(Vec::new()),
+            cwd: Mutex::new(env::current_dir().unwrap_or_else(|_| PathBuf::from("."))),
+            agent: Mutex::new(ConfigAgent::default()),
+            planning: AtomicBool::new(false),
+            plan: Mutex::new(Vec::new()),
+            turn: Mutex::new(None),
+            checkpoints: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[must_use]
+    pub fn plan_mode(&self) -> bool {
+        self.planning.load(Ordering::Relaxed)
+    }
+
+    pub fn set_plan_mode(&self, on: bool) {
+        info!("plan mode {}", if on { "on" } else { "off" });
+        self.planning.store(on, Ordering::Relaxed);
+    }
+
+    fn plan_steps(&self) -> MutexGuard<'_, Vec<AdoDataArtifact>> {
+        self.plan.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Add a step to the plan, returns its 1-based number
+    pub fn add_to_plan(&self, artifact: AdoDataArtifact) -> usize {
+        let mut plan = self.plan_steps();
+        plan.push(artifact);
+        plan.len()
+    }
+
+    /// Steps planned so far
+    #[must_use]
+    pub fn plan(&self) -> Vec<AdoDataArtifact> {
+        self.plan_steps().clone()
+    }
+
+    /// Empty the plan, returning its steps
+    pub fn take_plan(&self) -> Vec<AdoDataArtifact> {
+        std::mem::take(&mut *self.plan_steps())
+    }
+
+    /// Directory the agent's commands run in
+    #[must_use]
+    pub fn cwd(&self) -> PathBuf {
+        self.cwd.lock().unwrap_or_else(std::sync::PoisonError::into_inner).clone()
+    }
+
+    /// `path` as the agent means it, relative to its working directory
+    #[must_use]
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.cwd().join(path)
+    }
+
+    /// Move the agent to `dir`, relative to its current directory. Returns the
+    /// new directory.
+    pub fn change_dir(&self, dir: &Path) -> Result<PathBuf> {
+        let mut cwd = self.cwd.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        let target = cwd.join(dir);
+
+        if !target.is_dir() {
+            bail!("{} is not a directory", target.display());
+        }
+
+        let target = fs::canonicalize(&target)
+            .with_context(|| format!("Unable to resolve {}", target.display()))?;
+
+        info!("agent working directory {}", target.display());
+        cwd.clone_from(&target);
+
+        Ok(target)
+    }
+
+    #[must_use]
+    pub fn agent(&self) -> ConfigAgent {
+        self.agent.lock().unwrap_or_else(std::sync::PoisonError::into_inner).clone()
+    }
+
+    /// Follow the `[agent]` section of a (re)loaded config
+    pub fn set_agent(&self, agent: &ConfigAgent) {
+        self.agent
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone_from(agent);
+    }
+
+    fn checkpoint_list(&self) -> MutexGuard<'_, Vec<Checkpoint>> {
+        self.checkpoints.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// A prompt starts a turn, `turns` being the length of the conversation
+    /// before it
+    pub fn begin_turn(&self, prompt: &str, turns: usize) {
+        *self.turn.lock().unwrap_or_else(std::sync::PoisonError::into_inner) =
+            Some((prompt.to_string(), turns));
+    }
+
+    /// Take a checkpoint before the first change of the turn in progress,
+    /// later changes of the turn don't
+    pub fn checkpoint(&self) {
+        let Some((prompt, turns)) =
+            self.turn.lock().unwrap_or_else(std::sync::PoisonError::into_inner).take()
+        else {
+            return;
+        };
+
+        let snapshot = GitSnapshot::take(&self.cwd());
+
+        if let Some(s) = &snapshot {
+            info!("checkpoint of {} at {}", s.top.display(), s.commit);
+        }
+
+        self.checkpoint_list().push(Checkpoint {
+            prompt,
+            taken: SystemTime::now(),
+            turns,
+            writes: self.undoable(),
+            snapshot,
+        });
+    }
+
+    /// Note the tracked files changed since the last checkpoint after the
+    /// agent ran a command, for `/rewind` to restore them
+    pub fn record_command(&self) {
+        let mut checkpoints = self.checkpoint_list();
+
+        let Some(snapshot) = checkpoints.last_mut().and_then(|c| c.snapshot.as_mut()) else {
+            return;
+        };
+
+        match snapshot.changed() {
+            Ok(changed) => {
+                for path in changed {
+                    if !snapshot.touched.contains(&path) {
+                        snapshot.touched.push(path);
+                    }
+                }
+            }
+            Err(e) => error!("{e:#}"),
+        }
+    }
+
+    #[must_use]
+    pub fn checkpoints(&self) -> Vec<Checkpoint> {
+        self.checkpoint_list().clone()
+    }
+
+    /// Restore the files as they were at the checkpoint `index`, dropping it
+    /// and the later ones. Returns what was done to the files, and the turns
+    /// the conversation goes back to.
+    pub fn rewind(&self, index: usize) -> Result<(Vec<String>, usize)> {
+        let mut checkpoints = self.checkpoint_list();
+
+        let Some(checkpoint) = checkpoints.get(index).cloned() else {
+            bail!("No checkpoint {}", index.saturating_add(1));
+        };
+
+        let mut done = self.undo(self.undoable().saturating_sub(checkpoint.writes))?;
+
+        // the latest first, a file touched in several turns ending up as it
+        // was at the checkpoint
+        for snapshot in checkpoints
+            .get(index..)
+            .unwrap_or_default()
+            .iter()
+            .rev()
+            .flat_map(|c| &c.snapshot)
+        {
+            snapshot.restore()?;
+
+            for path in &snapshot.touched {
+                let line = format!("restored {} from git", snapshot.top.join(path).display());
+
+                if !done.contains(&line) {
+                    done.push(line);
+                }
+            }
+        }
+
+        checkpoints.truncate(index);
+        self.turn.lock().unwrap_or_else(std::sync::PoisonError::into_inner).take();
+
+        Ok((done, checkpoint.turns))
+    }
+
+    fn writes(&self) -> MutexGuard<'_, Vec<FileWrite>> {
+        // a panic elsewhere doesn't make the journal wrong
+        self.writes.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Back `path` up before the agent writes or removes it
+    pub fn record_write(&self, path: &Path) -> Result<()> {
+        let path = self.resolve(path);
+
+        let mut writes = self.writes();
+
+        let backup = if path.is_file() {
+            fs::create_dir_all(&self.backup_dir)
+                .with_context(|| format!("Unable to create {}", self.backup_dir.display()))?;
+
+            let name =
+                path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let backup = self.backup_dir.join(format!("{}-{name}", writes.len()));
+
+            fs::copy(&path, &backup)
+                .with_context(|| format!("Unable to back {} up", path.display()))?;
+
+            info!("{} backed up to {}", path.display(), backup.display());
+
+            Some(backup)
+        } else {
+            None
+        };
+
+        writes.push(FileWrite { path, backup });
+
+        Ok(())
+    }
+
+    /// Number of writes `/undo` can restore
+    #[must_use]
+    pub fn undoable(&self) -> usize {
+        self.writes().len()
+    }
+
+    /// Restore the files as they were before the last `count` writes, the
+    /// most recent first. Returns what was done to each file.
+    pub fn undo(&self, count: usize) -> Result<Vec<String>> {
+        let mut writes = self.writes();
+        let mut done = Vec::new();
+
+        for _ in 0..count {
+            let Some(write) = writes.pop() else { break };
+
+            match write.restore() {
+                Ok(v) => done.push(v),
+                Err(e) => {
+                    // kept for another try
+                    error!("{e:#}");
+                    writes.push(write);
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(done)
+    }
+}
+
+impl FileWrite {
+    fn restore(&self) -> Result<String> {
+        if let Some(backup) = &self.backup {
+            fs::copy(backup, &self.path)
+                .with_context(|| format!("Unable to restore {}", self.path.display()))?;
+            return Ok(format!("restored {}", self.path.display()));
+        }
+
+        if self.path.exists() {
+            fs::remove_file(&self.path)
+                .with_context(|| format!("Unable to remove {}", self.path.display()))?;
+        }
+
+        Ok(format!("removed {}", self.path.display()))
+    }
+}
+
+impl Checkpoint {
+    /// Short hash of the git snapshot, if any
+    #[must_use]
+    pub fn git_commit(&self) -> Option<&str> {
+        self.snapshot.as_ref().map(|s| s.commit.get(..7).unwrap_or(&s.commit))
+    }
+}
+
+impl GitSnapshot {
+    /// Snapshot of the repository `dir` is in, `None` outside of one or
+    /// before its first commit
+    fn take(dir: &Path) -> Option<Self> {
+        let top = git(dir, &["rev-parse", "--show-toplevel"]).ok()?;
+
+        // nothing when the tracked files are as committed
+        let commit = match git(dir, &["stash", "create"]).ok()? {
+            c if c.is_empty() => git(dir, &["rev-parse", "HEAD"]).ok()?,
+            c => c,
+        };
+
+        Some(Self {
+            top: PathBuf::from(top),
+            commit,
+            touched: Vec::new(),
+        })
+    }
+
+    /// Tracked files that differ from the snapshot, relative to `top`. Those
+    /// added since can't be restored and are left out.
+    fn changed(&self) -> Result<Vec<String>> {
+        let names = git(
+            &self.top,
+            &["diff", "--name-only", "--diff-filter=a", "-z", &self.commit],
+        )?;
+
+        Ok(names.split('\0').filter(|n| !n.is_empty()).map(ToString::to_string).collect())
+    }
+
+    /// Put the files touched by the agent back as they were, the index left
+    /// as is
+    fn restore(&self) -> Result<()> {
+        if self.touched.is_empty() {
+            return Ok(());
+        }
+
+        let mut args =
+            vec!["--literal-pathspecs", "restore", "--source", &self.commit, "--worktree", "--"];
+        args.extend(self.touched.iter().map(String::as_str));
+
+        git(&self.top, &args)?;
+        Ok(())
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git").args(args).current_dir(dir).output()?;
+
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewind_restores_touched_files_only() {
+        let td = tempfile::tempdir().unwrap();
+        let dir = td.path();
+
+        fs::write(dir.join("agent.txt"), "agent\n").unwrap();
+        fs::write(dir.join("user.txt"), "user\n").unwrap();
+
+        for args in [
+            &["init", "-q"][..],
+            &["add", "."],
+            &["-c", "user.name=ado", "-c", "user.email=ado@localhost", "commit", "-qm", "init"],
+        ] {
+            git(dir, args).unwrap();
+        }
+
+        let session = Session::new();
+        session.change_dir(dir).unwrap();
+
+        session.begin_turn("change agent.txt", 2);
+        session.checkpoint();
+
+        // a command of the agent, then the user editing another file
+        fs::write(dir.join("agent.txt"), "changed by a command\n").unwrap();
+        session.record_command();
+        fs::write(dir.join("user.txt"), "edited by the user\n").unwrap();
+
+        let (done, turns) = session.rewind(0).unwrap();
+
+        assert_eq!(turns, 2);
+        assert_eq!(done.len(), 1);
+        assert_eq!(
+            fs::read_to_string(dir.join("agent.txt")).unwrap(),
+            "agent\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("user.txt")).unwrap(),
+            "edited by the user\n"
+        );
+        assert!(session.checkpoints().is_empty());
+    }
+}
@@ -0,0 +1,349 @@
+//! Skills are Markdown files found in the skills directories, optionally
+//! starting with a YAML front-matter:
+//!
+//! ```markdown
+//! ---
+//! name: commit
+//! description: Write a commit message for the staged changes
+//! triggers: [commit, "commit message"]
+//! tools: [command]
+//! ---
+//! Run `git diff --staged` and ... $ARGUMENTS
+//! ```
+//!
+//! Only the name and description of each skill go in the system prompt. The
+//! body is sent when the model asks for it with a `skill` artifact or when the
+//! user runs `/skill <name> [args]`.
+//!
+//! `tools` (or `allowed-tools`) is advisory: the model is told to stick to
+//! those artifact types, nothing stops it from using others.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use log::{error, info};
+use serde::Deserialize;
+
+/// Replaced by the arguments of the invocation
+pub const ARGUMENTS_PLACEHOLDER: &str = "$ARGUMENTS";
+
+const FRONT_MATTER_DELIMITER: &str = "---";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SkillFrontMatter {
+    name: Option<String>,
+    description: Option<String>,
+    triggers: Vec<String>,
+    #[serde(alias = "allowed-tools", alias = "allowed_tools")]
+    tools: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Skill {
+    pub name: String,
+    pub description: String,
+    /// words or phrases that should make the model reach for the skill
+    pub triggers: Vec<String>,
+    /// artifact types the skill is meant to use, asked of the model but not
+    /// enforced
+    pub suggested_tools: Vec<String>,
+    pub path: PathBuf,
+    body: String,
+}
+
+/// `(front-matter, body)`, front-matter is `None` for plain Markdown
+pub(crate) fn split_front_matter(text: &str) -> (Option<&str>, &str) {
+    let Some(rest) = text
+        .strip_prefix(FRONT_MATTER_DELIMITER)
+        .and_then(|r| r.strip_prefix('\n').or_else(|| r.strip_prefix("\r\n")))
+    else {
+        return (None, text);
+    };
+
+    let mut offset: usize = 0;
+
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == FRONT_MATTER_DELIMITER {
+            let body = rest.get(offset.saturating_add(line.len())..).unwrap_or_default();
+            return (rest.get(..offset), body);
+        }
+
+        offset = offset.saturating_add(line.len());
+    }
+
+    // never closed, not front-matter
+    (None, text)
+}
+
+impl Skill {
+    pub fn parse(path: &Path, text: &str) -> Result<Self> {
+        let (front_matter, body) = split_front_matter(text);
+
+        let front_matter: SkillFrontMatter = match front_matter {
+            Some(yaml) => serde_yaml_ng::from_str(yaml)
+                .with_context(|| format!("Invalid front-matter in {}", path.display()))?,
+            None => SkillFrontMatter::default(),
+        };
+
+        let name = match front_matter.name {
+            Some(name) => name,
+            None => path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .with_context(|| format!("Unable to name skill {}", path.display()))?
+                .to_string(),
+        };
+
+        // skills written before front-matter: their first line says it all
+        let description = front_matter.description.unwrap_or_else(|| {
+            body.lines()
+                .map(|l| l.trim().trim_start_matches('#').trim())
+                .find(|l| !l.is_empty())
+                .unwrap_or_default()
+                .to_string()
+        });
+
+        Ok(Self {
+            name,
+            description,
+            triggers: front_matter.triggers,
+            suggested_tools: front_matter.tools,
+            path: path.to_path_buf(),
+            body: body.trim().to_string(),
+        })
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        Skill::parse(path, &text)
+    }
+
+    /// Full instructions, `args` replacing `$ARGUMENTS` or appended when the
+    /// skill doesn't use it
+    #[must_use]
+    pub fn render(&self, args: &str) -> String {
+        let args = args.trim();
+
+        let mut lines = vec![format!("# Skill: {}", self.name), String::new()];
+
+        if !self.suggested_tools.is_empty() {
+            lines.push(format!(
+                "While following this skill only use artifacts of type: {}",
+                self.suggested_tools.join(", ")
+            ));
+            lines.push(String::new());
+        }
+
+        if self.body.contains(ARGUMENTS_PLACEHOLDER) {
+            lines.push(self.body.replace(ARGUMENTS_PLACEHOLDER, args));
+        } else {
+            lines.push(self.body.clone());
+
+            if !args.is_empty() {
+                lines.push(String::new());
+                lines.push(format!("Arguments: {args}"));
+            }
+        }
+
+        lines.join("\n")
+    }
+}
+
+#[must_use]
+pub fn skills_dirs() -> Vec<PathBuf> {
+    let mut skills_dirs = Vec::new();
+
+    //
+    // Skills in the user directory
+    //
+    if let Some(config_dir) = dirs::config_dir() {
+        let config_dir = config_dir.join("ado");
+        let skills_dir = config_dir.join("skills");
+
+        skills_dirs.push(skills_dir);
+    }
+
+    //
+    // Skills in the current directory
+    //
+    if let Ok(cwd) = env::current_dir() {
+        let skills_dir = cwd.join("skills");
+        skills_dirs.push(skills_dir);
+    }
+
+    skills_dirs
+}
+
+/// Markdown files of `dir`, sorted
+pub fn skill_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let glob_pattern = format!("{}/*.md", dir.display());
+
+    let mut files: Vec<PathBuf> = glob::glob(&glob_pattern)?.flatten().collect();
+    files.sort();
+
+    Ok(files)
+}
+
+/// Every skill, a skill of the current directory overriding a user one of the
+/// same name. Invalid skills are logged and skipped.
+#[must_use]
+pub fn discover() -> Vec<Skill> {
+    let mut skills: Vec<Skill> = Vec::new();
+
+    for dir in skills_dirs() {
+        if !dir.exists() {
+            info!("{} doesn't exist", dir.display());
+            continue;
+        }
+
+        info!("Loading skills from {}", dir.display());
+
+        let files = match skill_files(&dir) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Unable to list skills in {} ({e})", dir.display());
+                continue;
+            }
+        };
+
+        for file in files {
+            match Skill::from_path(&file) {
+                Ok(skill) => {
+                    skills.retain(|s| s.name != skill.name);
+                    skills.push(skill);
+                }
+                Err(e) => error!("{e:#}"),
+            }
+        }
+    }
+
+    skills.sort_by(|a, b| a.name.cmp(&b.name));
+    skills
+}
+
+#[must_use]
+pub fn find(name: &str) -> Option<Skill> {
+    discover().into_iter().find(|s| s.name == name)
+}
+
+/// The skill index for the system prompt, `None` without skills
+#[must_use]
+pub fn system_prompt(skills: &[Skill]) -> Option<String> {
+    if skills.is_empty() {
+        return None;
+    }
+
+    let mut lines = vec![
+        "# Skills".to_string(),
+        String::new(),
+        "Load the instructions of a skill before using it with a `skill` artifact \
+         (see the operations prompt)."
+            .to_string(),
+        String::new(),
+    ];
+
+    for s in skills {
+        if s.triggers.is_empty() {
+            lines.push(format!("- `{}`: {}", s.name, s.description));
+        } else {
+            lines.push(format!(
+                "- `{}`: {} (use for: {})",
+                s.name,
+                s.description,
+                s.triggers.join(", ")
+            ));
+        }
+    }
+
+    Some(lines.join("\n"))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SKILL: &str = "---\n\
+                         name: commit\n\
+                         description: Write a commit message\n\
+                         triggers: [commit, \"commit message\"]\n\
+                         allowed-tools: [command]\n\
+                         ---\n\
+                         Run `git diff --staged` for $ARGUMENTS\n";
+
+    #[test]
+    fn test_split_front_matter() {
+        assert_eq!(
+            split_front_matter("---\nname: a\n---\nbody\n"),
+            (Some("name: a\n"), "body\n")
+        );
+        assert_eq!(
+            split_front_matter("---\r\nname: a\r\n---\r\nbody"),
+            (Some("name: a\r\n"), "body")
+        );
+        assert_eq!(split_front_matter("---\n---\nbody"), (Some(""), "body"));
+
+        // plain Markdown, or a front-matter never closed
+        assert_eq!(split_front_matter("# Title\n"), (None, "# Title\n"));
+        assert_eq!(
+            split_front_matter("---\nname: a\n"),
+            (None, "---\nname: a\n")
+        );
+        assert_eq!(split_front_matter("----\nbody"), (None, "----\nbody"));
+    }
+
+    #[test]
+    fn test_parse() {
+        let skill = Skill::parse(Path::new("skills/other.md"), SKILL).unwrap();
+
+        assert_eq!(skill.name, "commit");
+        assert_eq!(skill.description, "Write a commit message");
+        assert_eq!(skill.triggers, ["commit", "commit message"]);
+        assert_eq!(skill.suggested_tools, ["command"]);
+        assert_eq!(skill.body, "Run `git diff --staged` for $ARGUMENTS");
+
+        // named after the file, described by its first line
+        let skill = Skill::parse(
+            Path::new("skills/review.md"),
+            "\n# Review a file\n\nRead it.",
+        )
+        .unwrap();
+
+        assert_eq!(skill.name, "review");
+        assert_eq!(skill.description, "Review a file");
+        assert!(skill.triggers.is_empty());
+
+        let skill = Skill::parse(Path::new("a.md"), "---\ntools: [read, grep]\n---\nbody").unwrap();
+        assert_eq!(skill.suggested_tools, ["read", "grep"]);
+
+        assert!(Skill::parse(Path::new("bad.md"), "---\ntriggers: 3\n---\nbody").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let skill = Skill::parse(Path::new("commit.md"), SKILL).unwrap();
+
+        assert_eq!(
+            skill.render(" the parser "),
+            "# Skill: commit\n\n\
+             While following this skill only use artifacts of type: command\n\n\
+             Run `git diff --staged` for the parser"
+        );
+
+        let skill = Skill::parse(Path::new("review.md"), "Review the file.").unwrap();
+
+        assert_eq!(skill.render(""), "# Skill: review\n\nReview the file.");
+        assert_eq!(
+            skill.render("main.rs"),
+            "# Skill: review\n\nReview the file.\n\nArguments: main.rs"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use adolib::{cache::kv::KVCache, console::ConsoleTrait, llm::chain::LLMChain};
+use anyhow::{Result, bail};
+use log::error;
+
+use crate::commands::UserCommansTrait;
+
+const CACHE_USAGE: &str = "usage: /cache stats | clear [realm] | purge-expired";
+
+pub struct CommandCache<'a> {
+    cache: &'a KVCache,
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn human_size(size: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+
+    let mut value = size as f64;
+    let mut unit = "B";
+
+    for u in UNITS {
+        unit = u;
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+    }
+
+    if unit == "B" {
+        format!("{size} B")
+    } else {
+        format!("{value:.1} {unit}")
+    }
+}
+
+impl<'a> CommandCache<'a> {
+    #[must_use]
+    pub fn new(cache: &'a KVCache) -> Self {
+        Self { cache }
+    }
+
+    fn stats(&self) -> Result<String> {
+        let stats = self.cache.stats()?;
+
+        let mut lines = vec![
+            "# Cache".to_string(),
+            String::new(),
+            "| realm | entries | expired | size |".to_string(),
+            "|---|---:|---:|---:|".to_string(),
+        ];
+
+        for r in &stats.realms {
+            lines.push(format!(
+                "| {} | {} | {} | {} |",
+                r.realm,
+                r.entries,
+                r.expired,
+                human_size(r.size)
+            ));
+        }
+
+        lines.push(format!(
+            "| **total** | **{}** | **{}** | **{}** |",
+            stats.entries(),
+            stats.expired(),
+            human_size(stats.size())
+        ));
+        lines.push(String::new());
+        lines.push(format!("On disk: {}", human_size(stats.disk_size)));
+
+        Ok(lines.join("\n"))
+    }
+
+    fn clear(&self, realm: Option<&str>) -> Result<String> {
+        let removed = self.cache.clear(realm)?;
+
+        let msg = match realm {
+            Some(r) => format!("Removed {removed} entries from `{r}`"),
+            None => format!("Removed {removed} entries"),
+        };
+
+        Ok(msg)
+    }
+
+    fn purge_expired(&self) -> Result<String> {
+        let removed = self.cache.purge_expired()?;
+        Ok(format!("Purged {removed} expired entries"))
+    }
+
+    fn run(&self, input: &str) -> Result<String> {
+        let mut args = input.split_whitespace();
+
+        match (args.next(), args.next()) {
+            (None | Some("stats"), None) => self.stats(),
+            (Some("clear"), realm) => self.clear(realm),
+            (Some("purge-expired"), None) => self.purge_expired(),
+            _ => bail!(CACHE_USAGE),
+        }
+    }
+}
+
+impl UserCommansTrait for CommandCache<'_> {
+    fn name(&self) -> &'static str {
+        "cache"
+    }
+
+    fn desc(&self) -> &'static str {
+        "cache stats | clear [realm] | purge-expired"
+    }
+
+    fn callback(&mut self, input: &str, _chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        match self.run(input) {
+            Ok(md) => console.print_markdown(&md),
+            Err(e) => {
+                error!("cache command failure ({e})");
+                console.error_message(&format!("{e}"));
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::SystemTime;
+
+use adolib::{console::ConsoleTrait, llm::chain::LLMChain};
+use log::error;
+
+use crate::{commands::UserCommansTrait, session::Session};
+
+/// Longest part of a prompt shown in the list
+const MAX_PROMPT_LEN: usize = 60;
+
+pub struct CommandCheckpoints<'a> {
+    session: &'a Session,
+}
+
+pub struct CommandRewind<'a> {
+    session: &'a Session,
+}
+
+/// How long ago `time` was, e.g. "5m ago"
+fn ago(time: SystemTime) -> String {
+    let secs = time.elapsed().map(|d| d.as_secs()).unwrap_or_default();
+
+    match secs {
+        0..60 => format!("{secs}s ago"),
+        60..3600 => format!("{}m ago", secs / 60),
+        _ => format!("{}h ago", secs / 3600),
+    }
+}
+
+fn summary(prompt: &str) -> String {
+    let prompt = prompt.lines().next().unwrap_or_default();
+
+    match prompt.char_indices().nth(MAX_PROMPT_LEN) {
+        Some((i, _)) => format!("{}…", prompt.get(..i).unwrap_or(prompt)),
+        None => prompt.to_string(),
+    }
+}
+
+impl<'a> CommandCheckpoints<'a> {
+    #[must_use]
+    pub fn new(session: &'a Session) -> Self {
+        Self { session }
+    }
+}
+
+impl UserCommansTrait for CommandCheckpoints<'_> {
+    fn name(&self) -> &'static str {
+        "checkpoints"
+    }
+
+    fn desc(&self) -> &'static str {
+        "list the checkpoints taken before the agent changed files, to `/rewind` to"
+    }
+
+    fn callback(&mut self, _input: &str, _chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        let checkpoints = self.session.checkpoints();
+
+        if checkpoints.is_empty() {
+            console.print_markdown("_no checkpoint, the agent didn't change anything yet_");
+            return;
+        }
+
+        let mut lines = vec!["# Checkpoints".to_string(), String::new()];
+
+        for (i, checkpoint) in checkpoints.iter().enumerate() {
+            // writes made from this checkpoint to the next one
+            let until = checkpoints
+                .get(i.saturating_add(1))
+                .map_or_else(|| self.session.undoable(), |c| c.writes);
+            let writes = until.saturating_sub(checkpoint.writes);
+
+            let snapshot = checkpoint
+                .git_commit()
+                .map(|c| format!(", git snapshot `{c}`"))
+                .unwrap_or_default();
+
+            lines.push(format!(
+                "{}. {}, before \"{}\": {writes} file write(s){snapshot}",
+                i.saturating_add(1),
+                ago(checkpoint.taken),
+                summary(&checkpoint.prompt)
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(
+            "`/rewind <n>` to restore the files and the conversation as they were".to_string(),
+        );
+
+        console.print_markdown(&lines.join("\n"));
+    }
+}
+
+impl<'a> CommandRewind<'a> {
+    #[must_use]
+    pub fn new(session: &'a Session) -> Self {
+        Self { session }
+    }
+}
+
+impl UserCommansTrait for CommandRewind<'_> {
+    fn name(&self) -> &'static str {
+        "rewind [n]"
+    }
+
+    fn desc(&self) -> &'static str {
+        "restore the files changed by the agent and the conversation as they were at a checkpoint (the last one by default)"
+    }
+
+    fn callback(&mut self, input: &str, chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        let count = self.session.checkpoints().len();
+
+        if count == 0 {
+            console.print_markdown("_no checkpoint to rewind to_");
+            return;
+        }
+
+        let input = input.trim();
+
+        let n = if input.is_empty() {
+            count
+        } else if let Ok(n) = input.parse::<usize>()
+            && (1..=count).contains(&n)
+        {
+            n
+        } else {
+            console.error_message(&format!("usage: /rewind [n], n from 1 to {count}"));
+            return;
+        };
+
+        match self.session.rewind(n.saturating_sub(1)) {
+            Ok((done, turns)) => {
+                chain.truncate_turns(turns);
+
+                let mut lines = vec![format!("# Rewound to checkpoint {n}"), String::new()];
+                lines.extend(done.iter().map(|d| format!("* {d}")));
+                lines.push(format!("* conversation back to {turns} message(s)"));
+
+                console.print_markdown(&lines.join("\n"));
+            }
+            Err(e) => {
+                error!("rewind failure ({e:#})");
+                console.error_message(&format!("{e:#}"));
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use adolib::{
+    config::loader::{AdoConfig, ConfigEntry},
+    console::ConsoleTrait,
+    llm::chain::LLMChain,
+};
+use anyhow::Result;
+
+use crate::commands::UserCommansTrait;
+
+pub struct CommandConfig {
+    entries: Vec<ConfigEntry>,
+}
+
+impl CommandConfig {
+    pub fn new(config: &AdoConfig) -> Result<Self> {
+        Ok(Self {
+            entries: config.entries()?,
+        })
+    }
+
+    /// `prefix` narrows the table down to a section, e.g. `llm.claude`
+    fn render(&self, prefix: &str) -> String {
+        let mut lines = vec![
+            "# Config".to_string(),
+            String::new(),
+            "| key | value | origin |".to_string(),
+            "|---|---|---|".to_string(),
+        ];
+
+        for e in self.entries.iter().filter(|e| e.key.starts_with(prefix)) {
+            let value = e.value.replace('|', "\\|");
+            lines.push(format!("| {} | `{value}` | {} |", e.key, e.origin));
+        }
+
+        lines.join("\n")
+    }
+}
+
+impl UserCommansTrait for CommandConfig {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+
+    fn desc(&self) -> &'static str {
+        "effective configuration and where each value came from"
+    }
+
+    fn callback(&mut self, input: &str, _chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        console.print_markdown(&self.render(input.trim()));
+    }
+}
@@ -0,0 +1,41 @@
+use adolib::{console::ConsoleTrait, llm::chain::LLMChain};
+use log::error;
+
+use crate::{commands::UserCommansTrait, custom_commands::Template, session::Session};
+
+/// A custom command, sending its template to the agent
+pub struct CommandTemplate<'a> {
+    template: Template,
+    session: &'a Session,
+}
+
+impl<'a> CommandTemplate<'a> {
+    #[must_use]
+    pub fn new(template: Template, session: &'a Session) -> Self {
+        Self { template, session }
+    }
+}
+
+impl UserCommansTrait for CommandTemplate<'_> {
+    fn name(&self) -> &str {
+        &self.template.name
+    }
+
+    fn desc(&self) -> &str {
+        &self.template.description
+    }
+
+    fn callback(&mut self, input: &str, chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        let prompt = self.template.render(input);
+
+        self.session.begin_turn(
+            &format!("/{} {}", self.template.name, input.trim()),
+            chain.turns().len(),
+        );
+
+        if let Err(e) = chain.link(prompt, console) {
+            error!("{} failure ({e})", self.template.path.display());
+            console.error_message(&format!("{e}"));
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::{path::Path, process::Command};
+
+use adolib::{config::loader::AdoConfig, console::ConsoleTrait, llm::chain::LLMChain};
+use anyhow::{Result, bail};
+use log::error;
+
+use crate::{commands::UserCommansTrait, intrinsics::CommandPrompts, session::Session};
+
+/// Most bytes of git output sent to the model
+const MAX_GIT_OUTPUT: usize = 100 * 1024;
+/// Commits of the recent log sent along
+const LOG_LENGTH: &str = "15";
+
+const BLAME_USAGE: &str = "usage: /blame <file>[:<line>[-<line>]] [question]";
+
+/// Output of `git args` in `dir`, cut to `MAX_GIT_OUTPUT` bytes
+fn git(args: &[&str], dir: &Path) -> Result<String> {
+    let output = Command::new("git").args(args).current_dir(dir).output()?;
+
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    if text.len() <= MAX_GIT_OUTPUT {
+        return Ok(text.into_owned());
+    }
+
+    let cut = text.floor_char_boundary(MAX_GIT_OUTPUT);
+
+    Ok(format!(
+        "{}\n... output truncated after {MAX_GIT_OUTPUT} bytes",
+        text.get(..cut).unwrap_or_default()
+    ))
+}
+
+/// Commits of the recent log, empty outside of a repository or before the
+/// first commit
+fn recent_log(format: &str, dir: &Path) -> String {
+    git(
+        &["log", "-n", LOG_LENGTH, &format!("--format={format}")],
+        dir,
+    )
+    .unwrap_or_default()
+}
+
+fn prompt(name: &str) -> String {
+    CommandPrompts::get(name)
+        .map(|p| String::from_utf8_lossy(&p.data).into_owned())
+        .unwrap_or_default()
+}
+
+/// Sends `message` to the conversation, so the answer can be followed up on,
+/// as the turn of the command typed as `prompt`
+fn link(
+    prompt: &str,
+    message: String,
+    session: &Session,
+    chain: &mut LLMChain,
+    console: &dyn ConsoleTrait,
+) {
+    session.begin_turn(prompt, chain.turns().len());
+
+    if let Err(e) = chain.link(message, console) {
+        error!("git command failure ({e})");
+        console.error_message(&format!("{e}"));
+    }
+}
+
+pub struct CommandDiff<'a> {
+    session: &'a Session,
+}
+
+impl<'a> CommandDiff<'a> {
+    #[must_use]
+    pub fn new(session: &'a Session) -> Self {
+        Self { session }
+    }
+
+    /// The diff to review and what it is: the arguments' when given,
+    /// otherwise the staged changes or else the unstaged ones
+    fn diff(input: &str, dir: &Path) -> Result<(String, String)> {
+        let args: Vec<&str> = input.split_whitespace().collect();
+
+        if !args.is_empty() {
+            let diff = git(&[&["diff"], args.as_slice()].concat(), dir)?;
+            return Ok((diff, format!("git diff {}", args.join(" "))));
+        }
+
+        let staged = git(&["diff", "--staged"], dir)?;
+
+        if !staged.trim().is_empty() {
+            return Ok((staged, "staged changes".to_string()));
+        }
+
+        Ok((git(&["diff"], dir)?, "unstaged changes".to_string()))
+    }
+}
+
+impl UserCommansTrait for CommandDiff<'_> {
+    fn name(&self) -> &'static str {
+        "diff [git diff arguments]"
+    }
+
+    fn desc(&self) -> &'static str {
+        "review the staged changes, the unstaged ones when nothing is staged, or those of `git diff <arguments>`"
+    }
+
+    fn callback(&mut self, input: &str, chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        let dir = self.session.cwd();
+
+        let (diff, what) = match Self::diff(input, &dir) {
+            Ok(found) => found,
+            Err(e) => {
+                console.error_message(&format!("{e}"));
+                return;
+            }
+        };
+
+        if diff.trim().is_empty() {
+            console.print_markdown("_nothing to review_");
+            return;
+        }
+
+        let message = format!(
+            "{}\n\n## Recent commits\n\n```\n{}\n```\n\n## Diff of the {what}\n\n```diff\n{}\n```",
+            prompt("review.md"),
+            recent_log("%h %s", &dir).trim_end(),
+            diff.trim_end()
+        );
+
+        link(
+            &format!("/diff {}", input.trim()),
+            message,
+            self.session,
+            chain,
+            console,
+        );
+    }
+}
+
+pub struct CommandCommit<'a> {
+    model: Option<String>,
+    session: &'a Session,
+}
+
+impl<'a> CommandCommit<'a> {
+    #[must_use]
+    pub fn new(config: &AdoConfig, session: &'a Session) -> Self {
+        Self {
+            model: config.command().git.model.clone(),
+            session,
+        }
+    }
+
+    fn message(&self, hints: &str, chain: &LLMChain) -> Result<Option<String>> {
+        let dir = self.session.cwd();
+        let diff = git(&["diff", "--staged"], &dir)?;
+
+        if diff.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let mut content = format!(
+            "Recent commit subjects:\n\n{}\n\nStaged diff:\n\n{}",
+            recent_log("%s", &dir).trim_end(),
+            diff.trim_end()
+        );
+
+        if !hints.trim().is_empty() {
+            content.push_str("\n\nHints from the user: ");
+            content.push_str(hints.trim());
+        }
+
+        let message = chain.message_with_system(
+            Some(&prompt("commit.md")),
+            content,
+            self.model.as_deref(),
+        )?;
+
+        Ok(Some(message.trim().to_string()))
+    }
+}
+
+impl UserCommansTrait for CommandCommit<'_> {
+    fn name(&self) -> &'static str {
+        "commit [hints]"
+    }
+
+    fn desc(&self) -> &'static str {
+        "write a commit message for the staged changes, following the style of the recent ones"
+    }
+
+    fn callback(&mut self, input: &str, chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        match self.message(input, chain) {
+            Ok(Some(message)) => console.print_markdown(&format!("```\n{message}\n```")),
+            Ok(None) => console.print_markdown("_nothing staged, `git add` the changes first_"),
+            Err(e) => {
+                error!("commit message failure ({e})");
+                console.error_message(&format!("{e}"));
+            }
+        }
+    }
+}
+
+pub struct CommandBlame<'a> {
+    session: &'a Session,
+}
+
+impl<'a> CommandBlame<'a> {
+    #[must_use]
+    pub fn new(session: &'a Session) -> Self {
+        Self { session }
+    }
+
+    /// `file[:start[-end]]` as the file and `git blame -L` range
+    fn parse_target(target: &str) -> Option<(&str, Option<String>)> {
+        let Some((file, lines)) = target.rsplit_once(':') else {
+            return Some((target, None));
+        };
+
+        let (start, end) = lines.split_once('-').unwrap_or((lines, lines));
+        let (start, end) = (start.parse::<usize>().ok()?, end.parse::<usize>().ok()?);
+
+        if file.is_empty() || start == 0 || end < start {
+            return None;
+        }
+
+        Some((file, Some(format!("{start},{end}"))))
+    }
+}
+
+impl UserCommansTrait for CommandBlame<'_> {
+    fn name(&self) -> &'static str {
+        "blame <file>[:<line>[-<line>]] [question]"
+    }
+
+    fn desc(&self) -> &'static str {
+        "explain a file or some of its lines from git blame and the file history"
+    }
+
+    fn callback(&mut self, input: &str, chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        let input = input.trim();
+        let (target, question) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+
+        let Some((file, range)) = Self::parse_target(target).filter(|_| !target.is_empty()) else {
+            console.error_message(BLAME_USAGE);
+            return;
+        };
+
+        let dir = self.session.cwd();
+
+        let mut args = vec!["blame", "--date=short"];
+        if let Some(range) = &range {
+            args.extend(["-L", range]);
+        }
+        args.extend(["--", file]);
+
+        let blame = match git(&args, &dir) {
+            Ok(blame) => blame,
+            Err(e) => {
+                console.error_message(&format!("{e}"));
+                return;
+            }
+        };
+
+        let history = git(
+            &["log", "-n", LOG_LENGTH, "--date=short", "--format=%h %ad %an %s", "--", file],
+            &dir,
+        )
+        .unwrap_or_default();
+
+        let mut message = format!(
+            "{}\n\n## git blame {target}\n\n```\n{}\n```\n\n## History of {file}\n\n```\n{}\n```",
+            prompt("blame.md"),
+            blame.trim_end(),
+            history.trim_end()
+        );
+
+        if !question.trim().is_empty() {
+            message.push_str("\n\n## Question\n\n");
+            message.push_str(question.trim());
+        }
+
+        link(
+            &format!("/blame {input}"),
+            message,
+            self.session,
+            chain,
+            console,
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use adolib::{config::loader::AdoConfig, console::ConsoleTrait, llm::chain::LLMChain};
+use anyhow::{Result, bail};
+use log::error;
+
+use crate::{commands::UserCommansTrait, memories::Memories};
+
+fn run_command(
+    console: &dyn ConsoleTrait,
+    name: &str,
+    f: impl FnOnce(&Memories) -> Result<String>,
+) -> bool {
+    let ret = Memories::from_default().and_then(|m| f(&m));
+
+    match ret {
+        Ok(md) => {
+            console.print_markdown(&md);
+            true
+        }
+        Err(e) => {
+            error!("{name} failure ({e})");
+            console.error_message(&format!("{e}"));
+            false
+        }
+    }
+}
+
+pub struct CommandMemories;
+
+impl UserCommansTrait for CommandMemories {
+    fn name(&self) -> &'static str {
+        "memories"
+    }
+
+    fn desc(&self) -> &'static str {
+        "list the facts remembered across sessions"
+    }
+
+    fn callback(&mut self, _input: &str, _chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        run_command(console, "memories", |memories| {
+            let list = memories.list()?;
+
+            let mut lines = vec!["# Memories".to_string(), String::new()];
+
+            if list.is_empty() {
+                lines.push("_nothing remembered yet, see /remember_".to_string());
+            }
+
+            for (i, m) in list.iter().enumerate() {
+                lines.push(format!("{}. {m}", i.saturating_add(1)));
+            }
+
+            lines.push(String::new());
+            lines.push(format!("Stored in {}", memories.path().display()));
+
+            Ok(lines.join("\n"))
+        });
+    }
+}
+
+/// `/remember` and `/forget` change the system prompt, the chain is rebuilt
+/// from the same config to pick the change up
+pub struct CommandRemember {
+    config: AdoConfig,
+    pending: Option<AdoConfig>,
+}
+
+pub struct CommandForget {
+    config: AdoConfig,
+    pending: Option<AdoConfig>,
+}
+
+impl CommandRemember {
+    #[must_use]
+    pub fn new(config: &AdoConfig) -> Self {
+        Self {
+            config: config.clone(),
+            pending: None,
+        }
+    }
+}
+
+impl CommandForget {
+    #[must_use]
+    pub fn new(config: &AdoConfig) -> Self {
+        Self {
+            config: config.clone(),
+            pending: None,
+        }
+    }
+}
+
+impl UserCommansTrait for CommandRemember {
+    fn name(&self) -> &'static str {
+        "remember <fact>"
+    }
+
+    fn desc(&self) -> &'static str {
+        "remember a fact across sessions"
+    }
+
+    fn callback(&mut self, input: &str, _chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        let changed = run_command(console, "remember", |memories| {
+            if input.trim().is_empty() {
+                bail!("usage: /remember <fact>");
+            }
+
+            if memories.add(input)? {
+                Ok("Remembered".to_string())
+            } else {
+                Ok("Already remembered".to_string())
+            }
+        });
+
+        if changed {
+            self.pending = Some(self.config.clone());
+        }
+    }
+
+    fn take_config(&mut self) -> Option<AdoConfig> {
+        self.pending.take()
+    }
+}
+
+impl UserCommansTrait for CommandForget {
+    fn name(&self) -> &'static str {
+        "forget <number|text>"
+    }
+
+    fn desc(&self) -> &'static str {
+        "forget a memory by number or every memory containing text"
+    }
+
+    fn callback(&mut self, input: &str, _chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        let changed = run_command(console, "forget", |memories| {
+            let forgotten = memories.forget(input)?;
+
+            if forgotten.is_empty() {
+                bail!("no memory matches `{}`, see /memories", input.trim());
+            }
+
+            let mut lines = vec!["Forgot:".to_string()];
+            lines.extend(forgotten.iter().map(|m| format!("* {m}")));
+
+            Ok(lines.join("\n"))
+        });
+
+        if changed {
+            self.pending = Some(self.config.clone());
+        }
+    }
+
+    fn take_config(&mut self) -> Option<AdoConfig> {
+        self.pending.take()
+    }
+}
@@ -1 +1,12 @@
+pub mod cache;
+pub mod checkpoints;
+pub mod config;
+pub mod custom;
+pub mod git;
+pub mod memories;
+pub mod plan;
+pub mod plugin;
+pub mod profile;
 pub mod reddit;
+pub mod reload;
+pub mod undo;
@@ -0,0 +1,179 @@
+use std::fs;
+
+use adolib::{
+    console::ConsoleTrait,
+    data::types::{AdoDataArtifact, AdoDataArtifactType},
+    llm::chain::LLMChain,
+    patch,
+};
+use log::{error, info};
+
+use crate::{
+    agentic::{self, AgenticUi},
+    commands::UserCommansTrait,
+    session::Session,
+};
+
+const USAGE: &str = "usage: /plan [on|off|show|approve|discard]";
+
+pub struct CommandPlan<'a> {
+    session: &'a Session,
+}
+
+/// Surfaces an approved plan running on the console, without asking again
+/// about what the plan already shows
+struct ApprovedPlan<'c> {
+    console: &'c dyn ConsoleTrait,
+}
+
+impl AgenticUi for ApprovedPlan<'_> {
+    fn notify(&self, text: &str) {
+        self.console.print_line(text);
+    }
+
+    fn show_diff(&self, _diff: &str) {}
+
+    fn confirm(&self, question: &str) -> bool {
+        info!("plan approved, auto-approving: {question}");
+        true
+    }
+}
+
+/// What a planned step would do, as reviewed with `/plan show`
+fn preview(artifact: &AdoDataArtifact, session: &Session) -> String {
+    match artifact.artifact_type {
+        AdoDataArtifactType::File => {
+            let Some(path) = &artifact.path else {
+                return String::new();
+            };
+
+            let current = fs::read_to_string(session.resolve(path)).unwrap_or_default();
+            format!(
+                "```diff\n{}\n```",
+                patch::unified_diff(path, &current, &artifact.content).trim_end()
+            )
+        }
+        AdoDataArtifactType::Edit => {
+            let old = artifact.old.as_deref().unwrap_or_default();
+            let lines: Vec<String> = old
+                .lines()
+                .map(|l| format!("-{l}"))
+                .chain(artifact.content.lines().map(|l| format!("+{l}")))
+                .collect();
+
+            format!("```diff\n{}\n```", lines.join("\n"))
+        }
+        AdoDataArtifactType::Diff => format!("```diff\n{}\n```", artifact.content.trim_end()),
+        _ => String::new(),
+    }
+}
+
+impl<'a> CommandPlan<'a> {
+    #[must_use]
+    pub fn new(session: &'a Session) -> Self {
+        Self { session }
+    }
+
+    fn set_mode(&self, on: bool, console: &dyn ConsoleTrait) {
+        self.session.set_plan_mode(on);
+
+        if on {
+            console.print_markdown(
+                "_plan mode on: the agent only reads, its changes are planned for you to `/plan approve`_",
+            );
+            return;
+        }
+
+        let pending = self.session.plan().len();
+
+        if pending == 0 {
+            console.print_markdown("_plan mode off_");
+        } else {
+            console.print_markdown(&format!(
+                "_plan mode off, {pending} planned step(s) left: `/plan approve` or `/plan discard`_"
+            ));
+        }
+    }
+
+    fn show(&self, console: &dyn ConsoleTrait) {
+        let plan = self.session.plan();
+
+        if plan.is_empty() {
+            console.print_markdown("_nothing planned_");
+            return;
+        }
+
+        let mut lines = vec!["# Plan".to_string()];
+
+        for (i, artifact) in plan.iter().enumerate() {
+            lines.push(String::new());
+            lines.push(format!(
+                "## {}. {}",
+                i.saturating_add(1),
+                agentic::describe_step(artifact)
+            ));
+
+            let preview = preview(artifact, self.session);
+            if !preview.is_empty() {
+                lines.push(String::new());
+                lines.push(preview);
+            }
+        }
+
+        lines.push(String::new());
+        lines.push(
+            "`/plan approve` to execute every step, `/plan discard` to drop them".to_string(),
+        );
+
+        console.print_markdown(&lines.join("\n"));
+    }
+
+    fn approve(&self, chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        if self.session.plan().is_empty() {
+            console.print_markdown("_nothing planned_");
+            return;
+        }
+
+        let ui = ApprovedPlan { console };
+        self.session.begin_turn("/plan approve", chain.turns().len());
+
+        let Some(results) = agentic::execute_plan(&ui, self.session) else {
+            return;
+        };
+
+        // the agent carries on from the results, e.g. to check its changes
+        let message = format!(
+            "The user approved the plan and its steps were executed, results are indexed by step:\n\n{results}"
+        );
+
+        if let Err(e) = chain.link(message, console) {
+            error!("plan results failure ({e})");
+            console.error_message(&format!("{e}"));
+        }
+    }
+}
+
+impl UserCommansTrait for CommandPlan<'_> {
+    fn name(&self) -> &'static str {
+        "plan [on|off|show|approve|discard]"
+    }
+
+    fn desc(&self) -> &'static str {
+        "toggle plan mode, where the agent only reads and plans its changes, review, approve or discard them"
+    }
+
+    fn callback(&mut self, input: &str, chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        match input.trim() {
+            "" => self.set_mode(!self.session.plan_mode(), console),
+            "on" => self.set_mode(true, console),
+            "off" => self.set_mode(false, console),
+            "show" => self.show(console),
+            "approve" => self.approve(chain, console),
+            "discard" => {
+                let dropped = self.session.take_plan().len();
+                console.print_markdown(&format!("_{dropped} planned step(s) discarded_"));
+            }
+            _ => console.error_message(USAGE),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use adolib::{console::ConsoleTrait, llm::chain::LLMChain};
+use anyhow::Result;
+use log::error;
+
+use crate::{
+    commands::UserCommansTrait,
+    plugins::{Plugin, PluginContext, PluginOutput},
+    session::Session,
+};
+
+/// A plugin command, running its `ado-<name>` executable
+pub struct CommandPlugin<'a> {
+    plugin: Plugin,
+    desc: String,
+    session: &'a Session,
+}
+
+impl<'a> CommandPlugin<'a> {
+    #[must_use]
+    pub fn new(plugin: Plugin, session: &'a Session) -> Self {
+        let desc = format!("run the {} plugin", plugin.path.display());
+
+        Self {
+            plugin,
+            desc,
+            session,
+        }
+    }
+
+    fn run(&self, input: &str, chain: &mut LLMChain, console: &dyn ConsoleTrait) -> Result<()> {
+        let arguments = shell_words::split(input)?;
+
+        let context = PluginContext::new(
+            &self.plugin.name,
+            arguments,
+            chain.model(),
+            self.session.cwd(),
+            chain.turns(),
+        );
+
+        console.enter_thinking("");
+        let output = self.plugin.run(&context);
+        console.leave_thinking();
+
+        match output? {
+            PluginOutput::Markdown(text) if text.is_empty() => {}
+            PluginOutput::Markdown(text) => console.print_markdown(&text),
+            PluginOutput::Data(data) => {
+                self.session.begin_turn(
+                    &format!("/{} {}", self.plugin.name, input.trim()),
+                    chain.turns().len(),
+                );
+
+                // artifacts were executed, the agent carries on from their
+                // results
+                if let Some(results) = console.io(data) {
+                    let message = format!(
+                        "The /{} command executed artifacts, their results:\n\n{results}",
+                        self.plugin.name
+                    );
+                    chain.link(message, console)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl UserCommansTrait for CommandPlugin<'_> {
+    fn name(&self) -> &str {
+        &self.plugin.name
+    }
+
+    fn desc(&self) -> &str {
+        &self.desc
+    }
+
+    fn callback(&mut self, input: &str, chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        if let Err(e) = self.run(input, chain, console) {
+            error!("plugin failure ({e:#})");
+            console.error_message(&format!("{e:#}"));
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use adolib::{config::loader::AdoConfig, console::ConsoleTrait, llm::chain::LLMChain};
+use log::error;
+
+use crate::commands::UserCommansTrait;
+
+/// `/profile none` goes back to the plain config
+const NO_PROFILE: &str = "none";
+
+pub struct CommandProfile {
+    config: AdoConfig,
+    pending: Option<AdoConfig>,
+}
+
+impl CommandProfile {
+    #[must_use]
+    pub fn new(config: &AdoConfig) -> Self {
+        Self {
+            config: config.clone(),
+            pending: None,
+        }
+    }
+
+    fn list(&self) -> String {
+        let active = self.config.profile().unwrap_or(NO_PROFILE);
+
+        let mut lines = vec![format!("# Profile: {active}"), String::new()];
+
+        let profiles = self.config.profiles();
+
+        if profiles.is_empty() {
+            lines.push("No `[profile.<name>]` section in the config".to_string());
+        }
+
+        for p in profiles {
+            if p == active {
+                lines.push(format!("* **{p}**"));
+            } else {
+                lines.push(format!("* {p}"));
+            }
+        }
+
+        lines.join("\n")
+    }
+}
+
+impl UserCommansTrait for CommandProfile {
+    fn name(&self) -> &'static str {
+        "profile"
+    }
+
+    fn desc(&self) -> &'static str {
+        "show or switch the config profile"
+    }
+
+    fn callback(&mut self, input: &str, _chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        let input = input.trim();
+
+        if input.is_empty() {
+            console.print_markdown(&self.list());
+            return;
+        }
+
+        let profile = (input != NO_PROFILE).then_some(input);
+
+        match self.config.with_profile(profile) {
+            Ok(config) => {
+                self.pending = Some(config);
+                console.print_markdown(&format!("# Profile: {input}"));
+            }
+            Err(e) => {
+                error!("unable to switch profile ({e})");
+                console.error_message(&format!("{e}"));
+            }
+        }
+    }
+
+    fn take_config(&mut self) -> Option<AdoConfig> {
+        self.pending.take()
+    }
+}
@@ -9,7 +9,7 @@ use log::{error, info};
 use crate::commands::UserCommansTrait;
 
 pub struct CommandReddit<'a> {
-    model: String,
+    model: Option<String>,
     cache: &'a KVCache,
 }
 
@@ -57,7 +57,7 @@ impl<'a> CommandReddit<'a> {
             input.as_ref()
         );
 
-        let ret = chain.message(query, Some(&self.model))?;
+        let ret = chain.message(query, self.model.as_deref())?;
 
         Ok(ret)
     }
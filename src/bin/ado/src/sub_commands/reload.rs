@@ -0,0 +1,46 @@
+use adolib::{config::loader::AdoConfig, console::ConsoleTrait, llm::chain::LLMChain};
+use log::error;
+
+use crate::commands::UserCommansTrait;
+
+pub struct CommandReload {
+    config: AdoConfig,
+    pending: Option<AdoConfig>,
+}
+
+impl CommandReload {
+    #[must_use]
+    pub fn new(config: &AdoConfig) -> Self {
+        Self {
+            config: config.clone(),
+            pending: None,
+        }
+    }
+}
+
+impl UserCommansTrait for CommandReload {
+    fn name(&self) -> &'static str {
+        "reload"
+    }
+
+    fn desc(&self) -> &'static str {
+        "re-read config, ADO.md and skills, keeping the conversation"
+    }
+
+    fn callback(&mut self, _input: &str, _chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        match self.config.reload() {
+            Ok(config) => {
+                self.pending = Some(config);
+                console.print_markdown("# Reloaded");
+            }
+            Err(e) => {
+                error!("unable to reload ({e})");
+                console.error_message(&format!("{e}"));
+            }
+        }
+    }
+
+    fn take_config(&mut self) -> Option<AdoConfig> {
+        self.pending.take()
+    }
+}
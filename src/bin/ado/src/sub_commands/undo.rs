@@ -0,0 +1,61 @@
+use adolib::{console::ConsoleTrait, llm::chain::LLMChain};
+use log::error;
+
+use crate::{commands::UserCommansTrait, session::Session};
+
+pub struct CommandUndo<'a> {
+    session: &'a Session,
+}
+
+impl<'a> CommandUndo<'a> {
+    #[must_use]
+    pub fn new(session: &'a Session) -> Self {
+        Self { session }
+    }
+}
+
+impl UserCommansTrait for CommandUndo<'_> {
+    fn name(&self) -> &'static str {
+        "undo [count]"
+    }
+
+    fn desc(&self) -> &'static str {
+        "restore the files changed by the last agent writes (1 by default)"
+    }
+
+    fn callback(&mut self, input: &str, _chain: &mut LLMChain, console: &dyn ConsoleTrait) {
+        let input = input.trim();
+
+        let count = if input.is_empty() {
+            1
+        } else if let Ok(count) = input.parse::<usize>() {
+            count
+        } else {
+            console.error_message("usage: /undo [count]");
+            return;
+        };
+
+        if self.session.undoable() == 0 {
+            console.print_markdown("_nothing to undo_");
+            return;
+        }
+
+        match self.session.undo(count) {
+            Ok(done) => {
+                let mut lines = vec!["# Undo".to_string(), String::new()];
+                lines.extend(done.iter().map(|d| format!("* {d}")));
+                lines.push(String::new());
+                lines.push(format!(
+                    "{} more write(s) can be undone",
+                    self.session.undoable()
+                ));
+
+                console.print_markdown(&lines.join("\n"));
+            }
+            Err(e) => {
+                error!("undo failure ({e:#})");
+                console.error_message(&format!("{e:#}"));
+            }
+        }
+    }
+}
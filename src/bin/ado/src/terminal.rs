@@ -15,21 +15,20 @@ use crossterm::{execute, style, terminal};
 use log::{error, info};
 use which::which;
 
-use crate::{agentic, spinner::AdoSpinner};
+use crate::{
+    agentic::{self, AgenticUi},
+    session::Session,
+    spinner::AdoSpinner,
+};
 
 ///////////////////////////////////////////////////////////////////////////////
 // Console — prints directly to stdout
 ///////////////////////////////////////////////////////////////////////////////
 
-pub struct Console {
+pub struct Console<'a> {
     glow: Option<PathBuf>,
     spinner: AdoSpinner,
-}
-
-impl Default for Console {
-    fn default() -> Self {
-        Self::new()
-    }
+    session: &'a Session,
 }
 
 fn print_separator() {
@@ -83,13 +82,68 @@ fn print_action(text: &str) {
     );
 }
 
-impl Console {
+/// Unified diff with added lines in green, removed ones in red
+fn print_diff(diff: &str) {
+    let mut stdout = io::stdout();
+
+    for line in diff.lines() {
+        let color = if line.starts_with("+++") || line.starts_with("---") {
+            style::Color::White
+        } else if line.starts_with('+') {
+            style::Color::Green
+        } else if line.starts_with('-') {
+            style::Color::Red
+        } else if line.starts_with("@@") {
+            style::Color::Cyan
+        } else {
+            style::Color::Reset
+        };
+
+        let _ = execute!(
+            stdout,
+            style::SetForegroundColor(color),
+            style::Print(format!("{line}\n")),
+            style::ResetColor
+        );
+    }
+}
+
+impl AgenticUi for Console<'_> {
+    fn notify(&self, text: &str) {
+        print_action(text);
+    }
+
+    fn show_diff(&self, diff: &str) {
+        print_diff(diff);
+    }
+
+    fn confirm(&self, question: &str) -> bool {
+        let mut stdout = io::stdout();
+        let _ = execute!(
+            stdout,
+            style::SetForegroundColor(style::Color::Yellow),
+            style::Print(format!("  {question} [y/N] ")),
+            style::ResetColor
+        );
+        let _ = stdout.flush();
+
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer).is_err() {
+            return false;
+        }
+
+        matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+    }
+}
+
+impl<'a> Console<'a> {
     #[must_use]
-    pub fn new() -> Self {
+    pub fn new(session: &'a Session) -> Self {
         let glow = which("glow").ok();
         Self {
             glow,
             spinner: AdoSpinner::new(),
+            session,
         }
     }
 
@@ -132,12 +186,13 @@ impl Console {
                 self.display_text(&format!("```{}\n{}\n```", lang, artifact.content));
             }
             AdoDataArtifactType::Note => self.display_text(&artifact.content),
+            AdoDataArtifactType::Diff => print_diff(&artifact.content),
             _ => error!("artifact {} not handled in Console", artifact.artifact_type),
         }
     }
 }
 
-impl ConsoleTrait for Console {
+impl ConsoleTrait for Console<'_> {
     fn error_message(&self, message: &str) {
         let mut stdout = io::stdout();
         let _ = execute!(
@@ -169,19 +224,8 @@ impl ConsoleTrait for Console {
             }
             AdoDataStatus::Partial => {
                 self.display_text(&data.response.message);
-                let mut results = Vec::new();
-                if let Some(artifacts) = &data.response.artifacts {
-                    for artifact in artifacts {
-                        if let Some(r) = agentic::execute_partial_artifact(artifact, &print_action) {
-                            results.push(r);
-                        }
-                    }
-                }
-                if results.is_empty() {
-                    None
-                } else {
-                    Some(results.join(" "))
-                }
+                let artifacts = data.response.artifacts.as_deref().unwrap_or_default();
+                agentic::execute_partial_artifacts(artifacts, self, self.session)
             }
         };
 
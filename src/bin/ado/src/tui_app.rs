@@ -12,19 +12,23 @@ use crate::{
 };
 
 pub fn run(mut commands: UserCommands, history_file: &Path) -> Result<()> {
-    // Build the list of command names and history path before moving commands
-    let command_names: Vec<String> = commands.list_commands().iter().map(|c| c.name().to_string()).collect();
+    // Build the list of commands and history path before moving commands
+    let command_names: Vec<(String, String)> = commands
+        .list_commands()
+        .iter()
+        .map(|c| (c.name().to_string(), c.desc().to_string()))
+        .collect();
 
     if let Ok(banner) = render_banner(format!("{} {PKG_VERSION}", PKG_NAME.to_uppercase())) {
         println!("{banner}");
     }
 
-    let console = Console::new();
+    let console = Console::new(commands.session());
     let mut editor = input::create_editor(history_file, command_names)?;
 
     loop {
         let model = commands.current_model();
-        match input::read_line(&mut editor, &model)? {
+        match input::read_line(&mut editor, &model, commands.current_profile())? {
             InputResult::Line(line) => {
                 let trimmed = line.trim().to_string();
                 if trimmed.is_empty() {
@@ -0,0 +1,341 @@
+//! First-run configuration. Walks the user through picking a provider and a
+//! model, probing the provider for the models it actually serves, and writes
+//! the result with `AdoConfig::sync`.
+
+use std::{
+    env,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use adolib::{
+    config::loader::AdoConfig,
+    llm::{
+        chain::LLMChain,
+        config::{ClaudeConfig, ConfigOllama, OLLAMA_DEFAULT_ENDPOINT},
+    },
+};
+use anyhow::{Context, Result, bail};
+use crossterm::style::Stylize;
+use log::error;
+
+const PROVIDERS: &[&str] = &["claude", "ollama"];
+const CLAUDE_FALLBACK_MODEL: &str = "claude-sonnet-4-6";
+const ANTHROPIC_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+
+struct Wizard<R, W> {
+    input: R,
+    output: W,
+    /// models a provider serves, [`LLMChain::available_models`] but in tests
+    available_models: fn(&AdoConfig) -> adolib::error::Result<Vec<String>>,
+}
+
+impl<R, W> Wizard<R, W>
+where
+    R: BufRead,
+    W: Write,
+{
+    fn ask(&mut self, question: &str, default: Option<&str>) -> Result<String> {
+        loop {
+            match default {
+                Some(d) if !d.is_empty() => {
+                    write!(self.output, "{question} [{}]: ", d.dark_grey())?;
+                }
+                _ => write!(self.output, "{question}: ")?,
+            }
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                bail!("configuration aborted");
+            }
+
+            let answer = line.trim();
+
+            if !answer.is_empty() {
+                return Ok(answer.to_string());
+            }
+
+            if let Some(d) = default {
+                return Ok(d.to_string());
+            }
+        }
+    }
+
+    /// Numbered menu, accepts either the number or the value itself
+    fn choose_index(
+        &mut self,
+        question: &str,
+        options: &[String],
+        default: usize,
+    ) -> Result<usize> {
+        for (i, o) in options.iter().enumerate() {
+            writeln!(self.output, "  {}) {o}", i.saturating_add(1))?;
+        }
+
+        let default_str = default.saturating_add(1).to_string();
+
+        loop {
+            let answer = self.ask(question, Some(&default_str))?;
+
+            if let Ok(n) = answer.parse::<usize>()
+                && let Some(i) = n.checked_sub(1).filter(|i| *i < options.len())
+            {
+                return Ok(i);
+            }
+
+            if let Some(i) = options.iter().position(|o| *o == answer) {
+                return Ok(i);
+            }
+
+            writeln!(
+                self.output,
+                "{}",
+                format!("{answer} is not a valid choice").red()
+            )?;
+        }
+    }
+
+    fn choose(&mut self, question: &str, options: &[String], default: usize) -> Result<String> {
+        let i = self.choose_index(question, options, default)?;
+        options.get(i).cloned().context("invalid choice")
+    }
+
+    fn choose_model(&mut self, config: &AdoConfig, fallback: Option<&str>) -> Result<String> {
+        writeln!(
+            self.output,
+            "{}",
+            "Fetching available models...".dark_grey()
+        )?;
+
+        match (self.available_models)(config) {
+            Ok(mut models) if !models.is_empty() => {
+                models.sort();
+                let default =
+                    fallback.and_then(|f| models.iter().position(|m| m == f)).unwrap_or(0);
+                self.choose("Default model", &models, default)
+            }
+            Ok(_) => {
+                writeln!(self.output, "{}", "No models found".yellow())?;
+                self.ask("Default model", fallback)
+            }
+            Err(e) => {
+                error!("unable to list models ({e})");
+                writeln!(
+                    self.output,
+                    "{}",
+                    format!("Unable to list models ({e})").yellow()
+                )?;
+                self.ask("Default model", fallback)
+            }
+        }
+    }
+
+    fn ollama(&mut self, path: &Path) -> Result<AdoConfig> {
+        let endpoint = self.ask("Ollama endpoint", Some(OLLAMA_DEFAULT_ENDPOINT))?;
+
+        let mut config = AdoConfig::with_ollama(path, ConfigOllama::new(endpoint, ""));
+
+        let model = self.choose_model(&config, None)?;
+        config.ollama_mut()?.model = model;
+
+        Ok(config)
+    }
+
+    /// Prefer a reference to the key over writing it in plaintext
+    fn claude_key(&mut self) -> Result<ClaudeConfig> {
+        let options = vec![
+            format!("environment variable (${ANTHROPIC_KEY_ENV})"),
+            "command (e.g. pass show anthropic)".to_string(),
+            "plaintext in config.toml".to_string(),
+        ];
+
+        let choice = self.choose_index("Read the Anthropic API key from", &options, 0)?;
+
+        let mut claude = ClaudeConfig::new("", "");
+
+        match choice {
+            0 => {
+                let name = self.ask("Environment variable", Some(ANTHROPIC_KEY_ENV))?;
+                if env::var(&name).is_err() {
+                    writeln!(
+                        self.output,
+                        "{}",
+                        format!("${name} is not set in this shell").yellow()
+                    )?;
+                }
+                claude.key_env = Some(name);
+            }
+            1 => claude.key_cmd = Some(self.ask("Command", Some("pass show anthropic"))?),
+            _ => claude.key = self.ask("Anthropic API key", None)?,
+        }
+
+        if let Err(e) = claude.resolve_key() {
+            writeln!(self.output, "{}", format!("{e}").yellow())?;
+        }
+
+        Ok(claude)
+    }
+
+    fn claude(&mut self, path: &Path) -> Result<AdoConfig> {
+        let claude = self.claude_key()?;
+
+        let mut config = AdoConfig::with_claude(path, claude);
+
+        let model = self.choose_model(&config, Some(CLAUDE_FALLBACK_MODEL))?;
+        config.claude_mut()?.model = model;
+
+        Ok(config)
+    }
+
+    fn run(&mut self, path: &Path) -> Result<AdoConfig> {
+        writeln!(self.output, "{}", "Welcome to ado!".bold())?;
+        writeln!(
+            self.output,
+            "No configuration found, let's create {}\n",
+            path.display()
+        )?;
+
+        let providers: Vec<String> = PROVIDERS.iter().map(ToString::to_string).collect();
+
+        let provider = self.choose("LLM provider", &providers, 0)?;
+
+        let config = match provider.as_str() {
+            "ollama" => self.ollama(path)?,
+            _ => self.claude(path)?,
+        };
+
+        config.sync().with_context(|| format!("Unable to write {}", path.display()))?;
+
+        writeln!(
+            self.output,
+            "\n{}\n",
+            format!("Wrote {}", path.display()).green()
+        )?;
+
+        Ok(config)
+    }
+}
+
+/// Interactively create the config file at `path` and return it.
+pub fn run(path: &Path) -> Result<AdoConfig> {
+    let mut wizard = Wizard {
+        input: io::stdin().lock(),
+        output: io::stdout(),
+        available_models: LLMChain::available_models,
+    };
+
+    wizard.run(path)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use adolib::error::Error;
+
+    use super::*;
+
+    fn models() -> Vec<String> {
+        [CLAUDE_FALLBACK_MODEL, "llama3", "gemma"].map(ToString::to_string).to_vec()
+    }
+
+    fn no_models(_config: &AdoConfig) -> adolib::error::Result<Vec<String>> {
+        Err(Error::ApiFailure {
+            message: "unreachable".to_string(),
+        })
+    }
+
+    /// Run the wizard on `input`, returns the config and what was written
+    fn run_wizard(
+        input: &str,
+        available_models: fn(&AdoConfig) -> adolib::error::Result<Vec<String>>,
+    ) -> (AdoConfig, String) {
+        let td = tempfile::tempdir().unwrap();
+        let path = td.path().join("config.toml");
+
+        let mut wizard = Wizard {
+            input: input.as_bytes(),
+            output: Vec::new(),
+            available_models,
+        };
+
+        let config = wizard.run(&path).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+
+        (config, written)
+    }
+
+    #[test]
+    fn test_ollama() {
+        // a wrong choice is asked again, the default endpoint is kept, the
+        // model is picked by number from the sorted list
+        let (config, written) = run_wizard("3\nollama\n\n3\n", |_| Ok(models()));
+
+        assert_eq!(config.llm_provider(), "ollama");
+        assert_eq!(config.ollama().unwrap().endpoint, OLLAMA_DEFAULT_ENDPOINT);
+        assert_eq!(config.ollama().unwrap().model, "llama3");
+        assert!(written.contains("provider = \"ollama\""));
+        assert!(written.contains("model = \"llama3\""));
+
+        // models can't be listed: typed instead
+        let (config, _) = run_wizard("2\nhttp://gpu:11434\nqwen3\n", no_models);
+
+        assert_eq!(config.ollama().unwrap().endpoint, "http://gpu:11434");
+        assert_eq!(config.ollama().unwrap().model, "qwen3");
+    }
+
+    #[test]
+    fn test_claude_key_env() {
+        let (config, written) = run_wizard("1\n1\nADO_WIZARD_TEST_KEY\n\n", |_| Ok(models()));
+
+        let claude = config.claude().unwrap();
+        assert_eq!(config.llm_provider(), "claude");
+        assert_eq!(claude.key_env.as_deref(), Some("ADO_WIZARD_TEST_KEY"));
+        assert!(claude.key.is_empty());
+        // the fallback model is the default choice
+        assert_eq!(claude.model, CLAUDE_FALLBACK_MODEL);
+        assert!(written.contains("key_env = \"ADO_WIZARD_TEST_KEY\""));
+    }
+
+    #[test]
+    fn test_claude_key_cmd() {
+        let (config, written) = run_wizard("claude\n2\necho sk-test\n\n", no_models);
+
+        let claude = config.claude().unwrap();
+        assert_eq!(claude.key_cmd.as_deref(), Some("echo sk-test"));
+        assert_eq!(claude.model, CLAUDE_FALLBACK_MODEL);
+        assert!(written.contains("key_cmd = \"echo sk-test\""));
+        // the key itself isn't written
+        assert!(!written.contains("\nkey = "));
+    }
+
+    #[test]
+    fn test_claude_key_plaintext() {
+        let (config, written) = run_wizard("1\n3\nsk-plain\ngemma\n", |_| Ok(models()));
+
+        let claude = config.claude().unwrap();
+        assert_eq!(claude.key, "sk-plain");
+        assert_eq!(claude.model, "gemma");
+        assert!(written.contains("key = \"sk-plain\""));
+    }
+
+    #[test]
+    fn test_aborted() {
+        let td = tempfile::tempdir().unwrap();
+        let path = td.path().join("config.toml");
+
+        let mut wizard = Wizard {
+            input: "2\n".as_bytes(),
+            output: Vec::new(),
+            available_models: |_| Ok(models()),
+        };
+
+        assert!(wizard.run(&path).is_err());
+        assert!(!path.exists());
+    }
+}
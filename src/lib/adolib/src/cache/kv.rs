@@ -1,12 +1,14 @@
 use std::{
+    collections::BTreeMap,
     env, fs,
     path::{Path, PathBuf},
+    thread::{self, JoinHandle},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use log::{error, info, warn};
 use md5::{Digest, Md5};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use sled::Db;
 
 use crate::{
@@ -14,22 +16,219 @@ use crate::{
     error::{Error, Result},
 };
 
-#[derive(Serialize, Deserialize)]
+/// On-disk layout of a cache value (version 1):
+///
+/// ```text
+/// [version: u8][expiry: u64 LE][kind: u8][payload ...]
+/// ```
+///
+/// `expiry` is seconds since the epoch, `0` meaning the entry never expires.
+/// Version 0 is the original JSON encoding (`{"expiry":..,"data":{..}}`), which
+/// always starts with `{` and is still readable.
+const KV_FORMAT_VERSION: u8 = 1;
+const KV_HEADER_LEN: usize = 10;
+const KV_LEGACY_MAGIC: u8 = b'{';
+
+const KV_KIND_STRING: u8 = 0;
+const KV_KIND_BYTES: u8 = 1;
+const KV_KIND_JSON: u8 = 2;
+
 enum KVEntryType {
     String(String),
+    Bytes(Vec<u8>),
+    /// serde encoded value, see [`KVCache::put`]
+    Json(String),
 }
 
-#[derive(Serialize, Deserialize)]
 struct KVEntry {
-    expiry: u64,
+    expiry: Option<u64>,
     data: KVEntryType,
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+enum KVLegacyEntryType {
+    String(String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct KVLegacyEntry {
+    expiry: u64,
+    data: KVLegacyEntryType,
+}
+
+impl KVEntryType {
+    fn kind(&self) -> &'static str {
+        match self {
+            KVEntryType::String(_) => "string",
+            KVEntryType::Bytes(_) => "bytes",
+            KVEntryType::Json(_) => "json",
+        }
+    }
+}
+
+impl KVEntry {
+    fn new(data: KVEntryType, expiry: &Duration) -> Result<Self> {
+        let expiry = if expiry.is_zero() {
+            None
+        } else {
+            Some(now_secs()?.saturating_add(expiry.as_secs()))
+        };
+
+        Ok(Self { expiry, data })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let (kind, payload) = match &self.data {
+            KVEntryType::String(s) => (KV_KIND_STRING, s.as_bytes()),
+            KVEntryType::Bytes(b) => (KV_KIND_BYTES, b.as_slice()),
+            KVEntryType::Json(j) => (KV_KIND_JSON, j.as_bytes()),
+        };
+
+        let mut data = Vec::with_capacity(KV_HEADER_LEN.saturating_add(payload.len()));
+        data.push(KV_FORMAT_VERSION);
+        data.extend_from_slice(&self.expiry.unwrap_or(0).to_le_bytes());
+        data.push(kind);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn from_legacy(data: &[u8]) -> Result<Self> {
+        let data = String::from_utf8(data.to_vec()).inspect_err(|_| {
+            error!("Unable to convert unsafe data to a string");
+        })?;
+
+        let entry: KVLegacyEntry = serde_json::from_str(&data).inspect_err(|_| {
+            error!("Unable to deserialize {data}");
+        })?;
+
+        let KVLegacyEntryType::String(s) = entry.data;
+
+        Ok(Self {
+            expiry: Some(entry.expiry),
+            data: KVEntryType::String(s),
+        })
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<Self> {
+        let Some((&version, rest)) = data.split_first() else {
+            return Err(Error::InvalidFormat);
+        };
+
+        match version {
+            KV_LEGACY_MAGIC => return Self::from_legacy(data),
+            KV_FORMAT_VERSION => {}
+            v => {
+                error!("Unsupported cache entry version {v}");
+                return Err(Error::InvalidFormat);
+            }
+        }
+
+        let (Some(expiry), Some((&kind, payload))) = (
+            rest.first_chunk::<8>(),
+            rest.get(8..).and_then(<[u8]>::split_first),
+        ) else {
+            error!("Truncated cache entry");
+            return Err(Error::InvalidFormat);
+        };
+
+        let expiry = match u64::from_le_bytes(*expiry) {
+            0 => None,
+            v => Some(v),
+        };
+
+        let data = match kind {
+            KV_KIND_STRING => KVEntryType::String(String::from_utf8(payload.to_vec())?),
+            KV_KIND_BYTES => KVEntryType::Bytes(payload.to_vec()),
+            KV_KIND_JSON => KVEntryType::Json(String::from_utf8(payload.to_vec())?),
+            k => {
+                error!("Unknown cache entry kind {k}");
+                return Err(Error::InvalidFormat);
+            }
+        };
+
+        Ok(Self { expiry, data })
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expiry.is_some_and(|expiry| now > expiry)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct KVRealmStats {
+    pub realm: String,
+    pub entries: u64,
+    pub expired: u64,
+    /// key + value bytes
+    pub size: u64,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct KVCacheStats {
+    pub realms: Vec<KVRealmStats>,
+    pub disk_size: u64,
+}
+
+impl KVCacheStats {
+    #[must_use]
+    pub fn entries(&self) -> u64 {
+        self.realms.iter().map(|r| r.entries).sum()
+    }
+
+    #[must_use]
+    pub fn expired(&self) -> u64 {
+        self.realms.iter().map(|r| r.expired).sum()
+    }
+
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.realms.iter().map(|r| r.size).sum()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct KVCache {
     db: Db,
 }
 
+fn now_secs() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+fn realm_key(realm: &str, key: &str) -> String {
+    format!("{realm}_{}", hash_str(key.as_bytes()))
+}
+
+/// Keys are stored as `<realm>_<md5>`. The hash never contains `_` so the
+/// realm is everything before the last one.
+fn realm_from_key(key: &[u8]) -> Option<String> {
+    let key = String::from_utf8_lossy(key);
+    let (realm, _) = key.rsplit_once('_')?;
+    Some(realm.to_string())
+}
+
+fn purge_expired_db(db: &Db) -> Result<u64> {
+    let now = now_secs()?;
+    let mut purged: u64 = 0;
+
+    for item in db.iter() {
+        let (key, value) = item?;
+
+        // entries this version can't read, e.g. written by a newer ado
+        // sharing the cache, are left alone
+        let expired = KVEntry::from_bytes(&value).is_ok_and(|entry| entry.is_expired(now));
+
+        if expired {
+            db.remove(&key)?;
+            purged = purged.saturating_add(1);
+        }
+    }
+
+    info!("purged {purged} expired entries");
+
+    Ok(purged)
+}
+
 fn hash_str(data: &[u8]) -> String {
     let mut hasher = Md5::new();
     hasher.update(data);
@@ -91,64 +290,418 @@ impl KVCache {
         KVCache::new(db_file)
     }
 
+    fn insert(&self, realm: &str, key: &str, data: KVEntryType, expiry: &Duration) -> Result<()> {
+        let key = realm_key(realm, key);
+
+        let entry = KVEntry::new(data, expiry)?;
+
+        self.db.insert(key, entry.to_bytes())?;
+
+        Ok(())
+    }
+
+    fn lookup(&self, realm: &str, key: &str) -> Result<KVEntryType> {
+        let key = realm_key(realm, key);
+        let Ok(ret) = self.db.get(&key) else {
+            return Err(Error::NotFound);
+        };
+
+        let Some(data) = ret else {
+            return Err(Error::NotFound);
+        };
+
+        let entry = KVEntry::from_bytes(&data)?;
+
+        if entry.is_expired(now_secs()?) {
+            warn!("{key} is expired");
+            if let Err(e) = self.db.remove(&key) {
+                error!("Unable to delete {key} ({e})");
+            }
+            return Err(Error::Expired);
+        }
+
+        Ok(entry.data)
+    }
+
+    /// Store `value` in `realm`. A zero `expiry` ([`STORE_PERMANENT`]) never
+    /// expires.
+    ///
+    /// [`STORE_PERMANENT`]: crate::const_vars::STORE_PERMANENT
     pub fn add_string<R, K, V>(&self, realm: R, key: K, value: V, expiry: &Duration) -> Result<()>
     where
         R: AsRef<str>,
         K: AsRef<str>,
         V: Into<String>,
     {
-        let key = format!("{}_{}", realm.as_ref(), hash_str(key.as_ref().as_bytes()));
-
-        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.insert(
+            realm.as_ref(),
+            key.as_ref(),
+            KVEntryType::String(value.into()),
+            expiry,
+        )
+    }
 
-        let entry = KVEntry {
-            expiry: now + expiry.as_secs(),
-            data: KVEntryType::String(value.into()),
-        };
+    pub fn get_string<R, K>(&self, realm: R, key: K) -> Result<String>
+    where
+        R: AsRef<str>,
+        K: AsRef<str>,
+    {
+        match self.lookup(realm.as_ref(), key.as_ref())? {
+            KVEntryType::String(s) => Ok(s),
+            other => Err(Error::TypeError {
+                error: format!("expected string, found {}", other.kind()),
+            }),
+        }
+    }
 
-        let entry_str = serde_json::to_string(&entry)?;
+    pub fn add_bytes<R, K, V>(&self, realm: R, key: K, value: V, expiry: &Duration) -> Result<()>
+    where
+        R: AsRef<str>,
+        K: AsRef<str>,
+        V: Into<Vec<u8>>,
+    {
+        self.insert(
+            realm.as_ref(),
+            key.as_ref(),
+            KVEntryType::Bytes(value.into()),
+            expiry,
+        )
+    }
 
-        self.db.insert(key, entry_str.as_bytes())?;
+    pub fn get_bytes<R, K>(&self, realm: R, key: K) -> Result<Vec<u8>>
+    where
+        R: AsRef<str>,
+        K: AsRef<str>,
+    {
+        match self.lookup(realm.as_ref(), key.as_ref())? {
+            KVEntryType::Bytes(b) => Ok(b),
+            other => Err(Error::TypeError {
+                error: format!("expected bytes, found {}", other.kind()),
+            }),
+        }
+    }
 
-        Ok(())
+    /// Store any serde serializable `value`, retrieved with [`KVCache::get`].
+    pub fn put<R, K, T>(&self, realm: R, key: K, value: &T, expiry: &Duration) -> Result<()>
+    where
+        R: AsRef<str>,
+        K: AsRef<str>,
+        T: Serialize + ?Sized,
+    {
+        let data = serde_json::to_string(value)?;
+        self.insert(
+            realm.as_ref(),
+            key.as_ref(),
+            KVEntryType::Json(data),
+            expiry,
+        )
     }
 
-    pub fn get_string<R, K>(&self, realm: R, key: K) -> Result<String>
+    pub fn get<R, K, T>(&self, realm: R, key: K) -> Result<T>
     where
         R: AsRef<str>,
         K: AsRef<str>,
+        T: DeserializeOwned,
     {
-        let key = format!("{}_{}", realm.as_ref(), hash_str(key.as_ref().as_bytes()));
-        let Ok(ret) = self.db.get(&key) else {
-            return Err(Error::NotFound);
-        };
+        match self.lookup(realm.as_ref(), key.as_ref())? {
+            KVEntryType::Json(j) => Ok(serde_json::from_str(&j)?),
+            other => Err(Error::TypeError {
+                error: format!("expected json, found {}", other.kind()),
+            }),
+        }
+    }
 
-        let Some(data) = ret else {
-            return Err(Error::NotFound);
-        };
+    /// Every realm that currently holds at least one entry, sorted.
+    pub fn realms(&self) -> Result<Vec<String>> {
+        let mut realms = Vec::new();
 
-        let data = String::from_utf8(data.to_vec()).map_err(|e| {
-            error!("Unable to convert unsafe data to a string");
-            e
-        })?;
+        for key in self.db.iter().keys() {
+            if let Some(realm) = realm_from_key(&key?)
+                && !realms.contains(&realm)
+            {
+                realms.push(realm);
+            }
+        }
 
-        let entry: KVEntry = serde_json::from_str(&data).map_err(|e| {
-            error!("Unable to deserialize {data}");
-            e
-        })?;
+        realms.sort();
 
-        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        Ok(realms)
+    }
 
-        if now > entry.expiry {
-            warn!("{key} is expired");
-            if let Err(e) = self.db.remove(&key) {
-                error!("Unable to delete {key} ({e})");
+    /// Number of entries stored in `realm`, expired ones included.
+    pub fn count<R>(&self, realm: R) -> Result<u64>
+    where
+        R: AsRef<str>,
+    {
+        let mut count: u64 = 0;
+
+        for key in self.db.iter().keys() {
+            if realm_from_key(&key?).as_deref() == Some(realm.as_ref()) {
+                count = count.saturating_add(1);
             }
-            return Err(Error::Expired);
         }
 
-        let KVEntryType::String(s) = entry.data;
+        Ok(count)
+    }
+
+    pub fn stats(&self) -> Result<KVCacheStats> {
+        let now = now_secs()?;
+
+        let mut realms: BTreeMap<String, KVRealmStats> = BTreeMap::new();
+
+        for item in self.db.iter() {
+            let (key, value) = item?;
+
+            let Some(realm) = realm_from_key(&key) else {
+                warn!("ignoring unexpected key {}", String::from_utf8_lossy(&key));
+                continue;
+            };
+
+            // as in the purge, entries this version can't read aren't expired
+            let expired = KVEntry::from_bytes(&value).is_ok_and(|e| e.is_expired(now));
+
+            let stats = realms.entry(realm.clone()).or_insert_with(|| KVRealmStats {
+                realm,
+                ..Default::default()
+            });
+
+            stats.entries = stats.entries.saturating_add(1);
+            stats.size =
+                stats.size.saturating_add(key.len() as u64).saturating_add(value.len() as u64);
+
+            if expired {
+                stats.expired = stats.expired.saturating_add(1);
+            }
+        }
+
+        Ok(KVCacheStats {
+            realms: realms.into_values().collect(),
+            disk_size: self.db.size_on_disk()?,
+        })
+    }
+
+    /// Remove every entry of `realm`, or the whole cache when `None`.
+    /// Returns the number of entries removed.
+    pub fn clear(&self, realm: Option<&str>) -> Result<u64> {
+        let Some(realm) = realm else {
+            let count = self.db.len() as u64;
+            self.db.clear()?;
+            self.db.flush()?;
+            info!("cleared {count} entries");
+            return Ok(count);
+        };
+
+        let mut removed: u64 = 0;
+
+        for key in self.db.iter().keys() {
+            let key = key?;
+            if realm_from_key(&key).as_deref() == Some(realm) {
+                self.db.remove(&key)?;
+                removed = removed.saturating_add(1);
+            }
+        }
+
+        self.db.flush()?;
+
+        info!("cleared {removed} entries from {realm}");
+
+        Ok(removed)
+    }
+
+    /// Eagerly remove expired entries instead of waiting for them to be read.
+    /// Returns the number of entries removed.
+    pub fn purge_expired(&self) -> Result<u64> {
+        purge_expired_db(&self.db)
+    }
+
+    /// Run [`KVCache::purge_expired`] on a background thread so startup isn't
+    /// delayed by large caches.
+    #[must_use]
+    pub fn sweep_in_background(&self) -> JoinHandle<()> {
+        let db = self.db.clone();
+
+        thread::spawn(move || {
+            if let Err(e) = purge_expired_db(&db) {
+                error!("cache sweep failure ({e})");
+            }
+        })
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use crate::const_vars::{CACHE_05_DAYS, STORE_PERMANENT};
+
+    use super::*;
+
+    fn make_cache() -> (tempfile::TempDir, KVCache) {
+        let td = tempfile::Builder::new().prefix("kvcache_").tempdir().unwrap();
+        let cache = KVCache::new(td.path().join("cache.sled")).unwrap();
+        (td, cache)
+    }
+
+    fn add_expired(cache: &KVCache, realm: &str, key: &str) {
+        let entry = KVEntry {
+            expiry: Some(1),
+            data: KVEntryType::String("old".into()),
+        };
+        cache.db.insert(realm_key(realm, key), entry.to_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_realms_and_count() {
+        let (_td, cache) = make_cache();
+
+        cache.add_string("reddit", "a", "1", &CACHE_05_DAYS).unwrap();
+        cache.add_string("reddit", "b", "2", &CACHE_05_DAYS).unwrap();
+        cache.add_string("gcse", "a", "3", &CACHE_05_DAYS).unwrap();
+        cache.add_string("llm_claude", "a", "4", &CACHE_05_DAYS).unwrap();
+
+        assert_eq!(
+            cache.realms().unwrap(),
+            vec!["gcse", "llm_claude", "reddit"]
+        );
+        assert_eq!(cache.count("reddit").unwrap(), 2);
+        assert_eq!(cache.count("llm_claude").unwrap(), 1);
+        assert_eq!(cache.count("llm").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_stats() {
+        let (_td, cache) = make_cache();
+
+        cache.add_string("reddit", "a", "1", &CACHE_05_DAYS).unwrap();
+        add_expired(&cache, "reddit", "b");
+        cache.add_string("gcse", "a", "3", &Duration::from_mins(1)).unwrap();
+
+        let stats = cache.stats().unwrap();
+
+        assert_eq!(stats.entries(), 3);
+        assert_eq!(stats.expired(), 1);
+        assert_eq!(stats.realms.len(), 2);
+        assert!(stats.size() > 0);
+    }
+
+    #[test]
+    fn test_clear() {
+        let (_td, cache) = make_cache();
+
+        cache.add_string("reddit", "a", "1", &CACHE_05_DAYS).unwrap();
+        cache.add_string("gcse", "a", "2", &CACHE_05_DAYS).unwrap();
+        cache.add_string("gcse", "b", "3", &CACHE_05_DAYS).unwrap();
+
+        assert_eq!(cache.clear(Some("gcse")).unwrap(), 2);
+        assert_eq!(cache.realms().unwrap(), vec!["reddit"]);
+
+        assert_eq!(cache.clear(None).unwrap(), 1);
+        assert!(cache.realms().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_purge_expired() {
+        let (_td, cache) = make_cache();
+
+        cache.add_string("reddit", "a", "1", &CACHE_05_DAYS).unwrap();
+        add_expired(&cache, "reddit", "b");
+        add_expired(&cache, "gcse", "c");
+
+        assert_eq!(cache.purge_expired().unwrap(), 2);
+        assert_eq!(cache.count("reddit").unwrap(), 1);
+        assert_eq!(cache.get_string("reddit", "a").unwrap(), "1");
+
+        add_expired(&cache, "gcse", "d");
+        cache.sweep_in_background().join().unwrap();
+        assert_eq!(cache.count("gcse").unwrap(), 0);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Typed {
+        name: String,
+        values: Vec<u32>,
+    }
+
+    #[test]
+    fn test_typed() {
+        let (_td, cache) = make_cache();
+
+        let value = Typed {
+            name: "ado".into(),
+            values: vec![1, 2, 3],
+        };
+
+        cache.put("typed", "a", &value, &CACHE_05_DAYS).unwrap();
+        let ret: Typed = cache.get("typed", "a").unwrap();
+        assert_eq!(ret, value);
+
+        // type mismatches are reported instead of silently decoded
+        assert!(matches!(
+            cache.get_string("typed", "a"),
+            Err(Error::TypeError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_bytes() {
+        let (_td, cache) = make_cache();
+
+        let value = vec![0_u8, 0xff, b'{', 0x01];
+
+        cache.add_bytes("bin", "a", value.clone(), &CACHE_05_DAYS).unwrap();
+        assert_eq!(cache.get_bytes("bin", "a").unwrap(), value);
+    }
+
+    #[test]
+    fn test_permanent() {
+        let (_td, cache) = make_cache();
+
+        cache.add_string("perm", "a", "forever", &STORE_PERMANENT).unwrap();
+        assert_eq!(cache.get_string("perm", "a").unwrap(), "forever");
+
+        assert_eq!(cache.purge_expired().unwrap(), 0);
+        assert_eq!(cache.stats().unwrap().expired(), 0);
+    }
+
+    #[test]
+    fn test_legacy_format() {
+        let (_td, cache) = make_cache();
+
+        let legacy = KVLegacyEntry {
+            expiry: now_secs().unwrap().saturating_add(3600),
+            data: KVLegacyEntryType::String("legacy".into()),
+        };
+        let legacy_str = serde_json::to_string(&legacy).unwrap();
+        cache.db.insert(realm_key("old", "a"), legacy_str.as_bytes()).unwrap();
+
+        assert_eq!(cache.get_string("old", "a").unwrap(), "legacy");
+    }
+
+    #[test]
+    fn test_unknown_version() {
+        let (_td, cache) = make_cache();
+
+        cache.db.insert(realm_key("future", "a"), vec![0x7f, 0, 0]).unwrap();
+
+        assert!(matches!(
+            cache.get_string("future", "a"),
+            Err(Error::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn test_purge_keeps_unknown_version() {
+        let (_td, cache) = make_cache();
+
+        cache.db.insert(realm_key("future", "a"), vec![0x7f, 0, 0]).unwrap();
+        add_expired(&cache, "reddit", "b");
 
-        Ok(s)
+        // stats agree with what a purge removes
+        assert_eq!(cache.stats().unwrap().expired(), 1);
+        assert_eq!(cache.purge_expired().unwrap(), 1);
+        assert_eq!(cache.count("future").unwrap(), 1);
+        assert_eq!(cache.count("reddit").unwrap(), 0);
     }
 }
@@ -0,0 +1,750 @@
+use std::{fmt::Display, ops::Range};
+
+use serde::{Deserialize, Serialize};
+use toml::{
+    Spanned,
+    de::{DeTable, DeValue},
+};
+
+use crate::config::loader::ConfigFile;
+
+///////////////////////////////////////////////////////////////////////////////
+// SCHEMA
+///////////////////////////////////////////////////////////////////////////////
+
+enum ConfigValueType {
+    String,
+    Integer,
+    Bool,
+    StringArray,
+    Table(&'static [ConfigKey]),
+    /// table of user named tables, each a partial override of the schema
+    Profiles(&'static [ConfigKey]),
+}
+
+struct ConfigKey {
+    name: &'static str,
+    kind: ConfigValueType,
+    required: bool,
+}
+
+const fn key(name: &'static str, kind: ConfigValueType, required: bool) -> ConfigKey {
+    ConfigKey {
+        name,
+        kind,
+        required,
+    }
+}
+
+// Must be kept in sync with the serde structs. `test_schema_matches_structs`
+// catches most drift.
+const OLLAMA_KEYS: &[ConfigKey] = &[
+    key("endpoint", ConfigValueType::String, true),
+    key("model", ConfigValueType::String, true),
+    key("thinking", ConfigValueType::Bool, false),
+    key("keep_alive", ConfigValueType::Integer, false),
+];
+
+const CLAUDE_KEYS: &[ConfigKey] = &[
+    key("model", ConfigValueType::String, true),
+    key("url", ConfigValueType::String, true),
+    key("anthropic_version", ConfigValueType::String, true),
+    key("key", ConfigValueType::String, false),
+    key("key_env", ConfigValueType::String, false),
+    key("key_cmd", ConfigValueType::String, false),
+    key("max_tokens", ConfigValueType::Integer, true),
+    key("instructions", ConfigValueType::StringArray, false),
+    key("logs", ConfigValueType::String, false),
+];
+
+const MESSAGE_CACHE_KEYS: &[ConfigKey] = &[key("ttl", ConfigValueType::Integer, false)];
+
+const LLM_KEYS: &[ConfigKey] = &[
+    key("provider", ConfigValueType::String, true),
+    key("ollama", ConfigValueType::Table(OLLAMA_KEYS), false),
+    key("claude", ConfigValueType::Table(CLAUDE_KEYS), false),
+    key(
+        "message_cache",
+        ConfigValueType::Table(MESSAGE_CACHE_KEYS),
+        false,
+    ),
+];
+
+const GOOGLE_KEYS: &[ConfigKey] = &[
+    key("cx", ConfigValueType::String, true),
+    key("geo", ConfigValueType::String, true),
+    key("key", ConfigValueType::String, true),
+    key("url", ConfigValueType::String, true),
+    key("cache_size", ConfigValueType::Integer, false),
+    key("cache_ttl", ConfigValueType::Integer, false),
+];
+
+const SEARCH_KEYS: &[ConfigKey] = &[key("google", ConfigValueType::Table(GOOGLE_KEYS), false)];
+
+const REDDIT_KEYS: &[ConfigKey] = &[key("model", ConfigValueType::String, false)];
+
+const GIT_KEYS: &[ConfigKey] = &[key("model", ConfigValueType::String, false)];
+
+const COMMAND_KEYS: &[ConfigKey] = &[
+    key("reddit", ConfigValueType::Table(REDDIT_KEYS), false),
+    key("git", ConfigValueType::Table(GIT_KEYS), false),
+];
+
+const AGENT_KEYS: &[ConfigKey] = &[
+    key("shell", ConfigValueType::String, false),
+    key("max_iterations", ConfigValueType::Integer, false),
+];
+
+const PROFILE_KEYS: &[ConfigKey] = &[
+    key("llm", ConfigValueType::Table(LLM_KEYS), false),
+    key("search", ConfigValueType::Table(SEARCH_KEYS), false),
+];
+
+const ROOT_KEYS: &[ConfigKey] = &[
+    key("llm", ConfigValueType::Table(LLM_KEYS), true),
+    key("search", ConfigValueType::Table(SEARCH_KEYS), false),
+    key("command", ConfigValueType::Table(COMMAND_KEYS), false),
+    key("agent", ConfigValueType::Table(AGENT_KEYS), false),
+    key("profile", ConfigValueType::Profiles(PROFILE_KEYS), false),
+];
+
+const PROVIDERS: &[&str] = &["ollama", "claude"];
+
+///////////////////////////////////////////////////////////////////////////////
+// ISSUES
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigIssueLevel {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigIssue {
+    pub level: ConfigIssueLevel,
+    /// 1-based, `None` when the problem isn't tied to a location
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for ConfigIssueLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ConfigIssueLevel::Error => "error",
+            ConfigIssueLevel::Warning => "warning",
+        };
+
+        write!(f, "{s}")
+    }
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {line}: {}: {}", self.level, self.message),
+            None => write!(f, "{}: {}", self.level, self.message),
+        }
+    }
+}
+
+impl ConfigIssue {
+    #[must_use]
+    pub fn is_error(&self) -> bool {
+        self.level == ConfigIssueLevel::Error
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// CHECK
+///////////////////////////////////////////////////////////////////////////////
+
+struct ConfigChecker<'a> {
+    text: &'a str,
+    issues: Vec<ConfigIssue>,
+    /// profiles and layers only override some keys, nothing is required
+    partial: bool,
+}
+
+fn section_name(path: &str) -> String {
+    if path.is_empty() {
+        "top level".to_string()
+    } else {
+        format!("[{path}]")
+    }
+}
+
+fn table_get<'a, 'i>(table: &'a DeTable<'i>, name: &str) -> Option<&'a Spanned<DeValue<'i>>> {
+    table.iter().find(|(k, _)| k.get_ref() == name).map(|(_, v)| v)
+}
+
+fn type_matches(kind: &ConfigValueType, value: &DeValue) -> bool {
+    match kind {
+        ConfigValueType::String => value.is_str(),
+        ConfigValueType::Integer => value.is_integer(),
+        ConfigValueType::Bool => value.is_bool(),
+        ConfigValueType::StringArray => {
+            value.as_array().is_some_and(|a| a.iter().all(|v| v.get_ref().is_str()))
+        }
+        ConfigValueType::Table(_) | ConfigValueType::Profiles(_) => value.is_table(),
+    }
+}
+
+fn type_name(kind: &ConfigValueType) -> &'static str {
+    match kind {
+        ConfigValueType::String => "a string",
+        ConfigValueType::Integer => "an integer",
+        ConfigValueType::Bool => "a boolean",
+        ConfigValueType::StringArray => "an array of strings",
+        ConfigValueType::Table(_) => "a table",
+        ConfigValueType::Profiles(_) => "a table of profiles",
+    }
+}
+
+impl<'a> ConfigChecker<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            issues: Vec::new(),
+            partial: false,
+        }
+    }
+
+    fn line(&self, span: &Range<usize>) -> usize {
+        let end = span.start.min(self.text.len());
+        let before = self.text.get(..end).unwrap_or_default();
+        before.matches('\n').count().saturating_add(1)
+    }
+
+    fn push(&mut self, level: ConfigIssueLevel, span: Option<Range<usize>>, message: String) {
+        let line = span.map(|s| self.line(&s));
+        self.issues.push(ConfigIssue {
+            level,
+            line,
+            message,
+        });
+    }
+
+    fn check_table(
+        &mut self,
+        path: &str,
+        table: &DeTable,
+        span: &Range<usize>,
+        schema: &'static [ConfigKey],
+    ) {
+        for (k, v) in table {
+            let name = k.get_ref().as_ref();
+
+            let Some(expected) = schema.iter().find(|s| s.name == name) else {
+                self.push(
+                    ConfigIssueLevel::Warning,
+                    Some(k.span()),
+                    format!("unknown key `{name}` in {}", section_name(path)),
+                );
+                continue;
+            };
+
+            let full_name = if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{path}.{name}")
+            };
+
+            if !type_matches(&expected.kind, v.get_ref()) {
+                self.push(
+                    ConfigIssueLevel::Error,
+                    Some(v.span()),
+                    format!(
+                        "`{full_name}` should be {}, found {}",
+                        type_name(&expected.kind),
+                        v.get_ref().type_str()
+                    ),
+                );
+                continue;
+            }
+
+            match (&expected.kind, v.get_ref().as_table()) {
+                (ConfigValueType::Table(sub), Some(sub_table)) => {
+                    self.check_table(&full_name, sub_table, &v.span(), sub);
+                }
+                (ConfigValueType::Profiles(sub), Some(profiles)) => {
+                    self.check_profiles(&full_name, profiles, sub);
+                }
+                _ => {}
+            }
+        }
+
+        if self.partial {
+            return;
+        }
+
+        for expected in schema.iter().filter(|s| s.required) {
+            let present = table.iter().any(|(k, _)| k.get_ref() == expected.name);
+
+            if !present {
+                let span = if path.is_empty() {
+                    None
+                } else {
+                    Some(span.clone())
+                };
+                self.push(
+                    ConfigIssueLevel::Error,
+                    span,
+                    format!(
+                        "missing required key `{}` in {}",
+                        expected.name,
+                        section_name(path)
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_profiles(&mut self, path: &str, profiles: &DeTable, schema: &'static [ConfigKey]) {
+        for (name, v) in profiles {
+            let full_name = format!("{path}.{}", name.get_ref());
+
+            let Some(profile) = v.get_ref().as_table() else {
+                self.push(
+                    ConfigIssueLevel::Error,
+                    Some(v.span()),
+                    format!(
+                        "`{full_name}` should be a table, found {}",
+                        v.get_ref().type_str()
+                    ),
+                );
+                continue;
+            };
+
+            let partial = std::mem::replace(&mut self.partial, true);
+            self.check_table(&full_name, profile, &v.span(), schema);
+            self.partial = partial;
+        }
+    }
+
+    fn check_provider(&mut self, root: &DeTable) {
+        let Some(llm_table) = table_get(root, "llm").and_then(|v| v.get_ref().as_table()) else {
+            return;
+        };
+
+        let Some(provider) = table_get(llm_table, "provider") else {
+            return;
+        };
+
+        let Some(name) = provider.get_ref().as_str() else {
+            return;
+        };
+
+        if !PROVIDERS.contains(&name) {
+            self.push(
+                ConfigIssueLevel::Error,
+                Some(provider.span()),
+                format!(
+                    "unknown provider `{name}`, expected one of {}",
+                    PROVIDERS.join(", ")
+                ),
+            );
+            return;
+        }
+
+        if !self.partial && !llm_table.iter().any(|(k, _)| k.get_ref() == name) {
+            self.push(
+                ConfigIssueLevel::Error,
+                Some(provider.span()),
+                format!("provider is `{name}` but [llm.{name}] is missing"),
+            );
+        }
+    }
+
+    fn check_claude_key(&mut self, root: &DeTable) {
+        let Some(claude) = table_get(root, "llm")
+            .and_then(|v| v.get_ref().as_table())
+            .and_then(|llm| table_get(llm, "claude"))
+        else {
+            return;
+        };
+
+        let Some(claude_table) = claude.get_ref().as_table() else {
+            return;
+        };
+
+        if let Some(key) = table_get(claude_table, "key") {
+            self.push(
+                ConfigIssueLevel::Warning,
+                Some(key.span()),
+                "API key stored in plaintext, consider `key_env` or `key_cmd`".into(),
+            );
+        } else if !self.partial
+            && table_get(claude_table, "key_env").is_none()
+            && table_get(claude_table, "key_cmd").is_none()
+        {
+            self.push(
+                ConfigIssueLevel::Error,
+                Some(claude.span()),
+                "one of `key`, `key_env` or `key_cmd` is required in [llm.claude]".into(),
+            );
+        }
+    }
+
+    fn check(mut self) -> Vec<ConfigIssue> {
+        let (root, errors) = DeTable::parse_recoverable(self.text);
+
+        for e in &errors {
+            self.push(ConfigIssueLevel::Error, e.span(), e.message().to_string());
+        }
+
+        self.check_table("", root.get_ref(), &root.span(), ROOT_KEYS);
+        self.check_provider(root.get_ref());
+        self.check_claude_key(root.get_ref());
+
+        //
+        // Everything looked fine so far, make sure serde agrees in case the
+        // schema above drifted from the structs
+        //
+        if !self.partial
+            && !self.issues.iter().any(ConfigIssue::is_error)
+            && let Err(e) = toml::from_str::<ConfigFile>(self.text)
+        {
+            self.push(ConfigIssueLevel::Error, e.span(), e.message().to_string());
+        }
+
+        self.issues.sort_by_key(|i| i.line);
+        self.issues
+    }
+}
+
+/// Validate a config file's content, reporting every problem found instead of
+/// stopping at the first one. Unknown keys are reported as warnings.
+#[must_use]
+pub fn check_config<S>(text: S) -> Vec<ConfigIssue>
+where
+    S: AsRef<str>,
+{
+    ConfigChecker::new(text.as_ref()).check()
+}
+
+/// Validate a layer other than the user config, e.g. the system or a project
+/// file: nothing is required, other layers may set it
+#[must_use]
+pub fn check_layer_config<S>(text: S) -> Vec<ConfigIssue>
+where
+    S: AsRef<str>,
+{
+    ConfigChecker {
+        partial: true,
+        ..ConfigChecker::new(text.as_ref())
+    }
+    .check()
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// OVERRIDES
+///////////////////////////////////////////////////////////////////////////////
+
+fn schema_lookup(path: &str) -> Option<&'static ConfigValueType> {
+    let mut schema = ROOT_KEYS;
+    let mut kind: Option<&'static ConfigValueType> = None;
+
+    for part in path.split('.') {
+        if let Some(ConfigValueType::Table(sub)) = kind {
+            schema = sub;
+        } else if kind.is_some() {
+            return None;
+        }
+
+        kind = Some(&schema.iter().find(|k| k.name == part)?.kind);
+    }
+
+    kind
+}
+
+/// Map the remainder of an `ADO_*` variable (`LLM_CLAUDE_MAX_TOKENS`) to its
+/// dotted key (`llm.claude.max_tokens`). Key names contain underscores, so the
+/// schema decides where the separators are.
+pub(crate) fn env_key_path(name: &str) -> Option<String> {
+    fn walk(schema: &'static [ConfigKey], rest: &str) -> Option<String> {
+        for k in schema {
+            let upper = k.name.to_uppercase();
+
+            match &k.kind {
+                ConfigValueType::Table(sub) => {
+                    if let Some(tail) = rest.strip_prefix(&upper).and_then(|r| r.strip_prefix('_'))
+                        && let Some(path) = walk(sub, tail)
+                    {
+                        return Some(format!("{}.{path}", k.name));
+                    }
+                }
+                // selected with --profile, not overridable key by key
+                ConfigValueType::Profiles(_) => {}
+                _ if rest == upper => return Some(k.name.to_string()),
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    walk(ROOT_KEYS, name)
+}
+
+/// Convert the raw string of an environment or `--set` override to the type
+/// the schema expects for `path`.
+pub(crate) fn parse_override(path: &str, raw: &str) -> Result<toml::Value, String> {
+    let kind = schema_lookup(path).ok_or_else(|| format!("unknown key `{path}`"))?;
+
+    let invalid = || format!("`{path}` must be {}, got `{raw}`", type_name(kind));
+
+    match kind {
+        ConfigValueType::String => Ok(toml::Value::String(raw.to_string())),
+        ConfigValueType::Integer => {
+            raw.parse::<i64>().map(toml::Value::Integer).map_err(|_| invalid())
+        }
+        ConfigValueType::Bool => {
+            raw.parse::<bool>().map(toml::Value::Boolean).map_err(|_| invalid())
+        }
+        ConfigValueType::StringArray => {
+            // either a TOML array or a comma separated list
+            if let Ok(mut t) = toml::from_str::<toml::Table>(&format!("v = {raw}"))
+                && let Some(v @ toml::Value::Array(_)) = t.remove("v")
+            {
+                return Ok(v);
+            }
+
+            let items = raw
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| toml::Value::String(s.to_string()))
+                .collect();
+
+            Ok(toml::Value::Array(items))
+        }
+        ConfigValueType::Table(_) | ConfigValueType::Profiles(_) => {
+            Err(format!("`{path}` is a table, set one of its keys"))
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL_CONFIG: &str = r#"
+[llm]
+provider = "claude"
+
+[llm.message_cache]
+ttl = 60
+
+[llm.ollama]
+endpoint = "http://localhost:11434"
+model = "llama3"
+thinking = false
+keep_alive = 30
+
+[llm.claude]
+model = "claude-sonnet-4-6"
+url = "https://api.anthropic.com"
+anthropic_version = "2023-06-01"
+key_env = "ANTHROPIC_API_KEY"
+max_tokens = 1024
+instructions = ["be brief"]
+logs = "/tmp/logs"
+
+[search.google]
+cx = "cx"
+geo = "ca"
+key = "key"
+url = "https://example.com"
+cache_size = 0
+cache_ttl = 0
+
+[command.reddit]
+model = "test"
+
+[command.git]
+model = "test"
+
+[agent]
+shell = "ask"
+max_iterations = 10
+
+[profile.local.llm]
+provider = "ollama"
+
+[profile.work.llm.claude]
+key_env = "WORK_ANTHROPIC_API_KEY"
+"#;
+
+    #[test]
+    fn test_schema_matches_structs() {
+        let issues = check_config(FULL_CONFIG);
+        assert!(issues.is_empty(), "{issues:?}");
+
+        toml::from_str::<ConfigFile>(FULL_CONFIG).unwrap();
+    }
+
+    #[test]
+    fn test_optional_sections() {
+        let config =
+            "[llm]\nprovider = \"ollama\"\n\n[llm.ollama]\nendpoint = \"e\"\nmodel = \"m\"\n";
+
+        assert!(check_config(config).is_empty());
+    }
+
+    #[test]
+    fn test_profiles() {
+        let config = r#"[llm]
+provider = "ollama"
+
+[llm.ollama]
+endpoint = "e"
+model = "m"
+
+[profile.work.llm.claude]
+model = "claude-sonnet-4-6"
+colour = "blue"
+
+[profile.home]
+llm = 5
+"#;
+
+        let issues = check_config(config);
+        assert_eq!(issues.len(), 2, "{issues:?}");
+
+        let unknown = issues.iter().find(|i| i.message.contains("colour")).unwrap();
+        assert_eq!(unknown.level, ConfigIssueLevel::Warning);
+        assert_eq!(unknown.line, Some(10));
+
+        let bad_type = issues.iter().find(|i| i.message.contains("profile.home.llm")).unwrap();
+        assert!(bad_type.is_error());
+    }
+
+    #[test]
+    fn test_every_problem_reported() {
+        let config = r#"[llm]
+provider = "claude"
+colour = "blue"
+
+[llm.claude]
+model = 5
+url = "u"
+anthropic_version = "v"
+key = "k"
+"#;
+
+        let issues = check_config(config);
+
+        let unknown = issues.iter().find(|i| i.message.contains("colour")).unwrap();
+        assert_eq!(unknown.level, ConfigIssueLevel::Warning);
+        assert_eq!(unknown.line, Some(3));
+
+        let bad_type = issues.iter().find(|i| i.message.contains("llm.claude.model")).unwrap();
+        assert!(bad_type.is_error());
+        assert_eq!(bad_type.line, Some(6));
+
+        let missing = issues.iter().find(|i| i.message.contains("max_tokens")).unwrap();
+        assert!(missing.is_error());
+    }
+
+    #[test]
+    fn test_claude_key() {
+        let base = "[llm]\nprovider = \"claude\"\n\n[llm.claude]\nmodel = \"m\"\nurl = \"u\"\nanthropic_version = \"v\"\nmax_tokens = 1\n";
+
+        let issues = check_config(base);
+        assert!(issues.iter().any(|i| i.is_error() && i.message.contains("key_cmd")));
+
+        let issues = check_config(format!("{base}key_cmd = \"pass show anthropic\"\n"));
+        assert!(issues.is_empty(), "{issues:?}");
+
+        let issues = check_config(format!("{base}key = \"plaintext\"\n"));
+        assert!(issues.iter().any(|i| !i.is_error() && i.message.contains("plaintext")));
+    }
+
+    #[test]
+    fn test_provider() {
+        let issues = check_config("[llm]\nprovider = \"claude\"\n");
+        assert!(issues.iter().any(|i| i.message.contains("[llm.claude] is missing")));
+
+        let issues = check_config("[llm]\nprovider = \"openai\"\n");
+        assert!(issues.iter().any(|i| i.message.contains("unknown provider")));
+    }
+
+    #[test]
+    fn test_layer() {
+        let issues =
+            check_layer_config("[llm]\nprovider = \"claude\"\n[llm.claude]\nmodel = \"m\"\n");
+        assert!(issues.is_empty(), "{issues:?}");
+
+        let issues = check_layer_config("[llm.claude]\nmodel = 5\ncolour = \"blue\"\n");
+        assert_eq!(issues.len(), 2, "{issues:?}");
+        assert!(issues.iter().any(|i| i.is_error() && i.line == Some(2)));
+
+        let issues = check_layer_config("[llm]\nprovider = \"openai\"\n");
+        assert!(issues.iter().any(|i| i.message.contains("unknown provider")));
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let issues = check_config("[llm]\nprovider = \"claude\n");
+
+        assert!(issues.first().is_some_and(|i| i.is_error() && i.line == Some(2)));
+    }
+
+    #[test]
+    fn test_env_key_path() {
+        assert_eq!(
+            env_key_path("LLM_CLAUDE_MODEL").as_deref(),
+            Some("llm.claude.model")
+        );
+        assert_eq!(
+            env_key_path("LLM_CLAUDE_MAX_TOKENS").as_deref(),
+            Some("llm.claude.max_tokens")
+        );
+        assert_eq!(
+            env_key_path("LLM_CLAUDE_KEY_ENV").as_deref(),
+            Some("llm.claude.key_env")
+        );
+        assert_eq!(
+            env_key_path("LLM_MESSAGE_CACHE_TTL").as_deref(),
+            Some("llm.message_cache.ttl")
+        );
+        assert_eq!(env_key_path("CACHE_DIRECTORY"), None);
+        assert_eq!(env_key_path("LLM_CLAUDE"), None);
+    }
+
+    #[test]
+    fn test_parse_override() {
+        assert_eq!(
+            parse_override("llm.claude.max_tokens", "512"),
+            Ok(toml::Value::Integer(512))
+        );
+        assert!(parse_override("llm.claude.max_tokens", "many").is_err());
+        assert_eq!(
+            parse_override("llm.ollama.thinking", "true"),
+            Ok(toml::Value::Boolean(true))
+        );
+        assert_eq!(
+            parse_override("llm.claude.model", "123"),
+            Ok(toml::Value::String("123".into()))
+        );
+
+        let expected = toml::Value::Array(vec!["a".into(), "b c".into()]);
+        assert_eq!(
+            parse_override("llm.claude.instructions", "a, b c"),
+            Ok(expected.clone())
+        );
+        assert_eq!(
+            parse_override("llm.claude.instructions", r#"["a", "b c"]"#),
+            Ok(expected)
+        );
+
+        assert!(parse_override("llm.claude", "x").is_err());
+        assert!(parse_override("llm.colour", "x").is_err());
+    }
+}
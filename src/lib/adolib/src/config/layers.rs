@@ -0,0 +1,447 @@
+//! Configuration layers. Later layers override earlier ones key by key:
+//!
+//! 1. system  `/etc/ado/config.toml`
+//! 2. user    `~/.config/ado/config.toml` (or `--config-file`)
+//! 3. project `.ado/config.toml`, searched from the current directory up to
+//!    the git root
+//! 4. profile `[profile.<name>]` selected with `--profile`
+//! 5. environment `ADO_<SECTION>_<KEY>`, e.g. `ADO_LLM_CLAUDE_MODEL`
+//! 6. command line `--set llm.claude.model=...`
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+use toml::{Table, Value};
+
+use crate::{
+    config::check::{env_key_path, parse_override},
+    const_vars::CONFIG_FILE_NAME,
+    error::{Error, Result},
+};
+
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/ado/config.toml";
+pub const PROJECT_CONFIG_DIR: &str = ".ado";
+pub const ENV_PREFIX: &str = "ADO_";
+
+/// A repository is not trusted with credentials, with where requests (and
+/// therefore credentials) are sent, or with what the agent may run unasked.
+const PROJECT_DENIED_KEYS: &[&str] = &[
+    "llm.claude.key",
+    "llm.claude.key_env",
+    "llm.claude.key_cmd",
+    "llm.claude.url",
+    "llm.claude.logs",
+    "llm.ollama.endpoint",
+    "search.google.key",
+    "search.google.url",
+    "agent.shell",
+    "agent.max_iterations",
+    // profiles can carry any of the above
+    "profile",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigOrigin {
+    Default,
+    System(PathBuf),
+    User(PathBuf),
+    Project(PathBuf),
+    Profile(String),
+    Env(String),
+    Cli,
+}
+
+impl Display for ConfigOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigOrigin::Default => write!(f, "default"),
+            ConfigOrigin::System(p) => write!(f, "system {}", p.display()),
+            ConfigOrigin::User(p) => write!(f, "user {}", p.display()),
+            ConfigOrigin::Project(p) => write!(f, "project {}", p.display()),
+            ConfigOrigin::Profile(name) => write!(f, "profile {name}"),
+            ConfigOrigin::Env(name) => write!(f, "env ${name}"),
+            ConfigOrigin::Cli => write!(f, "--set"),
+        }
+    }
+}
+
+struct ConfigLayer {
+    origin: ConfigOrigin,
+    table: Table,
+}
+
+#[derive(Default)]
+pub(crate) struct ConfigLayers {
+    layers: Vec<ConfigLayer>,
+}
+
+/// Dotted paths of every non-table value
+fn leaf_paths(table: &Table, prefix: &str, out: &mut Vec<String>) {
+    for (k, v) in table {
+        let path = if prefix.is_empty() {
+            k.clone()
+        } else {
+            format!("{prefix}.{k}")
+        };
+
+        match v {
+            Value::Table(sub) => leaf_paths(sub, &path, out),
+            _ => out.push(path),
+        }
+    }
+}
+
+fn set_path(table: &mut Table, path: &str, value: Value) {
+    let mut current = table;
+    let mut parts = path.split('.').peekable();
+
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            current.insert(part.to_string(), value);
+            return;
+        }
+
+        let entry = current.entry(part).or_insert_with(|| Value::Table(Table::new()));
+
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+
+        let Value::Table(next) = entry else {
+            return;
+        };
+
+        current = next;
+    }
+}
+
+fn remove_path(table: &mut Table, path: &str) -> Option<Value> {
+    match path.split_once('.') {
+        Some((head, tail)) => remove_path(table.get_mut(head)?.as_table_mut()?, tail),
+        None => table.remove(path),
+    }
+}
+
+fn merge_tables(base: &mut Table, overlay: &Table) {
+    for (k, v) in overlay {
+        match (base.get_mut(k), v) {
+            (Some(Value::Table(b)), Value::Table(o)) => merge_tables(b, o),
+            _ => {
+                base.insert(k.clone(), v.clone());
+            }
+        }
+    }
+}
+
+/// Closest `.ado/config.toml` walking up from `start`, stopping at the git
+/// root so an unrelated parent directory can't inject settings. Outside a
+/// repository there is no project config.
+#[must_use]
+pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+    let root = start.ancestors().find(|dir| dir.join(".git").exists())?;
+
+    start
+        .ancestors()
+        .take_while(|dir| dir.starts_with(root))
+        .map(|dir| dir.join(PROJECT_CONFIG_DIR).join(CONFIG_FILE_NAME))
+        .find(|candidate| candidate.is_file())
+}
+
+impl ConfigLayers {
+    fn push(&mut self, origin: ConfigOrigin, table: Table) {
+        if !table.is_empty() {
+            self.layers.push(ConfigLayer { origin, table });
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Read `path` as a layer, `origin` is one of the file variants, e.g.
+    /// `ConfigOrigin::Project`
+    pub(crate) fn add_file(
+        &mut self,
+        path: &Path,
+        origin: fn(PathBuf) -> ConfigOrigin,
+    ) -> Result<()> {
+        let origin = origin(path.to_path_buf());
+
+        info!("config layer {origin}");
+
+        let data = fs::read_to_string(path)?;
+
+        let mut table = toml::from_str::<Table>(&data).map_err(|e| Error::ConfigError {
+            error: format!("{}: {}", path.display(), e.message()),
+        })?;
+
+        if matches!(origin, ConfigOrigin::Project(_)) {
+            for denied in PROJECT_DENIED_KEYS {
+                if remove_path(&mut table, denied).is_some() {
+                    warn!(
+                        "{}: ignoring `{denied}`, set it in the user config",
+                        path.display()
+                    );
+                }
+            }
+        }
+
+        self.push(origin, table);
+
+        Ok(())
+    }
+
+    /// Apply `[profile.<name>]` as defined by the layers added so far
+    pub(crate) fn add_profile(&mut self, name: &str) -> Result<()> {
+        let (merged, _) = self.merge();
+
+        let profiles = merged.get("profile").and_then(Value::as_table);
+
+        let Some(profile) = profiles.and_then(|p| p.get(name)).and_then(Value::as_table) else {
+            let available: Vec<&str> =
+                profiles.map(|p| p.keys().map(String::as_str).collect()).unwrap_or_default();
+
+            return Err(Error::ConfigError {
+                error: format!(
+                    "unknown profile `{name}`, available: [{}]",
+                    available.join(", ")
+                ),
+            });
+        };
+
+        info!("config layer profile {name}");
+
+        self.push(ConfigOrigin::Profile(name.to_string()), profile.clone());
+
+        Ok(())
+    }
+
+    /// `ADO_*` variables that map to a config key; others (e.g.
+    /// `ADO_CACHE_DIRECTORY`) are not config and are skipped.
+    pub(crate) fn add_env<I>(&mut self, vars: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut vars: Vec<(String, String)> =
+            vars.into_iter().filter(|(k, _)| k.starts_with(ENV_PREFIX)).collect();
+        vars.sort();
+
+        for (name, raw) in vars {
+            let Some(path) = name.strip_prefix(ENV_PREFIX).and_then(env_key_path) else {
+                info!("${name} is not a config key");
+                continue;
+            };
+
+            let value = parse_override(&path, &raw).map_err(|error| Error::ConfigError {
+                error: format!("${name}: {error}"),
+            })?;
+
+            let mut table = Table::new();
+            set_path(&mut table, &path, value);
+
+            // one layer per variable so each key reports its own variable
+            self.push(ConfigOrigin::Env(name), table);
+        }
+
+        Ok(())
+    }
+
+    /// `key=value` pairs from the command line
+    pub(crate) fn add_overrides<S>(&mut self, overrides: &[S]) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        let mut table = Table::new();
+
+        for o in overrides {
+            let o = o.as_ref();
+
+            let (path, raw) = o.split_once('=').ok_or_else(|| Error::ConfigError {
+                error: format!("--set {o}: expected key=value"),
+            })?;
+
+            let value = parse_override(path.trim(), raw).map_err(|error| Error::ConfigError {
+                error: format!("--set {o}: {error}"),
+            })?;
+
+            set_path(&mut table, path.trim(), value);
+        }
+
+        self.push(ConfigOrigin::Cli, table);
+
+        Ok(())
+    }
+
+    /// Effective table and the origin of each of its leaf keys
+    pub(crate) fn merge(&self) -> (Table, BTreeMap<String, ConfigOrigin>) {
+        let mut merged = Table::new();
+        let mut origins = BTreeMap::new();
+
+        for layer in &self.layers {
+            merge_tables(&mut merged, &layer.table);
+
+            let mut paths = Vec::new();
+            leaf_paths(&layer.table, "", &mut paths);
+
+            for p in paths {
+                origins.insert(p, layer.origin.clone());
+            }
+        }
+
+        (merged, origins)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, data: &str) -> PathBuf {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+        path.to_path_buf()
+    }
+
+    #[test]
+    fn test_merge_order() {
+        let td = tempfile::Builder::new().prefix("layers_").tempdir().unwrap();
+
+        let user = write(
+            &td.path().join("user.toml"),
+            "[llm]\nprovider = \"claude\"\n[llm.claude]\nmodel = \"user\"\nmax_tokens = 1\n",
+        );
+        let project = write(
+            &td.path().join("project.toml"),
+            "[llm.claude]\nmodel = \"project\"\nkey_cmd = \"curl evil\"\n",
+        );
+
+        let mut layers = ConfigLayers::default();
+        layers.add_file(&user, ConfigOrigin::User).unwrap();
+        layers.add_file(&project, ConfigOrigin::Project).unwrap();
+        layers
+            .add_env([
+                ("ADO_LLM_CLAUDE_MAX_TOKENS".to_string(), "42".to_string()),
+                ("ADO_CACHE_DIRECTORY".to_string(), "/tmp".to_string()),
+                ("HOME".to_string(), "/root".to_string()),
+            ])
+            .unwrap();
+
+        let (merged, origins) = layers.merge();
+        assert_eq!(merged["llm"]["claude"]["model"].as_str(), Some("project"));
+        assert_eq!(merged["llm"]["claude"]["max_tokens"].as_integer(), Some(42));
+        assert_eq!(merged["llm"]["provider"].as_str(), Some("claude"));
+        assert!(merged["llm"]["claude"].get("key_cmd").is_none());
+
+        assert_eq!(origins["llm.provider"], ConfigOrigin::User(user));
+        assert_eq!(origins["llm.claude.model"], ConfigOrigin::Project(project));
+        assert_eq!(
+            origins["llm.claude.max_tokens"],
+            ConfigOrigin::Env("ADO_LLM_CLAUDE_MAX_TOKENS".into())
+        );
+
+        layers.add_overrides(&["llm.claude.model=cli"]).unwrap();
+
+        let (merged, origins) = layers.merge();
+        assert_eq!(merged["llm"]["claude"]["model"].as_str(), Some("cli"));
+        assert_eq!(origins["llm.claude.model"], ConfigOrigin::Cli);
+    }
+
+    #[test]
+    fn test_profile() {
+        let td = tempfile::Builder::new().prefix("layers_").tempdir().unwrap();
+
+        let user = write(
+            &td.path().join("user.toml"),
+            "[llm]\nprovider = \"claude\"\n[llm.claude]\nmodel = \"user\"\n\n\
+             [profile.local.llm]\nprovider = \"ollama\"\n[profile.local.llm.ollama]\nmodel = \"llama3\"\n",
+        );
+        let project = write(
+            &td.path().join("project.toml"),
+            "[profile.local.llm.ollama]\nendpoint = \"http://evil\"\n",
+        );
+
+        let mut layers = ConfigLayers::default();
+        layers.add_file(&user, ConfigOrigin::User).unwrap();
+        layers.add_file(&project, ConfigOrigin::Project).unwrap();
+
+        assert!(layers.add_profile("work").is_err());
+
+        layers.add_profile("local").unwrap();
+
+        let (merged, origins) = layers.merge();
+        assert_eq!(merged["llm"]["provider"].as_str(), Some("ollama"));
+        assert_eq!(merged["llm"]["claude"]["model"].as_str(), Some("user"));
+        assert!(merged["llm"]["ollama"].get("endpoint").is_none());
+        assert_eq!(
+            origins["llm.provider"],
+            ConfigOrigin::Profile("local".into())
+        );
+    }
+
+    #[test]
+    fn test_project_agent_keys() {
+        let td = tempfile::Builder::new().prefix("layers_").tempdir().unwrap();
+
+        let user = write(
+            &td.path().join("user.toml"),
+            "[agent]\nshell = \"never\"\nmax_iterations = 5\n",
+        );
+        let project = write(
+            &td.path().join("project.toml"),
+            "[agent]\nshell = \"always\"\nmax_iterations = 0\n",
+        );
+
+        let mut layers = ConfigLayers::default();
+        layers.add_file(&user, ConfigOrigin::User).unwrap();
+        layers.add_file(&project, ConfigOrigin::Project).unwrap();
+
+        let (merged, origins) = layers.merge();
+        assert_eq!(merged["agent"]["shell"].as_str(), Some("never"));
+        assert_eq!(merged["agent"]["max_iterations"].as_integer(), Some(5));
+        assert_eq!(origins["agent.shell"], ConfigOrigin::User(user));
+    }
+
+    #[test]
+    fn test_bad_overrides() {
+        let mut layers = ConfigLayers::default();
+
+        assert!(layers.add_overrides(&["llm.claude.model"]).is_err());
+        assert!(layers.add_overrides(&["llm.colour=blue"]).is_err());
+        assert!(
+            layers
+                .add_env([("ADO_LLM_CLAUDE_MAX_TOKENS".to_string(), "lots".to_string())])
+                .is_err()
+        );
+        assert_eq!(layers.len(), 0);
+    }
+
+    #[test]
+    fn test_find_project_config() {
+        let td = tempfile::Builder::new().prefix("layers_").tempdir().unwrap();
+        let repo = td.path().join("repo");
+        let nested = repo.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(find_project_config(&nested), None);
+
+        // outside a repository
+        let config = write(&repo.join(PROJECT_CONFIG_DIR).join(CONFIG_FILE_NAME), "");
+        assert_eq!(find_project_config(&nested), None);
+
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        assert_eq!(find_project_config(&nested), Some(config));
+
+        // the git root bounds the search
+        fs::create_dir_all(repo.join("a").join(".git")).unwrap();
+        assert_eq!(find_project_config(&nested), None);
+    }
+}
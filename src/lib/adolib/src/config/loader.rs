@@ -1,13 +1,20 @@
 use std::{
-    fs,
+    collections::BTreeMap,
+    env, fs,
     io::Write,
     path::{Path, PathBuf},
 };
 
-use log::{error, info};
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    config::{
+        check::{ConfigIssue, check_config},
+        layers::{
+            ConfigLayers, ConfigOrigin, PROJECT_CONFIG_DIR, SYSTEM_CONFIG_PATH, find_project_config,
+        },
+    },
     const_vars::CONFIG_FILE_NAME,
     error::{Error, Result},
     llm::config::{ClaudeConfig, ConfigOllama},
@@ -19,28 +26,102 @@ pub struct ConfigLlm {
     ollama: Option<ConfigOllama>,
     claude: Option<ClaudeConfig>,
     provider: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message_cache: Option<ConfigMessageCache>,
 }
 
+/// Opt-in caching of one-shot `LLMChain::message` responses
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfigMessageCache {
+    /// seconds, 0 keeps responses forever
+    #[serde(default = "default_message_cache_ttl")]
+    pub ttl: u64,
+}
+
+fn default_message_cache_ttl() -> u64 {
+    // 24h
+    86400
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct ConfigSearch {
     google: Option<GoogleConfig>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct ConfigCommandReddit {
-    pub model: String,
+    /// defaults to the chain's model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// `/diff`, `/commit` and `/blame`
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct ConfigCommandGit {
+    /// model writing commit messages, defaults to the chain's model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct ConfigCommand {
+    #[serde(default)]
     pub reddit: ConfigCommandReddit,
+    #[serde(default)]
+    pub git: ConfigCommandGit,
+}
+
+/// How `command` artifacts needing a shell, e.g. with pipes, redirects,
+/// `&&` or globs, are run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellPolicy {
+    /// refused, the agent is asked to run one program at a time
+    Never,
+    /// through `/bin/sh -c` once the user agrees. Headless mode has no one
+    /// to ask and agrees to everything, making it `Always`: set `Never` to
+    /// keep the shell out of a headless run.
+    #[default]
+    Ask,
+    /// through `/bin/sh -c`
+    Always,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfigAgent {
+    #[serde(default)]
+    pub shell: ShellPolicy,
+    /// model calls answering one prompt, 0 for no limit
+    #[serde(default = "default_max_iterations")]
+    pub max_iterations: u32,
+}
+
+fn default_max_iterations() -> u32 {
+    25
+}
+
+impl Default for ConfigAgent {
+    fn default() -> Self {
+        Self {
+            shell: ShellPolicy::default(),
+            max_iterations: default_max_iterations(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-struct ConfigFile {
+pub(crate) struct ConfigFile {
     llm: ConfigLlm,
+    #[serde(default)]
     search: ConfigSearch,
+    #[serde(default)]
     command: ConfigCommand,
+    #[serde(default)]
+    agent: ConfigAgent,
+    /// `[profile.<name>]` partial overrides of `llm` and `search`, applied by
+    /// the layer loader
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    profile: BTreeMap<String, toml::Table>,
 }
 
 #[derive(Clone)]
@@ -53,19 +134,210 @@ pub enum AdoConfigSource {
 pub struct AdoConfig {
     source: AdoConfigSource,
     config_file: ConfigFile,
+    /// where each overridden key came from, keys not listed come from `source`
+    origins: BTreeMap<String, ConfigOrigin>,
+    /// more than one layer contributed, `source` alone no longer describes it
+    merged: bool,
+    /// active `--profile`
+    profile: Option<String>,
+    /// `--set` overrides, kept to load the config again
+    overrides: Vec<String>,
+    /// config files read or looked for, see [`AdoConfig::files`]
+    files: Vec<PathBuf>,
+}
+
+/// One effective value as shown by `/config`
+#[derive(Debug, Clone)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: String,
+    pub origin: ConfigOrigin,
+}
+
+/// Keys never displayed in clear
+const SECRET_KEYS: &[&str] = &["llm.claude.key", "search.google.key"];
+
+/// Deserialize `data`, logging unknown keys. Errors carry the line number of
+/// the first problem.
+fn parse_config(data: &str) -> core::result::Result<ConfigFile, String> {
+    match toml::from_str::<ConfigFile>(data) {
+        Ok(v) => {
+            for issue in check_config(data) {
+                warn!("config: {issue}");
+            }
+            Ok(v)
+        }
+        Err(e) => {
+            // the checker knows where things went wrong, serde only has a span
+            let first_error = check_config(data).into_iter().find(ConfigIssue::is_error);
+
+            match first_error {
+                Some(issue) => Err(issue.to_string()),
+                None => Err(e.message().to_string()),
+            }
+        }
+    }
+}
+
+fn flatten_entries(table: &toml::Table, prefix: &str, out: &mut Vec<(String, toml::Value)>) {
+    for (k, v) in table {
+        let key = if prefix.is_empty() {
+            k.clone()
+        } else {
+            format!("{prefix}.{k}")
+        };
+
+        match v {
+            toml::Value::Table(sub) => flatten_entries(sub, &key, out),
+            _ => out.push((key, v.clone())),
+        }
+    }
+}
+
+impl ConfigFile {
+    /// Resolve secrets of the active provider so inactive ones never run
+    /// `key_cmd` for nothing.
+    fn resolve_secrets(&mut self) -> Result<()> {
+        if self.llm.provider == "claude"
+            && let Some(claude) = &mut self.llm.claude
+        {
+            claude.resolve_key()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ConfigLlm {
+    fn with_provider<S>(provider: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            ollama: None,
+            claude: None,
+            provider: provider.into(),
+            message_cache: None,
+        }
+    }
+}
+
+/// Read and parse the config file at `path`, secrets are left unresolved
+fn read_config_file(path: &Path) -> Result<ConfigFile> {
+    info!("Using config file {}", path.display());
+
+    if !path.exists() {
+        error!("{} does not exist", path.display());
+        return Err(Error::FileNotFoundError {
+            file_path: path.into(),
+        });
+    }
+
+    let file_data = match fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Unable to read config file @ {}", path.display());
+            return Err(e.into());
+        }
+    };
+
+    parse_config(&file_data).map_err(|error| {
+        let error = format!("{}: {error}. See `ado config check`", path.display());
+        error!("{error}");
+        Error::ConfigError { error }
+    })
+}
+
+impl ConfigFile {
+    fn with_llm(llm: ConfigLlm) -> Self {
+        Self {
+            llm,
+            search: ConfigSearch::default(),
+            command: ConfigCommand::default(),
+            agent: ConfigAgent::default(),
+            profile: BTreeMap::new(),
+        }
+    }
 }
 
-impl ConfigFile {}
+/// Origin of the keys `path` sets, empty when it can't be read, e.g. before
+/// it is first written
+fn user_origins(path: &Path) -> BTreeMap<String, ConfigOrigin> {
+    let mut layers = ConfigLayers::default();
+
+    match layers.add_file(path, ConfigOrigin::User) {
+        Ok(()) => layers.merge().1,
+        Err(_) => BTreeMap::new(),
+    }
+}
 
 impl AdoConfig {
     fn new(source: AdoConfigSource, config_file: ConfigFile) -> Self {
+        let (files, origins) = match &source {
+            AdoConfigSource::File { path } => (vec![path.clone()], user_origins(path)),
+            AdoConfigSource::String => (Vec::new(), BTreeMap::new()),
+        };
+
         Self {
+            files,
             source,
             config_file,
+            origins,
+            merged: false,
+            profile: None,
+            overrides: Vec::new(),
         }
     }
 
+    /// Minimal ollama config that will be written to `path` by
+    /// [`AdoConfig::sync`]
+    pub fn with_ollama<P>(path: P, ollama: ConfigOllama) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        let mut llm = ConfigLlm::with_provider("ollama");
+        llm.ollama = Some(ollama);
+
+        let source = AdoConfigSource::File {
+            path: path.as_ref().into(),
+        };
+
+        AdoConfig::new(source, ConfigFile::with_llm(llm))
+    }
+
+    /// Minimal claude config that will be written to `path` by
+    /// [`AdoConfig::sync`]
+    pub fn with_claude<P>(path: P, claude: ClaudeConfig) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        let mut llm = ConfigLlm::with_provider("claude");
+        llm.claude = Some(claude);
+
+        let source = AdoConfigSource::File {
+            path: path.as_ref().into(),
+        };
+
+        AdoConfig::new(source, ConfigFile::with_llm(llm))
+    }
+
+    pub fn ollama_mut(&mut self) -> Result<&mut ConfigOllama> {
+        self.config_file.llm.ollama.as_mut().ok_or(Error::ConfigNotFound)
+    }
+
+    pub fn claude_mut(&mut self) -> Result<&mut ClaudeConfig> {
+        self.config_file.llm.claude.as_mut().ok_or(Error::ConfigNotFound)
+    }
+
     pub fn sync(&self) -> Result<()> {
+        if self.merged {
+            // writing would bake project / env / --set overrides into the file
+            return Err(Error::ConfigError {
+                error: "configuration is merged from several layers, edit the files instead"
+                    .to_string(),
+            });
+        }
+
         let toml_file = toml::to_string(&self.config_file)?;
 
         //
@@ -95,28 +367,153 @@ impl AdoConfig {
             path: path.as_ref().into(),
         };
 
-        info!("Using config file {}", path.as_ref().display());
+        let mut config_file = read_config_file(path.as_ref())?;
 
-        let file_data = match fs::read_to_string(&path) {
-            Ok(v) => v,
-            Err(e) => {
-                error!("Unable to read config file @ {}", path.as_ref().display());
-                return Err(e.into());
-            }
-        };
+        config_file.resolve_secrets()?;
 
-        let config_file: ConfigFile = match toml::from_str(&file_data) {
-            Ok(v) => v,
-            Err(e) => {
-                error!("Unable to deserialize {e}");
-                return Err(e.into());
+        Ok(AdoConfig::new(source, config_file))
+    }
+
+    /// `path` (the user config) layered with the system config, the project's
+    /// `.ado/config.toml`, `ADO_*` environment variables and `overrides`
+    /// (`key=value`, from `--set`). `profile` selects a `[profile.<name>]`.
+    /// See [`crate::config::layers`].
+    pub fn load<P, S>(path: P, profile: Option<&str>, overrides: &[S]) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        S: AsRef<str>,
+    {
+        let path = path.as_ref();
+
+        // the user config must stand on its own, this also keeps its
+        // line-numbered errors
+        let user_file = read_config_file(path)?;
+        let source = AdoConfigSource::File { path: path.into() };
+        let overrides: Vec<String> = overrides.iter().map(|o| o.as_ref().to_string()).collect();
+
+        let mut layers = ConfigLayers::default();
+
+        let system = Path::new(SYSTEM_CONFIG_PATH);
+        if system.is_file() {
+            layers.add_file(system, ConfigOrigin::System)?;
+        }
+
+        layers.add_file(path, ConfigOrigin::User)?;
+
+        let cwd = env::current_dir()?;
+
+        let project = match find_project_config(&cwd) {
+            Some(project) => {
+                layers.add_file(&project, ConfigOrigin::Project)?;
+                project
             }
+            // where one would be created
+            None => cwd.join(PROJECT_CONFIG_DIR).join(CONFIG_FILE_NAME),
         };
 
-        Ok(AdoConfig::new(source, config_file))
+        let files = vec![system.to_path_buf(), path.to_path_buf(), project];
+
+        if let Some(name) = profile {
+            layers.add_profile(name)?;
+        }
+
+        layers.add_env(env::vars())?;
+        layers.add_overrides(&overrides)?;
+
+        if layers.len() <= 1 {
+            let mut config_file = user_file;
+            config_file.resolve_secrets()?;
+            return Ok(AdoConfig {
+                // an empty `[profile.<name>]` is still the active one
+                profile: profile.map(ToString::to_string),
+                overrides,
+                files,
+                ..AdoConfig::new(source, config_file)
+            });
+        }
+
+        let (table, origins) = layers.merge();
+
+        let mut config_file: ConfigFile = table.try_into().map_err(|e: toml::de::Error| {
+            let error = format!("merged configuration: {}", e.message());
+            error!("{error}");
+            Error::ConfigError { error }
+        })?;
+
+        config_file.resolve_secrets()?;
+
+        Ok(Self {
+            source,
+            config_file,
+            origins,
+            merged: true,
+            profile: profile.map(ToString::to_string),
+            overrides,
+            files,
+        })
     }
 
-    pub fn from_default() -> Result<Self> {
+    /// Load the same layers again, picking up changes made to the files
+    pub fn reload(&self) -> Result<Self> {
+        self.with_profile(self.profile())
+    }
+
+    /// Config files this configuration depends on, including the ones that
+    /// didn't exist when it was loaded
+    #[must_use]
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Load the same layers again with `profile` active instead
+    pub fn with_profile(&self, profile: Option<&str>) -> Result<Self> {
+        match &self.source {
+            AdoConfigSource::File { path } => AdoConfig::load(path, profile, &self.overrides),
+            AdoConfigSource::String => Err(Error::NotImplemented),
+        }
+    }
+
+    #[must_use]
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Names of the `[profile.<name>]` sections
+    #[must_use]
+    pub fn profiles(&self) -> Vec<&str> {
+        self.config_file.profile.keys().map(String::as_str).collect()
+    }
+
+    /// Every effective value with the layer it came from, secrets masked
+    pub fn entries(&self) -> Result<Vec<ConfigEntry>> {
+        let table = toml::Table::try_from(&self.config_file)?;
+
+        let mut entries = Vec::new();
+        flatten_entries(&table, "", &mut entries);
+
+        // profile definitions aren't effective values, the active one is
+        // already merged in
+        entries.retain(|(key, _)| !key.starts_with("profile."));
+
+        Ok(entries
+            .into_iter()
+            .map(|(key, value)| {
+                // no layer sets it
+                let origin = self.origins.get(&key).cloned().unwrap_or(ConfigOrigin::Default);
+
+                let value = if SECRET_KEYS.contains(&key.as_str()) {
+                    "\"********\"".to_string()
+                } else {
+                    value.to_string()
+                };
+
+                ConfigEntry { key, value, origin }
+            })
+            .collect())
+    }
+
+    /// `~/.config/ado/config.toml`, creating the directory if needed
+    pub fn default_path() -> Result<PathBuf> {
         let config_dir = dirs::config_dir().ok_or(Error::ConfigNotFound)?;
 
         let ado_config_dir = config_dir.join("ado");
@@ -125,9 +522,11 @@ impl AdoConfig {
             fs::create_dir_all(&ado_config_dir)?;
         }
 
-        let config_file = ado_config_dir.join(CONFIG_FILE_NAME);
+        Ok(ado_config_dir.join(CONFIG_FILE_NAME))
+    }
 
-        AdoConfig::from_path(config_file)
+    pub fn from_default() -> Result<Self> {
+        AdoConfig::from_path(AdoConfig::default_path()?)
     }
 
     // mainly only used in testing
@@ -135,7 +534,10 @@ impl AdoConfig {
     where
         S: AsRef<str>,
     {
-        let config_file: ConfigFile = toml::from_str(value.as_ref())?;
+        let mut config_file =
+            parse_config(value.as_ref()).map_err(|error| Error::ConfigError { error })?;
+
+        config_file.resolve_secrets()?;
 
         Ok(AdoConfig::new(AdoConfigSource::String, config_file))
     }
@@ -166,6 +568,11 @@ impl AdoConfig {
         }
     }
 
+    #[must_use]
+    pub fn message_cache(&self) -> Option<&ConfigMessageCache> {
+        self.config_file.llm.message_cache.as_ref()
+    }
+
     pub fn search_google(&self) -> Result<&GoogleConfig> {
         if let Some(g) = &self.config_file.search.google {
             return Ok(g);
@@ -178,4 +585,140 @@ impl AdoConfig {
     pub fn command(&self) -> &ConfigCommand {
         &self.config_file.command
     }
+
+    #[must_use]
+    pub fn agent(&self) -> &ConfigAgent {
+        &self.config_file.agent
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use crate::llm::config::{ClaudeConfig, ConfigOllama};
+
+    use super::*;
+
+    #[test]
+    fn test_sync_roundtrip() {
+        let td = tempfile::Builder::new().prefix("config_").tempdir().unwrap();
+        let path = td.path().join(CONFIG_FILE_NAME);
+
+        let mut config = AdoConfig::with_claude(&path, ClaudeConfig::new("key", ""));
+        config.claude_mut().unwrap().model = "claude-sonnet-4-6".into();
+        config.sync().unwrap();
+
+        let config = AdoConfig::from_path(&path).unwrap();
+        assert_eq!(config.llm_provider(), "claude");
+        assert_eq!(config.claude().unwrap().model, "claude-sonnet-4-6");
+
+        let config =
+            AdoConfig::with_ollama(&path, ConfigOllama::new("http://localhost:11434", "llama3"));
+        config.sync().unwrap();
+
+        let config = AdoConfig::from_path(&path).unwrap();
+        assert_eq!(config.llm_provider(), "ollama");
+        assert_eq!(config.ollama().unwrap().model, "llama3");
+    }
+
+    #[test]
+    fn test_key_cmd_not_synced() {
+        let td = tempfile::Builder::new().prefix("config_").tempdir().unwrap();
+        let path = td.path().join(CONFIG_FILE_NAME);
+
+        let mut claude = ClaudeConfig::new("", "claude-sonnet-4-6");
+        claude.key_cmd = Some("printf s3cr3t".into());
+        AdoConfig::with_claude(&path, claude).sync().unwrap();
+
+        let config = AdoConfig::from_path(&path).unwrap();
+        assert_eq!(config.claude().unwrap().api_key(), "s3cr3t");
+
+        config.sync().unwrap();
+
+        let written: toml::Table = toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let claude = written["llm"]["claude"].as_table().unwrap();
+        assert!(!claude.contains_key("key"));
+        assert_eq!(claude["key_cmd"].as_str(), Some("printf s3cr3t"));
+    }
+
+    #[test]
+    fn test_unresolved_key() {
+        let td = tempfile::Builder::new().prefix("config_").tempdir().unwrap();
+        let path = td.path().join(CONFIG_FILE_NAME);
+
+        let mut claude = ClaudeConfig::new("", "claude-sonnet-4-6");
+        claude.key_env = Some("ADO_TEST_SECRET_THAT_IS_NOT_SET".into());
+        AdoConfig::with_claude(&path, claude).sync().unwrap();
+
+        let ret = AdoConfig::from_path(&path);
+        assert!(matches!(ret, Err(Error::ApiKeyNotFound { .. })));
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let td = tempfile::Builder::new().prefix("config_").tempdir().unwrap();
+
+        let ret = AdoConfig::from_path(td.path().join(CONFIG_FILE_NAME));
+        assert!(matches!(ret, Err(Error::FileNotFoundError { .. })));
+    }
+
+    #[test]
+    fn test_agent() {
+        let llm = "[llm]\nprovider = \"ollama\"\n\n[llm.ollama]\nendpoint = \"e\"\nmodel = \"m\"\n";
+
+        let config = AdoConfig::from_string(llm).unwrap();
+        assert_eq!(config.agent().shell, ShellPolicy::Ask);
+        assert_eq!(config.agent().max_iterations, 25);
+
+        let config =
+            AdoConfig::from_string(format!("{llm}\n[agent]\nshell = \"never\"\n")).unwrap();
+        assert_eq!(config.agent().shell, ShellPolicy::Never);
+
+        assert!(
+            AdoConfig::from_string(format!("{llm}\n[agent]\nshell = \"sometimes\"\n")).is_err()
+        );
+    }
+
+    #[test]
+    fn test_entries_origin() {
+        let td = tempfile::Builder::new().prefix("config_").tempdir().unwrap();
+        let path = td.path().join(CONFIG_FILE_NAME);
+
+        fs::write(
+            &path,
+            "[llm]\nprovider = \"ollama\"\n\n[llm.ollama]\nendpoint = \"e\"\nmodel = \"m\"\n",
+        )
+        .unwrap();
+
+        let config = AdoConfig::from_path(&path).unwrap();
+        let entries = config.entries().unwrap();
+
+        let origin =
+            |key: &str| entries.iter().find(|e| e.key == key).map(|e| e.origin.clone()).unwrap();
+
+        assert_eq!(origin("llm.ollama.model"), ConfigOrigin::User(path.clone()));
+        assert_eq!(origin("agent.max_iterations"), ConfigOrigin::Default);
+    }
+
+    #[test]
+    fn test_load_empty_profile() {
+        let td = tempfile::Builder::new().prefix("config_").tempdir().unwrap();
+        let path = td.path().join(CONFIG_FILE_NAME);
+
+        fs::write(
+            &path,
+            "[llm]\nprovider = \"ollama\"\n\n[llm.ollama]\nendpoint = \"e\"\nmodel = \"m\"\n\n\
+             [profile.empty]\n",
+        )
+        .unwrap();
+
+        let config = AdoConfig::load(&path, Some("empty"), &[] as &[&str]).unwrap();
+        assert_eq!(config.profile(), Some("empty"));
+
+        let config = AdoConfig::load(&path, None, &[] as &[&str]).unwrap();
+        assert_eq!(config.profile(), None);
+    }
 }
@@ -1 +1,4 @@
+pub mod check;
+pub mod layers;
 pub mod loader;
+pub mod secret;
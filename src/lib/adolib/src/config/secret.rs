@@ -0,0 +1,99 @@
+use std::{env, process::Command};
+
+use log::{error, info};
+
+use crate::error::{Error, Result};
+
+/// Where a secret such as an API key comes from. Only the reference is ever
+/// stored in the config file, the value itself lives in memory.
+pub enum SecretSource<'a> {
+    /// `key_env = "ANTHROPIC_API_KEY"`
+    Env(&'a str),
+    /// `key_cmd = "pass show anthropic"`, run through `sh -c`
+    Command(&'a str),
+}
+
+fn run_secret_command(cmd: &str) -> Result<String> {
+    let out =
+        Command::new("sh")
+            .arg("-c")
+            .arg(cmd)
+            .output()
+            .map_err(|e| Error::ApiKeyNotFound {
+                reason: format!("unable to execute `{cmd}` ({e})"),
+            })?;
+
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        return Err(Error::ApiKeyNotFound {
+            reason: format!("`{cmd}` failed with {} {}", out.status, stderr.trim()),
+        });
+    }
+
+    // `pass` and friends print the secret on the first line
+    let stdout = String::from_utf8(out.stdout)?;
+    let secret = stdout.lines().next().unwrap_or_default().trim().to_string();
+
+    if secret.is_empty() {
+        return Err(Error::ApiKeyNotFound {
+            reason: format!("`{cmd}` returned nothing"),
+        });
+    }
+
+    Ok(secret)
+}
+
+pub fn resolve_secret(source: &SecretSource) -> Result<String> {
+    match source {
+        SecretSource::Env(name) => {
+            info!("reading secret from ${name}");
+
+            match env::var(name) {
+                Ok(v) if !v.trim().is_empty() => Ok(v.trim().to_string()),
+                Ok(_) => Err(Error::ApiKeyNotFound {
+                    reason: format!("${name} is empty"),
+                }),
+                Err(e) => {
+                    error!("unable to read ${name} ({e})");
+                    Err(Error::ApiKeyNotFound {
+                        reason: format!("${name} is not set"),
+                    })
+                }
+            }
+        }
+        SecretSource::Command(cmd) => {
+            info!("reading secret from `{cmd}`");
+            run_secret_command(cmd)
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let secret = resolve_secret(&SecretSource::Command("printf 'abc\\nignored'")).unwrap();
+        assert_eq!(secret, "abc");
+
+        let ret = resolve_secret(&SecretSource::Command("exit 3"));
+        assert!(matches!(ret, Err(Error::ApiKeyNotFound { .. })));
+
+        let ret = resolve_secret(&SecretSource::Command("true"));
+        assert!(matches!(ret, Err(Error::ApiKeyNotFound { .. })));
+    }
+
+    #[test]
+    fn test_env() {
+        let ret = resolve_secret(&SecretSource::Env("ADO_TEST_SECRET_THAT_IS_NOT_SET"));
+        assert!(matches!(ret, Err(Error::ApiKeyNotFound { .. })));
+
+        let path = resolve_secret(&SecretSource::Env("PATH")).unwrap();
+        assert!(!path.is_empty());
+    }
+}
@@ -4,7 +4,7 @@ use log::{error, info};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum AdoDataArtifactType {
     Code,
@@ -12,6 +12,9 @@ pub enum AdoDataArtifactType {
     File,
     Command,
     Note,
+    Skill,
+    Memory,
+    Edit,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -41,13 +44,20 @@ pub struct AdoDataMeta {
     pub confidence: f32,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AdoDataArtifact {
     #[serde(rename = "type")]
     pub artifact_type: AdoDataArtifactType,
     pub language: Option<String>,
     pub path: Option<PathBuf>,
     pub content: String,
+    /// `edit` only: the exact text replaced by `content`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    /// `edit` only: which match of `old` to replace, 1-based, when it isn't
+    /// unique
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub occurrence: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -100,10 +110,12 @@ pub fn ado_data_schema() -> Value {
                             "type": "object",
                             "additionalProperties": false,
                             "properties": {
-                                "type": { "type": "string", "enum": ["code", "diff", "file", "command", "note"] },
+                                "type": { "type": "string", "enum": ["code", "diff", "file", "command", "note", "skill", "memory", "edit"] },
                                 "language": { "type": ["string", "null"] },
                                 "path": { "type": ["string", "null"] },
-                                "content": { "type": "string" }
+                                "content": { "type": "string" },
+                                "old": { "type": ["string", "null"] },
+                                "occurrence": { "type": ["integer", "null"] }
                             },
                             "required": ["type", "language", "path", "content"]
                         }
@@ -165,8 +177,51 @@ impl Display for AdoDataArtifactType {
             AdoDataArtifactType::Diff => "diff",
             AdoDataArtifactType::File => "file",
             AdoDataArtifactType::Note => "note",
+            AdoDataArtifactType::Skill => "skill",
+            AdoDataArtifactType::Memory => "memory",
+            AdoDataArtifactType::Edit => "edit",
         };
 
         write!(f, "{s}")
     }
 }
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_artifact() {
+        let json = r#"{
+            "meta": { "status": "partial", "intent": "fix typo", "confidence": 0.9 },
+            "response": {
+                "message": "Fixing the typo",
+                "artifacts": [
+                    { "type": "edit", "language": null, "path": "/a.rs", "content": "hello", "old": "helo", "occurrence": 2 },
+                    { "type": "command", "language": null, "path": null, "content": "ls" }
+                ]
+            },
+            "error": null
+        }"#;
+
+        let data: AdoData = json.parse().unwrap();
+        let artifacts = data.response.artifacts.unwrap();
+
+        assert!(matches!(
+            artifacts[0].artifact_type,
+            AdoDataArtifactType::Edit
+        ));
+        assert_eq!(artifacts[0].old.as_deref(), Some("helo"));
+        assert_eq!(artifacts[0].occurrence, Some(2));
+
+        // the edit fields are optional for every other type
+        assert!(artifacts[1].old.is_none());
+        assert!(artifacts[1].occurrence.is_none());
+        let json = serde_json::to_value(&artifacts[1]).unwrap();
+        assert!(json.get("old").is_none());
+    }
+}
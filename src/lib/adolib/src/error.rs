@@ -77,22 +77,28 @@ pub enum Error {
     LlmNotFound { llm: String },
     #[error("MissingArgument: {name}")]
     MissingArgument { name: String },
-    #[error(
-        "API was not found. Either use an config file or define the OPENAI_API_KEY env variable"
-    )]
-    ApiKeyNotFound,
+    #[error("ApiKeyNotFound: {reason}")]
+    ApiKeyNotFound { reason: String },
     #[error("ApiFailure: {message}")]
     ApiFailure { message: String },
     #[error("CommandNotFound: {command}")]
     CommandNotFound { command: String },
     #[error("Usage: {help}")]
     Usage { help: String },
+    #[error("UnsupportedOption: {option}")]
+    UnsupportedOption { option: String },
     #[error("StorageWriteFailure")]
     StorageWriteFailure,
     #[error("ConfigError: {error}")]
     ConfigError { error: String },
     #[error("ToolNotFound")]
     ToolNotFound,
+    #[error("InvalidPatch: line {line}, {reason}")]
+    InvalidPatch { line: usize, reason: String },
+    #[error("UnsupportedAttachment: {media_type}")]
+    UnsupportedAttachment { media_type: String },
+    #[error("TooManyIterations: stopped after {limit} iterations, see agent.max_iterations")]
+    TooManyIterations { limit: u32 },
     //
     // 2nd party
     //
@@ -123,6 +129,8 @@ pub enum Error {
     #[error(transparent)]
     WalkDir(#[from] walkdir::Error),
     #[error(transparent)]
+    Regex(#[from] regex::Error),
+    #[error(transparent)]
     Http(#[from] ureq::Error),
     #[error(transparent)]
     Cache(#[from] sled::Error),
@@ -5,6 +5,8 @@ pub mod const_vars;
 pub mod data;
 pub mod error;
 pub mod llm;
+pub mod patch;
 pub(crate) mod rest;
 pub mod search;
+pub mod tools;
 pub mod ui;
@@ -0,0 +1,201 @@
+use std::{fs, io::Read, path::Path};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use log::info;
+
+use crate::error::{Error, Result};
+
+/// Largest image the providers accept
+pub const MAX_IMAGE_SIZE: u64 = 5 * 1024 * 1024;
+/// Largest PDF the providers accept
+pub const MAX_DOCUMENT_SIZE: u64 = 32 * 1024 * 1024;
+
+const PDF_MEDIA_TYPE: &str = "application/pdf";
+
+/// Bytes needed to recognize every supported format
+const MAGIC_LEN: usize = 12;
+
+/// An image or a PDF sent along a user message, base64 encoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LLMAttachment {
+    pub media_type: String,
+    pub data: String,
+}
+
+/// MIME type of the supported attachments, from their magic bytes
+#[must_use]
+pub fn media_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        Some("image/webp")
+    } else if bytes.starts_with(b"%PDF-") {
+        Some(PDF_MEDIA_TYPE)
+    } else {
+        None
+    }
+}
+
+/// MIME type of the file at `path` when it can be attached, `None` for any
+/// other file, text included
+pub fn detect_media_type(path: &Path) -> Result<Option<&'static str>> {
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+
+    fs::File::open(path)?.take(MAGIC_LEN as u64).read_to_end(&mut magic)?;
+
+    Ok(media_type(&magic))
+}
+
+fn size_limit(media_type: &str) -> u64 {
+    if media_type == PDF_MEDIA_TYPE {
+        MAX_DOCUMENT_SIZE
+    } else {
+        MAX_IMAGE_SIZE
+    }
+}
+
+impl LLMAttachment {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let media_type = media_type(bytes).ok_or_else(|| Error::UnsupportedAttachment {
+            media_type: "unknown".to_string(),
+        })?;
+
+        let size = bytes.len() as u64;
+        let limit = size_limit(media_type);
+
+        if size > limit {
+            return Err(Error::FileTooLarge { size, limit });
+        }
+
+        Ok(Self {
+            media_type: media_type.to_string(),
+            data: STANDARD.encode(bytes),
+        })
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self> {
+        // checked before reading, the type isn't known yet
+        let size = fs::metadata(path)?.len();
+
+        if size > MAX_DOCUMENT_SIZE {
+            return Err(Error::FileTooLarge {
+                size,
+                limit: MAX_DOCUMENT_SIZE,
+            });
+        }
+
+        let attachment = LLMAttachment::from_bytes(&fs::read(path)?)?;
+
+        info!(
+            "attachment {} ({}, {size} bytes)",
+            path.display(),
+            attachment.media_type
+        );
+
+        Ok(attachment)
+    }
+
+    /// From data already base64 encoded, e.g. read back from a conversation
+    pub fn from_base64<S>(data: S) -> Result<Self>
+    where
+        S: Into<String>,
+    {
+        let data = data.into();
+
+        // only the magic bytes are needed, 16 base64 chars decode to 12 bytes
+        let prefix = data.get(..16).unwrap_or(&data);
+        let magic = STANDARD.decode(prefix)?;
+
+        let media_type = media_type(&magic).ok_or_else(|| Error::UnsupportedAttachment {
+            media_type: "unknown".to_string(),
+        })?;
+
+        Ok(Self {
+            media_type: media_type.to_string(),
+            data,
+        })
+    }
+
+    #[must_use]
+    pub fn is_image(&self) -> bool {
+        self.media_type.starts_with("image/")
+    }
+
+    /// Size of the file, not of its encoding
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        let padding = self.data.bytes().rev().take_while(|b| *b == b'=').count() as u64;
+        ((self.data.len() as u64).saturating_mul(3) / 4).saturating_sub(padding)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn test_media_type() {
+        assert_eq!(media_type(PNG), Some("image/png"));
+        assert_eq!(media_type(&[0xff, 0xd8, 0xff, 0xe0]), Some("image/jpeg"));
+        assert_eq!(media_type(b"GIF89a..."), Some("image/gif"));
+        assert_eq!(media_type(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(media_type(b"RIFF\0\0\0\0WAVEfmt "), None);
+        assert_eq!(media_type(b"%PDF-1.7"), Some("application/pdf"));
+        assert_eq!(media_type(b"fn main() {}"), None);
+        assert_eq!(media_type(b""), None);
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let attachment = LLMAttachment::from_bytes(PNG).unwrap();
+        assert_eq!(attachment.media_type, "image/png");
+        assert!(attachment.is_image());
+        assert_eq!(attachment.size(), PNG.len() as u64);
+
+        let back = LLMAttachment::from_base64(attachment.data.clone()).unwrap();
+        assert_eq!(back, attachment);
+
+        assert!(matches!(
+            LLMAttachment::from_bytes(b"plain text"),
+            Err(Error::UnsupportedAttachment { .. })
+        ));
+
+        let mut large = PNG.to_vec();
+        large.resize(usize::try_from(MAX_IMAGE_SIZE).unwrap() + 1, 0);
+        assert!(matches!(
+            LLMAttachment::from_bytes(&large),
+            Err(Error::FileTooLarge {
+                limit: MAX_IMAGE_SIZE,
+                ..
+            })
+        ));
+
+        // PDFs get a larger budget than images
+        large.splice(..PNG.len(), b"%PDF-1.7".iter().copied());
+        let pdf = LLMAttachment::from_bytes(&large).unwrap();
+        assert!(!pdf.is_image());
+    }
+
+    #[test]
+    fn test_detect_media_type() {
+        let mut png = tempfile::NamedTempFile::new().unwrap();
+        png.write_all(PNG).unwrap();
+        assert_eq!(detect_media_type(png.path()).unwrap(), Some("image/png"));
+
+        let mut text = tempfile::NamedTempFile::new().unwrap();
+        text.write_all(b"hi").unwrap();
+        assert_eq!(detect_media_type(text.path()).unwrap(), None);
+    }
+}
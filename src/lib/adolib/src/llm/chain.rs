@@ -5,9 +5,14 @@ use serde::{Deserialize, Serialize};
 use crate::{
     config::loader::AdoConfig,
     console::ConsoleTrait,
-    data::types::AdoData,
+    data::types::{AdoData, AdoDataStatus},
     error::{Error, Result},
-    llm::{claude::claude_chain::ClaudeChain, ollama::ollama_chain::OllamaChain},
+    llm::{
+        attachment::LLMAttachment,
+        claude::claude_chain::ClaudeChain,
+        message_cache::{LLMMessageCache, LLMMessageKey},
+        ollama::ollama_chain::OllamaChain,
+    },
 };
 
 use log::{error, info};
@@ -39,12 +44,25 @@ impl From<LLMRole> for String {
     }
 }
 
+/// A user or assistant message of the conversation, system prompts excluded
+pub struct LLMTurn {
+    pub role: LLMRole,
+    pub content: String,
+    /// images and documents sent with a user message
+    pub attachments: Vec<LLMAttachment>,
+}
+
 pub trait LLMChainTrait {
     fn add_content<S>(&mut self, role: LLMRole, content: S)
+    where
+        S: Into<String>;
+    /// A user message with images or documents, fails when the provider
+    /// doesn't support one of them
+    fn add_user_content<S>(&mut self, content: S, attachments: &[LLMAttachment]) -> Result<()>
     where
         S: Into<String>;
     fn call(&mut self) -> Result<AdoData>;
-    fn message<S, M>(&self, content: S, model: Option<M>) -> Result<String>
+    fn message<S, M>(&self, system: Option<&str>, content: S, model: Option<M>) -> Result<String>
     where
         S: Into<String>,
         M: AsRef<str>;
@@ -55,24 +73,38 @@ pub trait LLMChainTrait {
     where
         S: AsRef<str> + Display;
     fn usage(&self) -> LLMUsage;
+    /// Carry over the usage of another chain, e.g. one built from a previous
+    /// configuration
+    fn restore_usage(&mut self, usage: LLMUsage);
     fn dump_chain(&self) -> Result<AdoData>;
+    fn turns(&self) -> Vec<LLMTurn>;
+    /// Drop the turns after the first `len`, system prompts kept
+    fn truncate_turns(&mut self, len: usize);
 }
 
-pub enum LLMChain {
+enum LLMBackend {
     Ollama(Box<OllamaChain>),
     Claude(Box<ClaudeChain>),
 }
 
+pub struct LLMChain {
+    backend: LLMBackend,
+    provider: String,
+    message_cache: Option<LLMMessageCache>,
+    /// model calls answering one prompt, 0 for no limit
+    max_iterations: u32,
+}
+
 impl LLMChain {
     pub fn new(config: &AdoConfig) -> Result<Self> {
-        let chain = match config.llm_provider() {
+        let backend = match config.llm_provider() {
             "ollama" => {
                 let chain = OllamaChain::new(config)?;
-                LLMChain::Ollama(Box::new(chain))
+                LLMBackend::Ollama(Box::new(chain))
             }
             "claude" => {
                 let chain = ClaudeChain::new(config)?;
-                LLMChain::Claude(Box::new(chain))
+                LLMBackend::Claude(Box::new(chain))
             }
             unk => {
                 error!("Unknown provider: {unk}");
@@ -80,51 +112,145 @@ impl LLMChain {
             }
         };
 
-        Ok(chain)
+        Ok(Self {
+            backend,
+            provider: config.llm_provider().to_string(),
+            message_cache: None,
+            max_iterations: config.agent().max_iterations,
+        })
+    }
+
+    /// Models offered by the configured provider, without building a chain.
+    /// Used to validate a config before it's written to disk.
+    pub fn available_models(config: &AdoConfig) -> Result<Vec<String>> {
+        match config.llm_provider() {
+            "ollama" => OllamaChain::available_models(config.ollama()?),
+            "claude" => ClaudeChain::available_models(config.claude()?),
+            unk => Err(Error::LlmNotFound { llm: unk.into() }),
+        }
+    }
+
+    /// Opt in to caching [`LLMChain::message`] responses. Conversation calls
+    /// made through [`LLMChain::link`] are never cached.
+    pub fn set_message_cache(&mut self, cache: LLMMessageCache) {
+        self.message_cache = Some(cache);
     }
 
     fn call(&mut self) -> Result<AdoData> {
-        match self {
-            LLMChain::Claude(claude) => claude.call(),
-            LLMChain::Ollama(ollama) => ollama.call(),
+        match &mut self.backend {
+            LLMBackend::Claude(claude) => claude.call(),
+            LLMBackend::Ollama(ollama) => ollama.call(),
         }
     }
 
     #[must_use]
     pub fn models(&self) -> Vec<String> {
-        match self {
-            LLMChain::Claude(claude) => claude.models(),
-            LLMChain::Ollama(ollama) => ollama.models(),
+        match &self.backend {
+            LLMBackend::Claude(claude) => claude.models(),
+            LLMBackend::Ollama(ollama) => ollama.models(),
         }
     }
 
+    #[must_use]
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
     pub fn message<S, M>(&self, content: S, model: Option<M>) -> Result<String>
     where
         S: Into<String>,
         M: AsRef<str>,
     {
-        match self {
-            LLMChain::Ollama(ollama) => ollama.message(content, model),
-            LLMChain::Claude(claude) => claude.message(content, model),
+        self.message_with_system(None, content, model)
+    }
+
+    /// One-shot request outside of the conversation, optionally preceded by a
+    /// system prompt, to `model` or the chain's model. Served from the message
+    /// cache when one is configured.
+    pub fn message_with_system<S, M>(
+        &self,
+        system: Option<&str>,
+        content: S,
+        model: Option<M>,
+    ) -> Result<String>
+    where
+        S: Into<String>,
+        M: AsRef<str>,
+    {
+        let content = content.into();
+        let model = model.map_or_else(|| self.model().to_string(), |m| m.as_ref().to_string());
+
+        let key = LLMMessageKey {
+            provider: &self.provider,
+            model: &model,
+            system: system.unwrap_or_default(),
+            content: &content,
+        };
+
+        if let Some(cache) = &self.message_cache
+            && let Some(cached) = cache.get(&key)
+        {
+            return Ok(cached);
         }
+
+        let ret = match &self.backend {
+            LLMBackend::Ollama(ollama) => ollama.message(system, content.as_str(), Some(&model)),
+            LLMBackend::Claude(claude) => claude.message(system, content.as_str(), Some(&model)),
+        }?;
+
+        if let Some(cache) = &self.message_cache {
+            cache.put(&key, &ret);
+        }
+
+        Ok(ret)
     }
 
     pub fn add_content<S>(&mut self, role: LLMRole, content: S)
     where
         S: Into<String>,
     {
-        match self {
-            LLMChain::Ollama(ollama) => ollama.add_content(role, content),
-            LLMChain::Claude(claude) => claude.add_content(role, content),
+        match &mut self.backend {
+            LLMBackend::Ollama(ollama) => ollama.add_content(role, content),
+            LLMBackend::Claude(claude) => claude.add_content(role, content),
+        }
+    }
+
+    pub fn add_user_content<S>(&mut self, content: S, attachments: &[LLMAttachment]) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        match &mut self.backend {
+            LLMBackend::Ollama(ollama) => ollama.add_user_content(content, attachments),
+            LLMBackend::Claude(claude) => claude.add_user_content(content, attachments),
         }
     }
 
     pub fn link<C, S>(&mut self, content: S, console: &C) -> Result<()>
     where
-        C: ConsoleTrait + Send + Sync,
+        C: ConsoleTrait + ?Sized,
+        S: Into<String>,
+    {
+        self.link_with_attachments(content, &[], console)
+    }
+
+    /// [`LLMChain::link`] with images or documents sent along `content`
+    pub fn link_with_attachments<C, S>(
+        &mut self,
+        content: S,
+        attachments: &[LLMAttachment],
+        console: &C,
+    ) -> Result<()>
+    where
+        C: ConsoleTrait + ?Sized,
         S: Into<String>,
     {
-        self.add_content(LLMRole::User, content);
+        if attachments.is_empty() {
+            self.add_content(LLMRole::User, content);
+        } else {
+            self.add_user_content(content, attachments)?;
+        }
+
+        let mut iterations: u32 = 0;
 
         loop {
             console.enter_thinking("");
@@ -132,14 +258,29 @@ impl LLMChain {
             console.leave_thinking();
 
             let data = ret?;
+            iterations = iterations.saturating_add(1);
+
+            // stop before the console executes the artifacts of a partial
+            // response, their results would never reach the model. The
+            // conversation stays valid for the next prompt.
+            if self.max_iterations > 0
+                && iterations >= self.max_iterations
+                && matches!(data.meta.status, AdoDataStatus::Partial)
+            {
+                error!("agent stopped after {iterations} iterations");
+                return Err(Error::TooManyIterations {
+                    limit: self.max_iterations,
+                });
+            }
 
             match console.io(data) {
                 Some(r) => {
+                    info!("console returned {r}");
+
                     //
                     // we're continuing...
                     //
                     self.add_content(LLMRole::User, &r);
-                    info!("console returned {r}");
                 }
                 None => break,
             }
@@ -149,17 +290,17 @@ impl LLMChain {
     }
 
     pub fn reset(&mut self) {
-        match self {
-            LLMChain::Ollama(ollama) => ollama.reset(),
-            LLMChain::Claude(claude) => claude.reset(),
+        match &mut self.backend {
+            LLMBackend::Ollama(ollama) => ollama.reset(),
+            LLMBackend::Claude(claude) => claude.reset(),
         }
     }
 
     #[must_use]
     pub fn model(&self) -> &str {
-        match self {
-            LLMChain::Ollama(ollama) => ollama.model(),
-            LLMChain::Claude(claude) => claude.model(),
+        match &self.backend {
+            LLMBackend::Ollama(ollama) => ollama.model(),
+            LLMBackend::Claude(claude) => claude.model(),
         }
     }
 
@@ -167,24 +308,64 @@ impl LLMChain {
     where
         S: AsRef<str> + Display,
     {
-        match self {
-            LLMChain::Ollama(ollama) => ollama.change_model(model),
-            LLMChain::Claude(claude) => claude.change_model(model),
+        match &mut self.backend {
+            LLMBackend::Ollama(ollama) => ollama.change_model(model),
+            LLMBackend::Claude(claude) => claude.change_model(model),
         }
     }
 
     #[must_use]
     pub fn usage(&self) -> LLMUsage {
-        match self {
-            LLMChain::Ollama(ollama) => ollama.usage(),
-            LLMChain::Claude(claude) => claude.usage(),
+        match &self.backend {
+            LLMBackend::Ollama(ollama) => ollama.usage(),
+            LLMBackend::Claude(claude) => claude.usage(),
+        }
+    }
+
+    pub fn restore_usage(&mut self, usage: LLMUsage) {
+        match &mut self.backend {
+            LLMBackend::Ollama(ollama) => ollama.restore_usage(usage),
+            LLMBackend::Claude(claude) => claude.restore_usage(usage),
+        }
+    }
+
+    #[must_use]
+    pub fn turns(&self) -> Vec<LLMTurn> {
+        match &self.backend {
+            LLMBackend::Ollama(ollama) => ollama.turns(),
+            LLMBackend::Claude(claude) => claude.turns(),
+        }
+    }
+
+    /// Rewind the conversation to its first `len` turns
+    pub fn truncate_turns(&mut self, len: usize) {
+        match &mut self.backend {
+            LLMBackend::Ollama(ollama) => ollama.truncate_turns(len),
+            LLMBackend::Claude(claude) => claude.truncate_turns(len),
+        }
+    }
+
+    /// Append the `turns` of another chain, e.g. one built from a previous
+    /// configuration, after this chain's system prompts. Attachments the
+    /// provider doesn't support are dropped.
+    pub fn restore_turns(&mut self, turns: Vec<LLMTurn>) {
+        for t in turns {
+            if t.attachments.is_empty() {
+                self.add_content(t.role, t.content);
+                continue;
+            }
+
+            if let Err(e) = self.add_user_content(t.content.as_str(), &t.attachments) {
+                error!("dropping attachments ({e})");
+                self.add_content(t.role, t.content);
+            }
         }
     }
 
     pub fn dump_chain(&self) -> Result<AdoData> {
-        match self {
-            LLMChain::Ollama(ollama) => ollama.dump_chain(),
-            LLMChain::Claude(claude) => claude.dump_chain(),
+        match &self.backend {
+            LLMBackend::Ollama(ollama) => ollama.dump_chain(),
+            LLMBackend::Claude(claude) => claude.dump_chain(),
         }
     }
 }
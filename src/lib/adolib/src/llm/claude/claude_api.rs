@@ -7,6 +7,7 @@ use serde_json::Value;
 
 use crate::error::Error;
 use crate::error::Result;
+use crate::llm::attachment::LLMAttachment;
 use crate::llm::claude::claude_config::ClaudeConfig;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -43,6 +44,19 @@ pub enum ClaudeContentType {
     #[default]
     #[serde(rename = "text")]
     Text,
+    #[serde(rename = "image")]
+    Image,
+    #[serde(rename = "document")]
+    Document,
+}
+
+/// Inline data of an image or document block
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ClaudeSource {
+    #[serde(rename = "type")]
+    pub source_type: String,
+    pub media_type: String,
+    pub data: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -72,6 +86,8 @@ pub struct ClaudeContent {
     pub input: Option<HashMap<String, Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<ClaudeSource>,
     /// Marks a prompt-caching breakpoint; everything up to and including this
     /// block is cached. Only ever set on the last system block.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -185,6 +201,27 @@ impl ClaudeMessage {
     }
 }
 
+impl ClaudeContent {
+    #[must_use]
+    pub fn with_attachment(attachment: &LLMAttachment) -> Self {
+        let content_type = if attachment.is_image() {
+            ClaudeContentType::Image
+        } else {
+            ClaudeContentType::Document
+        };
+
+        Self {
+            content_type,
+            source: Some(ClaudeSource {
+                source_type: "base64".to_string(),
+                media_type: attachment.media_type.clone(),
+                data: attachment.data.clone(),
+            }),
+            ..Default::default()
+        }
+    }
+}
+
 impl ClaudeResponse {
     pub fn message(&self) -> Result<&str> {
         let text = self.content.first().ok_or(Error::Empty)?;
@@ -230,10 +267,48 @@ impl ClaudeMessages {
         self.messages.push(message);
     }
 
+    /// A message made of content blocks, the attachments before the text as
+    /// recommended for images
+    pub fn add_message_with_attachments<C>(
+        &mut self,
+        role: ClaudeRole,
+        message: C,
+        attachments: &[LLMAttachment],
+    ) -> Result<()>
+    where
+        C: Into<String>,
+    {
+        let mut blocks: Vec<ClaudeContent> =
+            attachments.iter().map(ClaudeContent::with_attachment).collect();
+
+        blocks.push(ClaudeContent {
+            content_type: ClaudeContentType::Text,
+            text: Some(message.into()),
+            ..Default::default()
+        });
+
+        self.messages.push(ClaudeMessage {
+            role,
+            content: serde_json::to_value(blocks)?,
+        });
+
+        Ok(())
+    }
+
     pub fn reset(&mut self) {
         self.messages = vec![];
     }
 
+    /// Keep the first `len` messages only
+    pub fn truncate(&mut self, len: usize) {
+        self.messages.truncate(len);
+    }
+
+    #[must_use]
+    pub fn messages(&self) -> &[ClaudeMessage] {
+        &self.messages
+    }
+
     /// Constrain responses to the given JSON schema via Anthropic structured
     /// outputs, so the model can only emit schema-valid JSON.
     pub fn set_output_schema(&mut self, schema: &Value) {
@@ -266,26 +341,40 @@ impl ClaudeMessages {
         }
 
         // Conversation prefix: move the breakpoint to the current last message.
-        // Revert any prior block-form content back to a plain string first.
+        // Revert any prior text-only block form back to a plain string first,
+        // messages with attachments keep their blocks.
         for msg in &mut self.messages {
-            if msg.content.is_array() {
-                let text = msg
-                    .content
-                    .get(0)
-                    .and_then(|b| b.get("text"))
-                    .and_then(Value::as_str)
-                    .unwrap_or_default()
-                    .to_string();
+            let Some(blocks) = msg.content.as_array_mut() else {
+                continue;
+            };
+
+            for block in blocks.iter_mut() {
+                if let Some(block) = block.as_object_mut() {
+                    block.remove("cache_control");
+                }
+            }
+
+            if let [block] = blocks.as_slice()
+                && block.get("type").and_then(Value::as_str) == Some("text")
+            {
+                let text =
+                    block.get("text").and_then(Value::as_str).unwrap_or_default().to_string();
                 msg.content = Value::String(text);
             }
         }
         if let Some(last) = self.messages.last_mut() {
-            let text = last.content.as_str().unwrap_or_default().to_string();
-            last.content = serde_json::json!([{
-                "type": "text",
-                "text": text,
-                "cache_control": { "type": "ephemeral" }
-            }]);
+            if let Some(text) = last.content.as_str() {
+                last.content = serde_json::json!([{ "type": "text", "text": text }]);
+            }
+
+            if let Some(block) = last.content.as_array_mut().and_then(|b| b.last_mut())
+                && let Some(block) = block.as_object_mut()
+            {
+                block.insert(
+                    "cache_control".to_string(),
+                    serde_json::json!({ "type": "ephemeral" }),
+                );
+            }
         }
     }
 }
@@ -300,7 +389,7 @@ impl ClaudeApi {
         let url = format!("{}/v1/models", self.config.url);
 
         let mut res = ureq::get(&url)
-            .header("x-api-key", &self.config.key)
+            .header("x-api-key", self.config.api_key())
             .header("anthropic-version", &self.config.anthropic_version)
             .call()?;
 
@@ -331,7 +420,7 @@ impl ClaudeApi {
 
         let mut res = ureq::post(&url)
             .header("Content-Type", "application/json")
-            .header("x-api-key", &self.config.key)
+            .header("x-api-key", self.config.api_key())
             .header("anthropic-version", &self.config.anthropic_version)
             .send(req_json)?;
 
@@ -371,11 +460,21 @@ impl ClaudeApi {
         }
     }
 
-    pub fn message<S>(&self, content: S) -> Result<ClaudeResponse>
+    /// One-shot request to `model`, which may differ from the configured one
+    pub fn message<S>(
+        &self,
+        model: &str,
+        system: Option<&str>,
+        content: S,
+    ) -> Result<ClaudeResponse>
     where
         S: Into<String>,
     {
-        let mut chat = ClaudeMessages::new(&self.config.model, 4096);
+        let mut chat = ClaudeMessages::new(model, 4096);
+
+        if let Some(system) = system {
+            chat.add_system_prompt(system);
+        }
 
         chat.add_message(ClaudeRole::User, content);
 
@@ -389,7 +488,10 @@ impl ClaudeApi {
 
 #[cfg(test)]
 mod tests {
-    use crate::llm::claude::claude_api::{ClaudeMessages, ClaudeResponse, ClaudeRole};
+    use crate::llm::{
+        attachment::LLMAttachment,
+        claude::claude_api::{ClaudeMessages, ClaudeResponse, ClaudeRole},
+    };
 
     #[test]
     fn test_response() {
@@ -454,4 +556,37 @@ mod tests {
         assert_eq!(system_bps, 1);
         assert_eq!(msg_bps, 1);
     }
+
+    #[test]
+    fn test_attachments() {
+        let png = LLMAttachment::from_bytes(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let pdf = LLMAttachment::from_bytes(b"%PDF-1.7").unwrap();
+
+        let mut chat = ClaudeMessages::new("claude-opus-4-7", 1024);
+        chat.add_message_with_attachments(ClaudeRole::User, "what's this?", &[png, pdf])
+            .unwrap();
+
+        let blocks = &chat.messages[0].content;
+        assert_eq!(blocks[0]["type"], "image");
+        assert_eq!(blocks[0]["source"]["type"], "base64");
+        assert_eq!(blocks[0]["source"]["media_type"], "image/png");
+        assert_eq!(blocks[1]["type"], "document");
+        assert_eq!(blocks[1]["source"]["media_type"], "application/pdf");
+        assert_eq!(blocks[2]["type"], "text");
+        assert_eq!(blocks[2]["text"], "what's this?");
+
+        // the breakpoint goes on the last block, then moves on without
+        // flattening the attachments
+        chat.set_cache_breakpoints();
+        assert_eq!(
+            chat.messages[0].content[2]["cache_control"]["type"],
+            "ephemeral"
+        );
+
+        chat.add_message(ClaudeRole::Assistant, "a screenshot");
+        chat.set_cache_breakpoints();
+        let blocks = chat.messages[0].content.as_array().unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert!(blocks.iter().all(|b| b.get("cache_control").is_none()));
+    }
 }
@@ -1,14 +1,19 @@
 use std::{fmt::Display, sync::atomic::AtomicI32};
 
 use log::info;
+use serde_json::Value;
 
 use crate::{
     config::loader::AdoConfig,
     data::types::AdoData,
     error::{Error, Result},
     llm::{
-        chain::{LLMChainTrait, LLMRole, LLMUsage},
-        claude::claude_api::{ClaudeApi, ClaudeMessages, ClaudeRole},
+        attachment::LLMAttachment,
+        chain::{LLMChainTrait, LLMRole, LLMTurn, LLMUsage},
+        claude::{
+            claude_api::{ClaudeApi, ClaudeContent, ClaudeContentType, ClaudeMessages, ClaudeRole},
+            claude_config::ClaudeConfig,
+        },
     },
 };
 
@@ -48,6 +53,36 @@ impl ClaudeChain {
             tokens: LLMUsage::default(),
         })
     }
+
+    /// Text and attachments of a message in block form
+    fn split_blocks(content: &Value) -> (String, Vec<LLMAttachment>) {
+        let Ok(blocks) = serde_json::from_value::<Vec<ClaudeContent>>(content.clone()) else {
+            return (content.to_string(), Vec::new());
+        };
+
+        let mut text = Vec::new();
+        let mut attachments = Vec::new();
+
+        for block in blocks {
+            match block.content_type {
+                ClaudeContentType::Text => text.extend(block.text),
+                ClaudeContentType::Image | ClaudeContentType::Document => {
+                    attachments.extend(block.source.map(|s| LLMAttachment {
+                        media_type: s.media_type,
+                        data: s.data,
+                    }));
+                }
+            }
+        }
+
+        (text.join("\n"), attachments)
+    }
+
+    /// Models the API key in `config` has access to.
+    pub fn available_models(config: &ClaudeConfig) -> Result<Vec<String>> {
+        let models = ClaudeApi::new(config).models()?;
+        Ok(models.into_iter().map(|m| m.id).collect())
+    }
 }
 
 impl LLMChainTrait for ClaudeChain {
@@ -79,15 +114,7 @@ impl LLMChainTrait for ClaudeChain {
     }
 
     fn models(&self) -> Vec<String> {
-        let mut models = Vec::new();
-
-        if let Ok(ret_models) = self.api.models() {
-            for model in ret_models {
-                models.push(model.id);
-            }
-        }
-
-        models
+        ClaudeChain::available_models(&self.api.config).unwrap_or_default()
     }
 
     fn add_content<S>(&mut self, role: LLMRole, content: S)
@@ -103,12 +130,21 @@ impl LLMChainTrait for ClaudeChain {
         }
     }
 
-    fn message<S, M>(&self, content: S, _model: Option<M>) -> Result<String>
+    fn add_user_content<S>(&mut self, content: S, attachments: &[LLMAttachment]) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.messages
+            .add_message_with_attachments(ClaudeRole::User, content, attachments)
+    }
+
+    fn message<S, M>(&self, system: Option<&str>, content: S, model: Option<M>) -> Result<String>
     where
         S: Into<String>,
         M: AsRef<str>,
     {
-        let resp = self.api.message(content)?;
+        let model = model.as_ref().map_or_else(|| self.model(), AsRef::as_ref);
+        let resp = self.api.message(model, system, content)?;
         Ok(resp.message()?.to_string())
     }
 
@@ -137,6 +173,36 @@ impl LLMChainTrait for ClaudeChain {
         }
     }
 
+    fn restore_usage(&mut self, usage: LLMUsage) {
+        self.tokens = usage;
+    }
+
+    fn turns(&self) -> Vec<LLMTurn> {
+        self.messages
+            .messages()
+            .iter()
+            .map(|m| {
+                let (content, attachments) = match &m.content {
+                    Value::String(s) => (s.clone(), Vec::new()),
+                    v => ClaudeChain::split_blocks(v),
+                };
+
+                LLMTurn {
+                    role: match m.role {
+                        ClaudeRole::User => LLMRole::User,
+                        ClaudeRole::Assistant => LLMRole::Assistant,
+                    },
+                    content,
+                    attachments,
+                }
+            })
+            .collect()
+    }
+
+    fn truncate_turns(&mut self, len: usize) {
+        self.messages.truncate(len);
+    }
+
     fn dump_chain(&self) -> Result<AdoData> {
         Err(Error::NotImplemented)
     }
@@ -151,7 +217,10 @@ mod tests {
 
     use crate::{
         config::loader::AdoConfig,
-        llm::{chain::LLMChainTrait, claude::claude_chain::ClaudeChain},
+        llm::{
+            chain::{LLMChainTrait, LLMUsage},
+            claude::{claude_chain::ClaudeChain, claude_config::ClaudeConfig},
+        },
     };
 
     #[test]
@@ -161,6 +230,20 @@ mod tests {
 
         let chain = ClaudeChain::new(&config_file).unwrap();
 
-        chain.message("hello world", None::<&str>).unwrap();
+        chain.message(None, "hello world", None::<&str>).unwrap();
+    }
+
+    #[test]
+    fn test_restore_usage() {
+        let config = AdoConfig::with_claude("config.toml", ClaudeConfig::new("key", "model"));
+        let mut chain = ClaudeChain::new(&config).unwrap();
+
+        chain.restore_usage(LLMUsage {
+            input_tokens: 3,
+            output_tokens: 4,
+        });
+
+        let usage = chain.usage();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (3, 4));
     }
 }
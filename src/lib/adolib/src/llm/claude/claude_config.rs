@@ -1,5 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    config::secret::{SecretSource, resolve_secret},
+    error::{Error, Result},
+};
+
 /*
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub enum ClaudeToolChoiceType {
@@ -38,13 +43,77 @@ fn default_true() -> bool {
 }
 */
 
+pub const CLAUDE_DEFAULT_URL: &str = "https://api.anthropic.com";
+pub const CLAUDE_DEFAULT_VERSION: &str = "2023-06-01";
+pub const CLAUDE_DEFAULT_MAX_TOKENS: u64 = 8192;
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct ClaudeConfig {
     pub model: String,
     pub url: String,
     pub anthropic_version: String,
+    /// plaintext key, prefer `key_env` or `key_cmd`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub key: String,
+    /// environment variable holding the key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_env: Option<String>,
+    /// command printing the key on stdout (e.g. `pass show anthropic`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_cmd: Option<String>,
+    /// `key_env` / `key_cmd` resolved at load time, never written back
+    #[serde(skip)]
+    resolved_key: String,
     pub max_tokens: u64,
     pub instructions: Option<Vec<String>>,
     pub logs: Option<String>,
 }
+
+impl ClaudeConfig {
+    /// Config pointing at the public Anthropic API
+    pub fn new<K, M>(key: K, model: M) -> Self
+    where
+        K: Into<String>,
+        M: Into<String>,
+    {
+        Self {
+            model: model.into(),
+            url: CLAUDE_DEFAULT_URL.into(),
+            anthropic_version: CLAUDE_DEFAULT_VERSION.into(),
+            key: key.into(),
+            max_tokens: CLAUDE_DEFAULT_MAX_TOKENS,
+            ..Default::default()
+        }
+    }
+
+    /// The key to send to the API, see [`ClaudeConfig::resolve_key`]
+    #[must_use]
+    pub fn api_key(&self) -> &str {
+        if self.key.is_empty() {
+            &self.resolved_key
+        } else {
+            &self.key
+        }
+    }
+
+    /// Resolve `key_env` or `key_cmd`, a plaintext `key` always wins.
+    pub fn resolve_key(&mut self) -> Result<()> {
+        if !self.key.is_empty() {
+            return Ok(());
+        }
+
+        let source = match (&self.key_env, &self.key_cmd) {
+            (Some(env), _) => SecretSource::Env(env),
+            (None, Some(cmd)) => SecretSource::Command(cmd),
+            (None, None) => {
+                return Err(Error::ApiKeyNotFound {
+                    reason: "set one of `key`, `key_env` or `key_cmd` in [llm.claude]".into(),
+                });
+            }
+        };
+
+        self.resolved_key = resolve_secret(&source)?;
+
+        Ok(())
+    }
+}
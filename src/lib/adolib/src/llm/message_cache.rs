@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use log::{error, info};
+
+use crate::cache::kv::KVCache;
+
+const LLM_MESSAGE_CACHE_REALM: &str = "llm";
+
+/// Everything that influences a one-shot [`message`] response.
+///
+/// [`message`]: crate::llm::chain::LLMChain::message
+pub(crate) struct LLMMessageKey<'a> {
+    pub provider: &'a str,
+    pub model: &'a str,
+    pub system: &'a str,
+    pub content: &'a str,
+}
+
+impl LLMMessageKey<'_> {
+    fn as_cache_key(&self) -> String {
+        // NUL can't show up in a model name and keeps fields from bleeding
+        // into each other ("a" + "bc" vs "ab" + "c")
+        format!(
+            "{}\0{}\0{}\0{}",
+            self.provider, self.model, self.system, self.content
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LLMMessageCache {
+    cache: KVCache,
+    ttl: Duration,
+}
+
+impl LLMMessageCache {
+    /// A zero `ttl` keeps responses forever.
+    #[must_use]
+    pub fn new(cache: KVCache, ttl: Duration) -> Self {
+        Self { cache, ttl }
+    }
+
+    pub(crate) fn get(&self, key: &LLMMessageKey) -> Option<String> {
+        let ret = self.cache.get_string(LLM_MESSAGE_CACHE_REALM, key.as_cache_key()).ok();
+
+        if ret.is_some() {
+            info!("message for {}/{} was cached", key.provider, key.model);
+        }
+
+        ret
+    }
+
+    pub(crate) fn put(&self, key: &LLMMessageKey, value: &str) {
+        if let Err(e) = self.cache.add_string(
+            LLM_MESSAGE_CACHE_REALM,
+            key.as_cache_key(),
+            value,
+            &self.ttl,
+        ) {
+            error!("unable to write message cache entry ({e})");
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use crate::const_vars::CACHE_05_DAYS;
+
+    use super::*;
+
+    #[test]
+    fn test_message_cache() {
+        let td = tempfile::Builder::new().prefix("kvcache_").tempdir().unwrap();
+        let cache = KVCache::new(td.path().join("cache.sled")).unwrap();
+
+        let msg_cache = LLMMessageCache::new(cache, CACHE_05_DAYS);
+
+        let key = LLMMessageKey {
+            provider: "claude",
+            model: "claude-sonnet-4-6",
+            system: "",
+            content: "hello",
+        };
+
+        assert!(msg_cache.get(&key).is_none());
+        msg_cache.put(&key, "world");
+        assert_eq!(msg_cache.get(&key).as_deref(), Some("world"));
+
+        // any change in the request misses
+        let other_model = LLMMessageKey {
+            model: "other",
+            ..key
+        };
+        assert!(msg_cache.get(&other_model).is_none());
+
+        let other_system = LLMMessageKey {
+            system: "be brief",
+            ..key
+        };
+        assert!(msg_cache.get(&other_system).is_none());
+    }
+}
@@ -1,9 +1,13 @@
+pub mod attachment;
 pub mod chain;
 mod claude;
+pub mod message_cache;
 mod ollama;
 pub mod question;
 
 pub mod config {
-    pub use crate::llm::claude::claude_config::ClaudeConfig;
-    pub use crate::llm::ollama::ollama_config::ConfigOllama;
+    pub use crate::llm::claude::claude_config::{
+        CLAUDE_DEFAULT_MAX_TOKENS, CLAUDE_DEFAULT_URL, CLAUDE_DEFAULT_VERSION, ClaudeConfig,
+    };
+    pub use crate::llm::ollama::ollama_config::{ConfigOllama, OLLAMA_DEFAULT_ENDPOINT};
 }
@@ -5,8 +5,8 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::{
-    error::Result,
-    llm::{chain::LLMRole, ollama::ollama_config::ConfigOllama},
+    error::{Error, Result},
+    llm::{attachment::LLMAttachment, chain::LLMRole, ollama::ollama_config::ConfigOllama},
     rest::{rest_get, rest_post},
 };
 
@@ -14,6 +14,9 @@ use crate::{
 pub struct OllamaMessage {
     pub role: String,
     pub content: String,
+    /// base64 encoded images for multimodal models
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
 }
 
 impl OllamaMessage {
@@ -25,6 +28,7 @@ impl OllamaMessage {
         Self {
             role: role.into(),
             content: content.into(),
+            images: vec![],
         }
     }
 }
@@ -103,9 +107,48 @@ impl OllamaChat {
         self.messages.push(message);
     }
 
+    /// A user message with images, Ollama has no document support
+    pub fn add_user_content<C>(&mut self, content: C, attachments: &[LLMAttachment]) -> Result<()>
+    where
+        C: Into<String>,
+    {
+        if let Some(a) = attachments.iter().find(|a| !a.is_image()) {
+            return Err(Error::UnsupportedAttachment {
+                media_type: a.media_type.clone(),
+            });
+        }
+
+        let mut message = OllamaMessage::new(LLMRole::User, content);
+        message.images = attachments.iter().map(|a| a.data.clone()).collect();
+        self.messages.push(message);
+
+        Ok(())
+    }
+
     pub fn reset(&mut self) {
         self.messages = vec![];
     }
+
+    /// Keep the system prompts and the first `len` turns only
+    pub fn truncate_turns(&mut self, len: usize) {
+        let system: String = LLMRole::System.into();
+        let mut turns: usize = 0;
+
+        self.messages.retain(|m| {
+            if m.role == system {
+                return true;
+            }
+
+            turns = turns.saturating_add(1);
+            turns <= len
+        });
+    }
+
+    /// Everything but the system prompts
+    pub fn turns(&self) -> impl Iterator<Item = &OllamaMessage> {
+        let system: String = LLMRole::System.into();
+        self.messages.iter().filter(move |m| m.role != system)
+    }
 }
 
 // https://docs.ollama.com/api/generate
@@ -184,11 +227,21 @@ impl OllamaApi {
         Ok(resp.models)
     }
 
-    pub fn message<S>(&self, content: S) -> Result<OllamaChatResponse>
+    /// One-shot request to `model`, which may differ from the configured one
+    pub fn message<S>(
+        &self,
+        model: &str,
+        system: Option<&str>,
+        content: S,
+    ) -> Result<OllamaChatResponse>
     where
         S: Into<String>,
     {
-        let mut chat = OllamaChat::new(&self.config.model, self.config.thinking);
+        let mut chat = OllamaChat::new(model, self.config.thinking);
+
+        if let Some(system) = system {
+            chat.add_content(LLMRole::System, system);
+        }
 
         chat.add_content(LLMRole::User, content);
 
@@ -233,4 +286,52 @@ mod tests {
             api.set_model(&m.name).unwrap();
         }
     }
+
+    #[test]
+    fn test_turns_skip_system() {
+        let mut chat = OllamaChat::new("llama3", false);
+        chat.add_content(LLMRole::System, "be brief");
+        chat.add_content(LLMRole::User, "hello");
+        chat.add_content(LLMRole::Assistant, "hi");
+
+        let turns: Vec<&str> = chat.turns().map(|m| m.content.as_str()).collect();
+        assert_eq!(turns, ["hello", "hi"]);
+    }
+
+    #[test]
+    fn test_truncate_turns() {
+        let mut chat = OllamaChat::new("llama3", false);
+        chat.add_content(LLMRole::System, "be brief");
+        chat.add_content(LLMRole::User, "hello");
+        chat.add_content(LLMRole::Assistant, "hi");
+        chat.add_content(LLMRole::User, "again");
+
+        chat.truncate_turns(1);
+
+        let turns: Vec<&str> = chat.turns().map(|m| m.content.as_str()).collect();
+        assert_eq!(turns, ["hello"]);
+        assert_eq!(chat.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_images() {
+        let png = LLMAttachment::from_bytes(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let pdf = LLMAttachment::from_bytes(b"%PDF-1.7").unwrap();
+
+        let mut chat = OllamaChat::new("llava", false);
+        chat.add_user_content("what's this?", std::slice::from_ref(&png)).unwrap();
+
+        let json = serde_json::to_value(&chat).unwrap();
+        assert_eq!(json["messages"][0]["images"][0], png.data.as_str());
+
+        assert!(matches!(
+            chat.add_user_content("and this?", &[pdf]),
+            Err(Error::UnsupportedAttachment { .. })
+        ));
+
+        // no images, no field
+        chat.add_content(LLMRole::Assistant, "a screenshot");
+        let json = serde_json::to_value(&chat).unwrap();
+        assert!(json["messages"][1].get("images").is_none());
+    }
 }
@@ -5,8 +5,12 @@ use crate::{
     data::types::AdoData,
     error::{Error, Result},
     llm::{
-        chain::{LLMChainTrait, LLMRole, LLMUsage},
-        ollama::ollama_api::{OllamaApi, OllamaChat},
+        attachment::LLMAttachment,
+        chain::{LLMChainTrait, LLMRole, LLMTurn, LLMUsage},
+        ollama::{
+            ollama_api::{OllamaApi, OllamaChat},
+            ollama_config::ConfigOllama,
+        },
     },
 };
 
@@ -28,6 +32,12 @@ impl OllamaChain {
 
         Ok(Self { api, chat })
     }
+
+    /// Models served by `config.endpoint`, without loading any of them.
+    pub fn available_models(config: &ConfigOllama) -> Result<Vec<String>> {
+        let models = OllamaApi::new(config).models()?;
+        Ok(models.into_iter().map(|m| m.name).collect())
+    }
 }
 
 impl LLMChainTrait for OllamaChain {
@@ -42,14 +52,7 @@ impl LLMChainTrait for OllamaChain {
     }
 
     fn models(&self) -> Vec<String> {
-        let mut names = Vec::new();
-
-        if let Ok(ollama_models) = self.api.models() {
-            for model in ollama_models {
-                names.push(model.name);
-            }
-        }
-        names
+        OllamaChain::available_models(&self.api.config).unwrap_or_default()
     }
 
     fn add_content<S>(&mut self, role: LLMRole, content: S)
@@ -59,12 +62,20 @@ impl LLMChainTrait for OllamaChain {
         self.chat.add_content(role, content);
     }
 
-    fn message<S, M>(&self, content: S, _model: Option<M>) -> Result<String>
+    fn add_user_content<S>(&mut self, content: S, attachments: &[LLMAttachment]) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.chat.add_user_content(content, attachments)
+    }
+
+    fn message<S, M>(&self, system: Option<&str>, content: S, model: Option<M>) -> Result<String>
     where
         S: Into<String>,
         M: AsRef<str>,
     {
-        let resp = self.api.message(content)?;
+        let model = model.as_ref().map_or_else(|| self.model(), AsRef::as_ref);
+        let resp = self.api.message(model, system, content)?;
         Ok(resp.message.content)
     }
 
@@ -93,6 +104,34 @@ impl LLMChainTrait for OllamaChain {
         }
     }
 
+    /// Tokens aren't counted, there's nothing to carry over
+    fn restore_usage(&mut self, _usage: LLMUsage) {}
+
+    fn turns(&self) -> Vec<LLMTurn> {
+        let assistant: String = LLMRole::Assistant.into();
+
+        self.chat
+            .turns()
+            .map(|m| LLMTurn {
+                role: if m.role == assistant {
+                    LLMRole::Assistant
+                } else {
+                    LLMRole::User
+                },
+                content: m.content.clone(),
+                attachments: m
+                    .images
+                    .iter()
+                    .filter_map(|i| LLMAttachment::from_base64(i.as_str()).ok())
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn truncate_turns(&mut self, len: usize) {
+        self.chat.truncate_turns(len);
+    }
+
     fn dump_chain(&self) -> Result<AdoData> {
         Err(Error::NotImplemented)
     }
@@ -113,6 +152,6 @@ mod ollama_tests {
 
         let chain = OllamaChain::new(&config_file).unwrap();
 
-        chain.message("hello world", None::<&str>).unwrap();
+        chain.message(None, "hello world", None::<&str>).unwrap();
     }
 }
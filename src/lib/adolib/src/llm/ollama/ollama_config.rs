@@ -10,6 +10,23 @@ pub struct ConfigOllama {
     pub keep_alive: i32,
 }
 
+pub const OLLAMA_DEFAULT_ENDPOINT: &str = "http://localhost:11434";
+
+impl ConfigOllama {
+    pub fn new<E, M>(endpoint: E, model: M) -> Self
+    where
+        E: Into<String>,
+        M: Into<String>,
+    {
+        Self {
+            endpoint: endpoint.into(),
+            model: model.into(),
+            thinking: default_false(),
+            keep_alive: default_keep_alive(),
+        }
+    }
+}
+
 fn default_false() -> bool {
     false
 }
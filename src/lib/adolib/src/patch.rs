@@ -0,0 +1,518 @@
+//! Unified diffs as emitted in `diff` artifacts: parsing and applying them to
+//! the content of a file. Hunks are located by their context, starting at the
+//! line given in their header and moving away from it, so a diff made against
+//! a slightly different version of a file still applies.
+
+use std::path::{Path, PathBuf};
+
+use similar::TextDiff;
+
+use crate::error::{Error, Result};
+
+const DEV_NULL: &str = "/dev/null";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Hunk {
+    /// 1-based, as in the `@@ -old_start,... @@` header
+    pub old_start: usize,
+    /// as in the `@@ -...,old_lines @@` header, 0 for a pure insertion after
+    /// `old_start`
+    pub old_lines: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FilePatch {
+    /// `None` for `/dev/null`, i.e. a new file
+    pub old_path: Option<PathBuf>,
+    /// `None` for `/dev/null`, i.e. a deleted file
+    pub new_path: Option<PathBuf>,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkStatus {
+    /// applied at this 1-based line of the original
+    Applied {
+        line: usize,
+    },
+    Failed,
+}
+
+/// Outcome of [`FilePatch::apply`], `content` is only set when every hunk
+/// applied
+#[derive(Debug)]
+pub struct PatchOutcome {
+    pub content: Option<String>,
+    pub hunks: Vec<HunkStatus>,
+}
+
+impl PatchOutcome {
+    #[must_use]
+    pub fn applied(&self) -> usize {
+        self.hunks.iter().filter(|h| matches!(h, HunkStatus::Applied { .. })).count()
+    }
+
+    #[must_use]
+    pub fn failed(&self) -> usize {
+        self.hunks.len().saturating_sub(self.applied())
+    }
+}
+
+/// `a/src/main.rs` → `src/main.rs`, `None` for `/dev/null`
+fn header_path(rest: &str) -> Option<PathBuf> {
+    // "--- a/file\t2024-01-01 ..." carries a timestamp after a tab
+    let path = rest.split('\t').next().unwrap_or_default().trim();
+
+    if path == DEV_NULL {
+        return None;
+    }
+
+    let path = path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")).unwrap_or(path);
+
+    Some(PathBuf::from(path))
+}
+
+/// `@@ -12,5 +12,6 @@ fn main()` → (12, 5), `@@ -12 +12 @@` → (12, 1)
+fn hunk_range(line: &str, n: usize) -> Result<(usize, usize)> {
+    let invalid = || Error::InvalidPatch {
+        line: n,
+        reason: format!("invalid hunk header `{line}`"),
+    };
+
+    let range = line
+        .strip_prefix("@@ -")
+        .and_then(|r| r.split_whitespace().next())
+        .ok_or_else(invalid)?;
+
+    let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+
+    Ok((
+        start.parse().map_err(|_| invalid())?,
+        count.parse().map_err(|_| invalid())?,
+    ))
+}
+
+impl FilePatch {
+    /// The file the patch applies to, the old one for a deletion
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.new_path.as_deref().or(self.old_path.as_deref())
+    }
+
+    #[must_use]
+    pub fn is_new(&self) -> bool {
+        self.old_path.is_none() && self.new_path.is_some()
+    }
+
+    #[must_use]
+    pub fn is_delete(&self) -> bool {
+        self.new_path.is_none() && self.old_path.is_some()
+    }
+
+    /// Apply every hunk to `original`. Hunks that don't match are reported as
+    /// failed and no content is produced: a diff applies entirely or not at
+    /// all. The line endings of `original`, `\n` or `\r\n`, are kept.
+    #[must_use]
+    pub fn apply(&self, original: &str) -> PatchOutcome {
+        let mut lines: Vec<&str> = original.lines().collect();
+        let trailing_newline = original.is_empty() || original.ends_with('\n');
+        let eol = if original.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+
+        let mut statuses = Vec::new();
+        let mut result: Vec<String> = Vec::new();
+        // next line of `lines` to copy to `result`
+        let mut cursor: usize = 0;
+        // how far the hunks were found from their header so far
+        let mut drift: isize = 0;
+
+        for hunk in &self.hunks {
+            let old: Vec<&str> = hunk
+                .lines
+                .iter()
+                .filter_map(|l| match l {
+                    HunkLine::Context(s) | HunkLine::Remove(s) => Some(s.as_str()),
+                    HunkLine::Add(_) => None,
+                })
+                .collect();
+
+            // 0-based line of the header: `@@ -5,0 ...` inserts after line 5,
+            // `@@ -0,0 ...` at the top
+            let start = if hunk.old_lines == 0 {
+                hunk.old_start
+            } else {
+                hunk.old_start.saturating_sub(1)
+            };
+            let expected = start.saturating_add_signed(drift);
+
+            let Some(at) = find(&lines, &old, expected, cursor) else {
+                statuses.push(HunkStatus::Failed);
+                continue;
+            };
+
+            result
+                .extend(lines.get(cursor..at).unwrap_or_default().iter().map(ToString::to_string));
+
+            for l in &hunk.lines {
+                match l {
+                    HunkLine::Context(s) | HunkLine::Add(s) => result.push(s.clone()),
+                    HunkLine::Remove(_) => {}
+                }
+            }
+
+            cursor = at.saturating_add(old.len());
+            drift = isize::try_from(at).unwrap_or_default().saturating_sub_unsigned(start);
+            statuses.push(HunkStatus::Applied {
+                line: at.saturating_add(1),
+            });
+        }
+
+        if statuses.contains(&HunkStatus::Failed) {
+            return PatchOutcome {
+                content: None,
+                hunks: statuses,
+            };
+        }
+
+        result.extend(lines.split_off(cursor.min(lines.len())).iter().map(ToString::to_string));
+
+        let mut content = result.join(eol);
+
+        if trailing_newline && !content.is_empty() {
+            content.push_str(eol);
+        }
+
+        PatchOutcome {
+            content: Some(content),
+            hunks: statuses,
+        }
+    }
+}
+
+/// Where `needle` starts in `haystack`, at or after `from`, closest to
+/// `expected`
+fn find(haystack: &[&str], needle: &[&str], expected: usize, from: usize) -> Option<usize> {
+    let last = haystack.len().checked_sub(needle.len())?;
+
+    if from > last {
+        return None;
+    }
+
+    let matches_at = |at: usize| haystack.get(at..at.saturating_add(needle.len())) == Some(needle);
+
+    let expected = expected.clamp(from, last);
+
+    for distance in 0..=last.saturating_sub(from) {
+        let after = expected.saturating_add(distance);
+
+        if after <= last && matches_at(after) {
+            return Some(after);
+        }
+
+        if let Some(before) = expected.checked_sub(distance)
+            && before >= from
+            && matches_at(before)
+        {
+            return Some(before);
+        }
+    }
+
+    None
+}
+
+/// Parse the unified diff `text`, possibly covering several files. A diff
+/// without `---`/`+++` headers is attributed to `default_path`.
+pub fn parse(text: &str, default_path: Option<&Path>) -> Result<Vec<FilePatch>> {
+    let mut patches: Vec<FilePatch> = Vec::new();
+    let mut current: Option<FilePatch> = None;
+    let mut hunk: Option<Hunk> = None;
+
+    let flush_hunk = |current: &mut Option<FilePatch>, hunk: &mut Option<Hunk>| {
+        if let (Some(patch), Some(mut h)) = (current.as_mut(), hunk.take()) {
+            // blank lines separating the hunk from what follows
+            while h.lines.last() == Some(&HunkLine::Context(String::new())) {
+                h.lines.pop();
+            }
+
+            patch.hunks.push(h);
+        }
+    };
+
+    let mut lines = text.lines().enumerate().peekable();
+
+    while let Some((i, line)) = lines.next() {
+        let n = i.saturating_add(1);
+
+        if let Some(rest) = line.strip_prefix("--- ")
+            && let Some((_, next)) = lines.peek()
+            && let Some(new) = next.strip_prefix("+++ ")
+        {
+            flush_hunk(&mut current, &mut hunk);
+            patches.extend(current.take());
+
+            current = Some(FilePatch {
+                old_path: header_path(rest),
+                new_path: header_path(new),
+                hunks: Vec::new(),
+            });
+
+            lines.next();
+            continue;
+        }
+
+        if line.starts_with("@@") {
+            flush_hunk(&mut current, &mut hunk);
+
+            if current.is_none() {
+                let Some(path) = default_path else {
+                    return Err(Error::InvalidPatch {
+                        line: n,
+                        reason: "hunk without a file header nor a path".to_string(),
+                    });
+                };
+
+                current = Some(FilePatch {
+                    old_path: Some(path.to_path_buf()),
+                    new_path: Some(path.to_path_buf()),
+                    hunks: Vec::new(),
+                });
+            }
+
+            let (old_start, old_lines) = hunk_range(line, n)?;
+
+            hunk = Some(Hunk {
+                old_start,
+                old_lines,
+                lines: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(h) = hunk.as_mut() else {
+            // `diff --git`, `index ...` and other preambles
+            continue;
+        };
+
+        if let Some(s) = line.strip_prefix('+') {
+            h.lines.push(HunkLine::Add(s.to_string()));
+        } else if let Some(s) = line.strip_prefix('-') {
+            h.lines.push(HunkLine::Remove(s.to_string()));
+        } else if let Some(s) = line.strip_prefix(' ') {
+            h.lines.push(HunkLine::Context(s.to_string()));
+        } else if line.is_empty() {
+            // editors and models strip the space of empty context lines
+            h.lines.push(HunkLine::Context(String::new()));
+        } else if line.starts_with('\\') {
+            // "\ No newline at end of file"
+        } else {
+            flush_hunk(&mut current, &mut hunk);
+        }
+    }
+
+    flush_hunk(&mut current, &mut hunk);
+    patches.extend(current);
+
+    patches.retain(|p| !p.hunks.is_empty());
+
+    if patches.is_empty() {
+        return Err(Error::InvalidPatch {
+            line: 0,
+            reason: "no hunk found".to_string(),
+        });
+    }
+
+    Ok(patches)
+}
+
+/// Unified diff turning `old` into `new`, empty when they're the same
+#[must_use]
+pub fn unified_diff(path: &Path, old: &str, new: &str) -> String {
+    // git style prefixes, only for relative paths
+    let (a, b) = if path.is_absolute() {
+        (path.display().to_string(), path.display().to_string())
+    } else {
+        (
+            format!("a/{}", path.display()),
+            format!("b/{}", path.display()),
+        )
+    };
+
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&a, &b)
+        .to_string()
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    const ORIGINAL: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+
+    #[test]
+    fn test_parse() {
+        let diff = "diff --git a/src/a.rs b/src/a.rs\n\
+                    index 1234..5678 100644\n\
+                    --- a/src/a.rs\n\
+                    +++ b/src/a.rs\n\
+                    @@ -1,2 +1,2 @@\n\
+                    -one\n\
+                    +ONE\n \
+                    two\n\
+                    --- /dev/null\n\
+                    +++ b/new.txt\n\
+                    @@ -0,0 +1 @@\n\
+                    +hello\n";
+
+        let patches = parse(diff, None).unwrap();
+        assert_eq!(patches.len(), 2);
+
+        assert_eq!(patches[0].path(), Some(Path::new("src/a.rs")));
+        assert_eq!(patches[0].hunks[0].old_start, 1);
+        assert_eq!(patches[0].hunks[0].lines.len(), 3);
+
+        assert!(patches[1].is_new());
+        assert_eq!(patches[1].path(), Some(Path::new("new.txt")));
+
+        // bare hunks need a path
+        assert!(parse("@@ -1 +1 @@\n-a\n+b\n", None).is_err());
+        let patches = parse("@@ -1 +1 @@\n-a\n+b\n", Some(Path::new("x"))).unwrap();
+        assert_eq!(patches[0].path(), Some(Path::new("x")));
+
+        assert!(parse("not a diff", Some(Path::new("x"))).is_err());
+        assert!(parse("@@ -x +1 @@\n", Some(Path::new("x"))).is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let diff = "--- a/f\n+++ b/f\n\
+                    @@ -2,3 +2,3 @@\n two\n-three\n+THREE\n four\n\
+                    @@ -6,2 +6,3 @@\n six\n+six and a half\n seven\n";
+
+        let patch = parse(diff, None).unwrap().remove(0);
+        let outcome = patch.apply(ORIGINAL);
+
+        assert_eq!(outcome.applied(), 2);
+        assert_eq!(
+            outcome.content.as_deref(),
+            Some("one\ntwo\nTHREE\nfour\nfive\nsix\nsix and a half\nseven\n")
+        );
+    }
+
+    #[test]
+    fn test_apply_offset() {
+        // written against a version without the first two lines
+        let diff = "@@ -1,2 +1,2 @@\n three\n-four\n+FOUR\n";
+
+        let patch = parse(diff, Some(Path::new("f"))).unwrap().remove(0);
+        let outcome = patch.apply(ORIGINAL);
+
+        assert_eq!(outcome.hunks, [HunkStatus::Applied { line: 3 }]);
+        assert_eq!(
+            outcome.content.as_deref(),
+            Some("one\ntwo\nthree\nFOUR\nfive\nsix\nseven\n")
+        );
+    }
+
+    #[test]
+    fn test_apply_failure() {
+        let diff = "@@ -1,2 +1,2 @@\n one\n-two\n+TWO\n\
+                    @@ -4,1 +4,1 @@\n-nope\n+NOPE\n";
+
+        let patch = parse(diff, Some(Path::new("f"))).unwrap().remove(0);
+        let outcome = patch.apply(ORIGINAL);
+
+        assert_eq!(
+            outcome.hunks,
+            [HunkStatus::Applied { line: 1 }, HunkStatus::Failed]
+        );
+        assert_eq!(outcome.failed(), 1);
+        assert!(outcome.content.is_none());
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let new = ORIGINAL.replace("four", "FOUR");
+
+        let diff = unified_diff(Path::new("f"), ORIGINAL, &new);
+        assert!(diff.starts_with("--- a/f\n+++ b/f\n@@"));
+        assert!(diff.contains("-four\n+FOUR\n"));
+
+        // what we write, we can apply
+        let patch = parse(&diff, None).unwrap().remove(0);
+        assert_eq!(patch.path(), Some(Path::new("f")));
+        assert_eq!(patch.apply(ORIGINAL).content.as_deref(), Some(new.as_str()));
+
+        assert!(unified_diff(Path::new("f"), ORIGINAL, ORIGINAL).is_empty());
+
+        let diff = unified_diff(Path::new("/tmp/f"), ORIGINAL, &new);
+        assert!(diff.starts_with("--- /tmp/f\n+++ /tmp/f\n"));
+        let patch = parse(&diff, None).unwrap().remove(0);
+        assert_eq!(patch.path(), Some(Path::new("/tmp/f")));
+    }
+
+    #[test]
+    fn test_apply_new_file() {
+        let patch = parse("--- /dev/null\n+++ b/n\n@@ -0,0 +1,2 @@\n+a\n+b\n", None)
+            .unwrap()
+            .remove(0);
+
+        assert_eq!(patch.apply("").content.as_deref(), Some("a\nb\n"));
+    }
+
+    #[test]
+    fn test_apply_crlf() {
+        let original = ORIGINAL.replace('\n', "\r\n");
+        let diff = "@@ -2,3 +2,3 @@\n two\n-three\n+THREE\n four\n";
+
+        let patch = parse(diff, Some(Path::new("f"))).unwrap().remove(0);
+
+        assert_eq!(
+            patch.apply(&original).content,
+            Some(ORIGINAL.replace("three", "THREE").replace('\n', "\r\n"))
+        );
+    }
+
+    #[test]
+    fn test_apply_insertion() {
+        let diff = "@@ -2,0 +3,2 @@\n+two and a half\n+two and three quarters\n";
+
+        let patch = parse(diff, Some(Path::new("f"))).unwrap().remove(0);
+        assert_eq!(patch.hunks[0].old_lines, 0);
+
+        let outcome = patch.apply(ORIGINAL);
+
+        assert_eq!(outcome.hunks, [HunkStatus::Applied { line: 3 }]);
+        assert_eq!(
+            outcome.content.as_deref(),
+            Some(
+                "one\ntwo\ntwo and a half\ntwo and three quarters\nthree\nfour\nfive\nsix\nseven\n"
+            )
+        );
+
+        // at the end of the file
+        let patch = parse("@@ -7,0 +8 @@\n+eight\n", Some(Path::new("f"))).unwrap().remove(0);
+        assert_eq!(
+            patch.apply(ORIGINAL).content,
+            Some(format!("{ORIGINAL}eight\n"))
+        );
+    }
+}
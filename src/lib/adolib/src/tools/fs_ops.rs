@@ -0,0 +1,384 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use glob::{MatchOptions, Pattern};
+
+use crate::{
+    error::{Error, Result},
+    tools::{
+        MAX_GLOB_RESULTS, MAX_READ_FILE_SIZE, MAX_READ_LINES,
+        gitignore::{self, IgnoreFilter},
+        is_binary, truncate_line, unsupported, usage,
+    },
+};
+
+const GLOB_HELP: &str = "glob PATTERN [DIR]";
+const READ_HELP: &str = "read PATH [START[-END]]";
+
+const GLOB_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    // hidden files are left out by the walk already
+    require_literal_leading_dot: false,
+};
+
+fn is_wildcard(component: &Component) -> bool {
+    component.as_os_str().to_string_lossy().contains(['*', '?', '['])
+}
+
+fn existing(path: &Path) -> Result<()> {
+    if path.exists() {
+        Ok(())
+    } else {
+        Err(Error::FileNotFoundError {
+            file_path: path.to_path_buf(),
+        })
+    }
+}
+
+/// `glob PATTERN [DIR]`: the paths matching `PATTERN`, relative to `DIR` if
+/// given
+pub fn glob(args: &[String], dir: &Path) -> Result<String> {
+    let pattern = match args {
+        [pattern] => PathBuf::from(pattern),
+        [pattern, base] => Path::new(base).join(pattern),
+        _ => return Err(usage(GLOB_HELP)),
+    };
+
+    let matcher = Pattern::new(&pattern.to_string_lossy())?;
+
+    // only what's below the part without wildcards needs walking
+    let root: PathBuf = pattern.components().take_while(|c| !is_wildcard(c)).collect();
+
+    let mut found: Vec<String> = Vec::new();
+
+    if root == pattern {
+        if dir.join(&pattern).exists() {
+            found.push(pattern.display().to_string());
+        }
+    } else {
+        for entry in gitignore::walk(&dir.join(&root), false) {
+            // matched as shown, relative when the pattern is
+            let path = if pattern.is_relative() {
+                entry.path().strip_prefix(dir).unwrap_or_else(|_| entry.path())
+            } else {
+                entry.path()
+            };
+
+            if matcher.matches_path_with(path, GLOB_OPTIONS) {
+                found.push(path.display().to_string());
+            }
+        }
+    }
+
+    if found.is_empty() {
+        return Ok(format!("No path matches {}", pattern.display()));
+    }
+
+    let total = found.len();
+    found.truncate(MAX_GLOB_RESULTS);
+
+    if total > MAX_GLOB_RESULTS {
+        found.push(format!(
+            "... {} more paths, narrow the pattern",
+            total.saturating_sub(MAX_GLOB_RESULTS)
+        ));
+    }
+
+    Ok(found.join("\n"))
+}
+
+fn ls_line(name: &str, path: &Path, long: bool) -> String {
+    let is_dir = path.is_dir();
+    let name = if is_dir {
+        format!("{name}/")
+    } else {
+        name.to_string()
+    };
+
+    if long {
+        let size = fs::metadata(path).map(|m| m.len()).unwrap_or_default();
+        let kind = if is_dir { 'd' } else { '-' };
+        format!("{kind} {size:>10} {name}")
+    } else {
+        name
+    }
+}
+
+/// `ls [-a] [-l] [PATH...]`: the entries of directories, hidden and ignored
+/// ones only with `-a`
+pub fn ls(args: &[String], dir: &Path) -> Result<String> {
+    let mut all = false;
+    let mut long = false;
+    let mut paths = Vec::new();
+
+    for arg in args {
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'a' | 'A' => all = true,
+                        'l' => long = true,
+                        _ => return Err(unsupported(arg)),
+                    }
+                }
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if paths.is_empty() {
+        paths.push(PathBuf::from("."));
+    }
+
+    let mut blocks = Vec::new();
+
+    for path in &paths {
+        let full = dir.join(path);
+        existing(&full)?;
+
+        if !full.is_dir() {
+            blocks.push(ls_line(&path.display().to_string(), &full, long));
+            continue;
+        }
+
+        let mut filter = IgnoreFilter::new(&full, false);
+        let mut entries = Vec::new();
+
+        for entry in fs::read_dir(&full)? {
+            let entry = entry?;
+            let entry_path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            // with -a, everything but .git is shown
+            if name == ".git" || (!all && filter.is_ignored(&entry_path, entry_path.is_dir())) {
+                continue;
+            }
+
+            entries.push((name, entry_path));
+        }
+
+        entries.sort();
+
+        let mut lines: Vec<String> =
+            entries.iter().map(|(name, p)| ls_line(name, p, long)).collect();
+
+        if paths.len() > 1 {
+            lines.insert(0, format!("{}:", path.display()));
+        }
+
+        blocks.push(lines.join("\n"));
+    }
+
+    Ok(blocks.join("\n\n"))
+}
+
+/// 1-based inclusive line range, `START`, `START-` or `START-END`
+fn parse_range(range: &str) -> Result<(usize, Option<usize>)> {
+    let parse =
+        |s: &str| s.parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(|| usage(READ_HELP));
+
+    match range.split_once('-') {
+        None => Ok((parse(range)?, None)),
+        Some((start, "")) => Ok((parse(start)?, None)),
+        Some((start, end)) => {
+            let (start, end) = (parse(start)?, parse(end)?);
+
+            if end < start {
+                return Err(usage(READ_HELP));
+            }
+
+            Ok((start, Some(end)))
+        }
+    }
+}
+
+/// `read PATH [START[-END]]`: the lines of a file, numbered like `cat -n`
+pub fn read(args: &[String], dir: &Path) -> Result<String> {
+    let (path, (start, end)) = match args {
+        [path] => (Path::new(path), (1, None)),
+        [path, range] => (Path::new(path), parse_range(range)?),
+        _ => return Err(usage(READ_HELP)),
+    };
+
+    let full = dir.join(path);
+    existing(&full)?;
+
+    let size = fs::metadata(&full)?.len();
+
+    if size > MAX_READ_FILE_SIZE {
+        return Err(Error::FileTooLarge {
+            size,
+            limit: MAX_READ_FILE_SIZE,
+        });
+    }
+
+    let bytes = fs::read(&full)?;
+
+    if is_binary(&bytes) {
+        return Ok(format!("{} is a binary file", path.display()));
+    }
+
+    let text = String::from_utf8_lossy(&bytes);
+    let total = text.lines().count();
+
+    if total == 0 {
+        return Ok(format!("{} is empty", path.display()));
+    }
+
+    if start > total {
+        return Ok(format!("{} has {total} lines", path.display()));
+    }
+
+    let end = end.unwrap_or(total).min(total);
+    let last = end.min(start.saturating_add(MAX_READ_LINES).saturating_sub(1));
+
+    let mut lines: Vec<String> = text
+        .lines()
+        .enumerate()
+        .skip(start.saturating_sub(1))
+        .take(last.saturating_add(1).saturating_sub(start))
+        .map(|(i, line)| format!("{:>6}\t{}", i.saturating_add(1), truncate_line(line)))
+        .collect();
+
+    if last < end {
+        lines.push(format!(
+            "... lines {start}-{last} of {total}, read {} {}- for more",
+            path.display(),
+            last.saturating_add(1)
+        ));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]\n").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("src/nested/lib.rs"), "").unwrap();
+        fs::write(root.join("target/out.rs"), "").unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn test_glob() {
+        let dir = tree();
+        let cwd = Path::new("/");
+        let root = dir.path().display().to_string();
+
+        let out = glob(&args(&format!("**/*.rs {root}")), cwd).unwrap();
+        assert_eq!(out, format!("{root}/src/main.rs\n{root}/src/nested/lib.rs"));
+
+        let out = glob(&args(&format!("{root}/src/*.rs")), cwd).unwrap();
+        assert_eq!(out, format!("{root}/src/main.rs"));
+
+        let out = glob(&args(&format!("Cargo.toml {root}")), cwd).unwrap();
+        assert_eq!(out, format!("{root}/Cargo.toml"));
+
+        let out = glob(&args(&format!("*.py {root}")), cwd).unwrap();
+        assert!(out.starts_with("No path matches"));
+
+        // relative to the working directory
+        assert_eq!(
+            glob(&args("src/**/*.rs"), dir.path()).unwrap(),
+            "src/main.rs\nsrc/nested/lib.rs"
+        );
+        assert_eq!(glob(&args("*.toml"), dir.path()).unwrap(), "Cargo.toml");
+
+        assert!(matches!(glob(&[], cwd), Err(Error::Usage { .. })));
+    }
+
+    #[test]
+    fn test_ls() {
+        let dir = tree();
+        let cwd = Path::new("/");
+        let root = dir.path().display().to_string();
+
+        assert_eq!(ls(&args(&root), cwd).unwrap(), "Cargo.toml\nsrc/");
+        assert_eq!(ls(&[], dir.path()).unwrap(), "Cargo.toml\nsrc/");
+        assert_eq!(
+            ls(&args(&format!("-a {root}")), cwd).unwrap(),
+            ".gitignore\nCargo.toml\nsrc/\ntarget/"
+        );
+        assert_eq!(ls(&args("-l src"), dir.path()).unwrap().lines().count(), 2);
+        assert_eq!(
+            ls(&args("src target"), dir.path()).unwrap(),
+            "src:\nmain.rs\nnested/\n\ntarget:\nout.rs"
+        );
+
+        assert!(matches!(
+            ls(&args("missing"), dir.path()),
+            Err(Error::FileNotFoundError { .. })
+        ));
+        assert!(matches!(
+            ls(&args("-lh"), cwd),
+            Err(Error::UnsupportedOption { .. })
+        ));
+        assert!(matches!(
+            ls(&args("--all"), cwd),
+            Err(Error::UnsupportedOption { .. })
+        ));
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = Path::new("/");
+        let path = dir.path().join("lines.txt");
+        let content: Vec<String> = (1..=2500).map(|i| format!("line {i}")).collect();
+        fs::write(&path, content.join("\n")).unwrap();
+        let path = path.display().to_string();
+
+        let out = read(&args(&format!("{path} 2-3")), cwd).unwrap();
+        assert_eq!(out, "     2\tline 2\n     3\tline 3");
+
+        let out = read(&args(&format!("{path} 2499")), cwd).unwrap();
+        assert_eq!(out, "  2499\tline 2499\n  2500\tline 2500");
+
+        let out = read(&args(&path), cwd).unwrap();
+        assert_eq!(out.lines().count(), MAX_READ_LINES + 1);
+        assert!(out.ends_with(&format!("lines 1-2000 of 2500, read {path} 2001- for more")));
+
+        let out = read(&args("lines.txt 1-1"), dir.path()).unwrap();
+        assert_eq!(out, "     1\tline 1");
+
+        assert!(read(&args(&format!("{path} 3000")), cwd).unwrap().ends_with("has 2500 lines"));
+        assert!(matches!(
+            read(&args(&format!("{path} 5-2")), cwd),
+            Err(Error::Usage { .. })
+        ));
+        assert!(matches!(
+            read(&args(&format!("{path} x")), cwd),
+            Err(Error::Usage { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("10").unwrap(), (10, None));
+        assert_eq!(parse_range("10-").unwrap(), (10, None));
+        assert_eq!(parse_range("10-20").unwrap(), (10, Some(20)));
+        assert!(parse_range("0").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Just enough of `.gitignore` for the file tools to skip what git skips:
+//! comments, `!` negation, directory-only `dir/` patterns, anchored `/build`
+//! or `src/gen` patterns and `**`. Each directory's `.gitignore` applies to
+//! what's below it, deeper files overriding shallower ones.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glob::{MatchOptions, Pattern};
+use walkdir::WalkDir;
+
+const GITIGNORE: &str = ".gitignore";
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+struct Rule {
+    pattern: Pattern,
+    negated: bool,
+    dir_only: bool,
+    /// matched against the path relative to the `.gitignore`, otherwise
+    /// against the file name
+    anchored: bool,
+}
+
+pub struct Gitignore {
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl Gitignore {
+    #[must_use]
+    pub fn parse(base: &Path, text: &str) -> Self {
+        let mut rules = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim_end();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+
+            let anchored = line.contains('/');
+            let line = line.strip_prefix('/').unwrap_or(line);
+
+            // invalid patterns are skipped, like git does
+            if let Ok(pattern) = Pattern::new(line) {
+                rules.push(Rule {
+                    pattern,
+                    negated,
+                    dir_only,
+                    anchored,
+                });
+            }
+        }
+
+        Self {
+            base: base.to_path_buf(),
+            rules,
+        }
+    }
+
+    /// The `.gitignore` of `dir`, if any
+    #[must_use]
+    pub fn from_dir(dir: &Path) -> Option<Self> {
+        let text = fs::read_to_string(dir.join(GITIGNORE)).ok()?;
+        Some(Gitignore::parse(dir, &text))
+    }
+
+    /// `Some(true)` when ignored, `Some(false)` when explicitly re-included,
+    /// `None` when no rule matches
+    #[must_use]
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        let name = relative.file_name()?;
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|r| {
+                if r.dir_only && !is_dir {
+                    return false;
+                }
+
+                if r.anchored {
+                    r.pattern.matches_path_with(relative, MATCH_OPTIONS)
+                } else {
+                    r.pattern.matches_with(&name.to_string_lossy(), MATCH_OPTIONS)
+                }
+            })
+            .map(|r| !r.negated)
+    }
+}
+
+/// Decides what the file tools skip below a root: `.git`, hidden files unless
+/// asked for, and whatever the `.gitignore` files say, including those of the
+/// repository above the root
+pub struct IgnoreFilter {
+    /// the root as given, and as an absolute path the rules are matched
+    /// against
+    root: PathBuf,
+    absolute_root: PathBuf,
+    /// the git root, or the walk root outside of a repository
+    top: PathBuf,
+    hidden: bool,
+    gitignores: HashMap<PathBuf, Option<Gitignore>>,
+}
+
+impl IgnoreFilter {
+    #[must_use]
+    pub fn new(root: &Path, hidden: bool) -> Self {
+        let absolute_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let top = absolute_root
+            .ancestors()
+            .find(|d| d.join(".git").exists())
+            .unwrap_or(&absolute_root)
+            .to_path_buf();
+
+        Self {
+            root: root.to_path_buf(),
+            absolute_root,
+            top,
+            hidden,
+            gitignores: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy()) else {
+            return false;
+        };
+
+        if name == ".git" || (!self.hidden && name.starts_with('.')) {
+            return true;
+        }
+
+        let path = match path.strip_prefix(&self.root) {
+            Ok(relative) => self.absolute_root.join(relative),
+            Err(_) => path.to_path_buf(),
+        };
+
+        let Some(parent) = path.parent() else {
+            return false;
+        };
+
+        let Ok(below_top) = parent.strip_prefix(&self.top) else {
+            return false;
+        };
+
+        let mut ignored = false;
+        let mut dir = self.top.clone();
+
+        // from the top down, deeper rules have the last word
+        for component in std::iter::once(None).chain(below_top.components().map(Some)) {
+            if let Some(c) = component {
+                dir.push(c);
+            }
+
+            let gitignore =
+                self.gitignores.entry(dir.clone()).or_insert_with(|| Gitignore::from_dir(&dir));
+
+            if let Some(m) = gitignore.as_ref().and_then(|g| g.matched(&path, is_dir)) {
+                ignored = m;
+            }
+        }
+
+        ignored
+    }
+}
+
+/// Every file and directory below `root` that isn't ignored, sorted by name,
+/// `root` excluded
+pub fn walk(root: &Path, hidden: bool) -> impl Iterator<Item = walkdir::DirEntry> {
+    let mut filter = IgnoreFilter::new(root, hidden);
+
+    WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(move |e| {
+            e.depth() == 0 || !filter.is_ignored(e.path(), e.file_type().is_dir())
+        })
+        .flatten()
+        .filter(|e| e.depth() > 0)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_matched() {
+        let base = Path::new("/repo");
+        let g = Gitignore::parse(
+            base,
+            "# build output\n\
+             target/\n\
+             *.log\n\
+             !keep.log\n\
+             /root.txt\n\
+             docs/**/*.html\n",
+        );
+
+        assert_eq!(g.matched(Path::new("/repo/target"), true), Some(true));
+        // directory only
+        assert_eq!(g.matched(Path::new("/repo/target"), false), None);
+        assert_eq!(g.matched(Path::new("/repo/a/b/x.log"), false), Some(true));
+        assert_eq!(g.matched(Path::new("/repo/keep.log"), false), Some(false));
+        assert_eq!(g.matched(Path::new("/repo/root.txt"), false), Some(true));
+        // anchored to the .gitignore directory
+        assert_eq!(g.matched(Path::new("/repo/sub/root.txt"), false), None);
+        assert_eq!(
+            g.matched(Path::new("/repo/docs/a/b/index.html"), false),
+            Some(true)
+        );
+        assert_eq!(g.matched(Path::new("/repo/src/index.html"), false), None);
+        assert_eq!(g.matched(Path::new("/elsewhere/x.log"), false), None);
+    }
+
+    #[test]
+    fn test_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.tmp\n").unwrap();
+        fs::write(root.join("src/.gitignore"), "gen/\n!keep.tmp\n").unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("src/a.tmp"), "").unwrap();
+        fs::write(root.join("src/keep.tmp"), "").unwrap();
+        fs::write(root.join("src/gen/x.rs"), "").unwrap();
+        fs::write(root.join("target/debug/ado"), "").unwrap();
+        fs::write(root.join(".env"), "").unwrap();
+
+        let found = |root: &Path, hidden: bool| -> Vec<String> {
+            walk(root, hidden)
+                .map(|e| e.path().strip_prefix(dir.path()).unwrap().display().to_string())
+                .collect()
+        };
+
+        assert_eq!(found(root, false), ["src", "src/keep.tmp", "src/main.rs"]);
+        assert_eq!(
+            found(root, true),
+            [".env", ".gitignore", "src", "src/.gitignore", "src/keep.tmp", "src/main.rs"]
+        );
+
+        // the repository's .gitignore applies below its root too
+        fs::write(root.join("src/b.tmp"), "").unwrap();
+        assert_eq!(
+            found(&root.join("src"), false),
+            ["src/keep.tmp", "src/main.rs"]
+        );
+    }
+}
@@ -0,0 +1,496 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glob::Pattern;
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    error::Result,
+    tools::{
+        MAX_GREP_FILE_SIZE, MAX_GREP_LINES, gitignore, is_binary, shown, truncate_line,
+        unsupported, usage,
+    },
+};
+
+const GREP_HELP: &str = "grep [-E] [-i] [-l] [-C N|-A N|-B N] [--glob GLOB] PATTERN [PATH...]";
+
+/// Single letter flags of `grep -rn` and friends that are always on here
+const IMPLIED_FLAGS: &[char] = &['r', 'R', 'n', 'I', 'H'];
+
+/// Operators of an extended regex that a basic one only knows escaped, and
+/// takes literally otherwise
+const BASIC_ESCAPED: &[char] = &['|', '(', ')', '{', '}', '+', '?'];
+
+/// Basic regex `pattern` (GNU `grep` without `-E`) in `regex` syntax
+fn from_basic(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(e) if BASIC_ESCAPED.contains(&e) => out.push(e),
+                Some(e) => {
+                    out.push('\\');
+                    out.push(e);
+                }
+                None => out.push_str("\\\\"),
+            },
+            // bracket expressions are the same but for the characters
+            // `regex` takes specially in them
+            '[' => {
+                out.push('[');
+
+                if chars.next_if_eq(&'^').is_some() {
+                    out.push('^');
+                }
+
+                if chars.next_if_eq(&']').is_some() {
+                    out.push_str("\\]");
+                }
+
+                while let Some(c) = chars.next() {
+                    match c {
+                        ']' => {
+                            out.push(']');
+                            break;
+                        }
+                        // `[:alpha:]`
+                        '[' if chars.peek() == Some(&':') => {
+                            out.push('[');
+
+                            for c in chars.by_ref() {
+                                out.push(c);
+
+                                if c == ']' {
+                                    break;
+                                }
+                            }
+                        }
+                        '\\' | '[' | '&' | '~' => {
+                            out.push('\\');
+                            out.push(c);
+                        }
+                        c => out.push(c),
+                    }
+                }
+            }
+            c if BASIC_ESCAPED.contains(&c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+struct Options {
+    regex: Regex,
+    files_only: bool,
+    before: usize,
+    after: usize,
+    /// `--glob`, against the file name or, with a `/`, the path below the
+    /// searched directory
+    glob: Option<Pattern>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<(Self, Vec<PathBuf>)> {
+        let mut extended = false;
+        let mut ignore_case = false;
+        let mut files_only = false;
+        let mut before = 0;
+        let mut after = 0;
+        let mut glob = None;
+        let mut positional = Vec::new();
+
+        let mut args = args.iter();
+
+        let number = |value: Option<&String>| -> Result<usize> {
+            value.and_then(|v| v.parse().ok()).ok_or_else(|| usage(GREP_HELP))
+        };
+
+        // options before or after the pattern, as GNU grep takes them
+        while let Some(arg) = args.next() {
+            if arg == "-" || !arg.starts_with('-') {
+                positional.push(arg.as_str());
+                continue;
+            }
+
+            if arg == "--" {
+                positional.extend(args.by_ref().map(String::as_str));
+                break;
+            }
+
+            if let Some(value) = arg.strip_prefix("--glob") {
+                let value = match value.strip_prefix('=') {
+                    Some(v) => v.to_string(),
+                    None if value.is_empty() => {
+                        args.next().cloned().ok_or_else(|| usage(GREP_HELP))?
+                    }
+                    None => return Err(usage(GREP_HELP)),
+                };
+
+                glob = Some(Pattern::new(&value)?);
+                continue;
+            }
+
+            // `--include`, `--exclude-dir`...
+            if arg.starts_with("--") {
+                return Err(unsupported(arg));
+            }
+
+            for context in ["-A", "-B", "-C"] {
+                if let Some(value) = arg.strip_prefix(context) {
+                    let n = if value.is_empty() {
+                        number(args.next())?
+                    } else {
+                        number(Some(&value.to_string()))?
+                    };
+
+                    if context != "-A" {
+                        before = n;
+                    }
+
+                    if context != "-B" {
+                        after = n;
+                    }
+                }
+            }
+
+            if arg.starts_with("-A") || arg.starts_with("-B") || arg.starts_with("-C") {
+                continue;
+            }
+
+            for flag in arg.chars().skip(1) {
+                match flag {
+                    'E' => extended = true,
+                    'i' => ignore_case = true,
+                    'l' => files_only = true,
+                    f if IMPLIED_FLAGS.contains(&f) => {}
+                    _ => return Err(unsupported(arg)),
+                }
+            }
+        }
+
+        let (pattern, paths) = positional.split_first().ok_or_else(|| usage(GREP_HELP))?;
+
+        let pattern = if extended {
+            (*pattern).to_string()
+        } else {
+            from_basic(pattern)
+        };
+
+        let regex = RegexBuilder::new(&pattern).case_insensitive(ignore_case).build()?;
+
+        let mut paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+
+        if paths.is_empty() {
+            paths.push(PathBuf::from("."));
+        }
+
+        Ok((
+            Self {
+                regex,
+                files_only,
+                before,
+                after,
+                glob,
+            },
+            paths,
+        ))
+    }
+
+    fn has_context(&self) -> bool {
+        self.before > 0 || self.after > 0
+    }
+
+    fn matches_glob(&self, relative: &Path) -> bool {
+        let Some(glob) = &self.glob else {
+            return true;
+        };
+
+        if glob.as_str().contains('/') {
+            glob.matches_path(relative)
+        } else {
+            relative.file_name().is_some_and(|n| glob.matches(&n.to_string_lossy()))
+        }
+    }
+
+    /// Output lines for the matches of `path`, empty when nothing matches
+    fn search(&self, path: &Path, name: &str) -> Vec<String> {
+        let Ok(metadata) = fs::metadata(path) else {
+            return Vec::new();
+        };
+
+        if metadata.len() > MAX_GREP_FILE_SIZE {
+            return Vec::new();
+        }
+
+        let Ok(bytes) = fs::read(path) else {
+            return Vec::new();
+        };
+
+        if is_binary(&bytes) {
+            return Vec::new();
+        }
+
+        let text = String::from_utf8_lossy(&bytes);
+        let lines: Vec<&str> = text.lines().collect();
+
+        let matched: Vec<usize> = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| self.regex.is_match(line))
+            .map(|(i, _)| i)
+            .collect();
+
+        if matched.is_empty() {
+            return Vec::new();
+        }
+
+        if self.files_only {
+            return vec![name.to_string()];
+        }
+
+        let mut out = Vec::new();
+        // next line not printed yet
+        let mut next = 0;
+
+        for (n, &i) in matched.iter().enumerate() {
+            let from = i.saturating_sub(self.before).max(next);
+            let to = i.saturating_add(self.after).min(lines.len().saturating_sub(1));
+
+            if n > 0 && from > next && self.has_context() {
+                out.push("--".to_string());
+            }
+
+            for j in from..=to {
+                let separator = if matched.binary_search(&j).is_ok() {
+                    ':'
+                } else {
+                    '-'
+                };
+                let line = lines.get(j).copied().unwrap_or_default();
+
+                out.push(format!(
+                    "{name}{separator}{}{separator}{}",
+                    j.saturating_add(1),
+                    truncate_line(line)
+                ));
+            }
+
+            next = next.max(to.saturating_add(1));
+        }
+
+        out
+    }
+}
+
+/// `grep [-E] [-i] [-l] [-C N|-A N|-B N] [--glob GLOB] PATTERN [PATH...]`:
+/// the lines matching a basic, or with `-E` extended, regex, with line
+/// numbers, directories searched recursively
+pub fn grep(args: &[String], dir: &Path) -> Result<String> {
+    let (options, paths) = Options::parse(args)?;
+
+    let mut out: Vec<String> = Vec::new();
+
+    for path in &paths {
+        let full = dir.join(path);
+        // file and name shown
+        let mut files = Vec::new();
+
+        if full.is_dir() {
+            for entry in gitignore::walk(&full, false).filter(|e| !e.file_type().is_dir()) {
+                let relative = entry.path().strip_prefix(&full).unwrap_or_else(|_| entry.path());
+
+                if !options.matches_glob(relative) {
+                    continue;
+                }
+
+                let name = if path.is_relative() {
+                    shown(entry.path(), dir)
+                } else {
+                    entry.path().display().to_string()
+                };
+
+                files.push((entry.into_path(), name));
+            }
+        } else {
+            files.push((full, path.display().to_string()));
+        }
+
+        for (file, name) in files {
+            let found = options.search(&file, &name);
+
+            if found.is_empty() {
+                continue;
+            }
+
+            if !out.is_empty() && options.has_context() {
+                out.push("--".to_string());
+            }
+
+            out.extend(found);
+
+            if out.len() > MAX_GREP_LINES {
+                out.truncate(MAX_GREP_LINES);
+                out.push(format!(
+                    "... output truncated after {MAX_GREP_LINES} lines, narrow the search"
+                ));
+                return Ok(out.join("\n"));
+            }
+        }
+    }
+
+    if out.is_empty() {
+        return Ok(format!("No match for {}", options.regex.as_str()));
+    }
+
+    Ok(out.join("\n"))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_grep() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(
+            root.join("src/main.rs"),
+            "use std::fs;\n\nfn main() {\n    todo!()\n}\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/notes.md"), "TODO: tests\n").unwrap();
+        fs::write(root.join("target/main.rs"), "todo!()\n").unwrap();
+        fs::write(root.join("src/blob.bin"), b"todo\0").unwrap();
+
+        let r = root.display();
+        let cwd = Path::new("/");
+
+        assert_eq!(
+            grep(&args(&format!("-rn todo {r}")), cwd).unwrap(),
+            format!("{r}/src/main.rs:4:    todo!()")
+        );
+
+        assert_eq!(
+            grep(&args(&format!("-i -l todo {r}")), cwd).unwrap(),
+            format!("{r}/src/main.rs\n{r}/src/notes.md")
+        );
+
+        assert_eq!(
+            grep(&args(&format!("-i --glob *.md todo {r}")), cwd).unwrap(),
+            format!("{r}/src/notes.md:1:TODO: tests")
+        );
+
+        assert_eq!(
+            grep(&args(&format!("-C1 todo {r}/src/main.rs")), cwd).unwrap(),
+            format!(
+                "{r}/src/main.rs-3-fn main() {{\n{r}/src/main.rs:4:    todo!()\n{r}/src/main.rs-5-}}"
+            )
+        );
+
+        assert_eq!(
+            grep(&args(&format!("-A 1 -iE ^(use|todo) {r}")), cwd).unwrap(),
+            format!(
+                "{r}/src/main.rs:1:use std::fs;\n{r}/src/main.rs-2-\n--\n{r}/src/notes.md:1:TODO: tests"
+            )
+        );
+
+        // relative to the working directory
+        assert_eq!(
+            grep(&args("-il todo"), root).unwrap(),
+            "src/main.rs\nsrc/notes.md"
+        );
+        assert_eq!(
+            grep(&args("todo src/main.rs"), root).unwrap(),
+            "src/main.rs:4:    todo!()"
+        );
+
+        assert!(grep(&args(&format!("nothing {r}")), cwd).unwrap().starts_with("No match"));
+        // options after the pattern
+        assert_eq!(
+            grep(&args("todo -i src -l"), root).unwrap(),
+            "src/main.rs\nsrc/notes.md"
+        );
+
+        for unsupported in ["-x todo", "-e todo", "--include=*.rs todo", "todo src -w"] {
+            assert!(
+                matches!(
+                    grep(&args(unsupported), cwd),
+                    Err(Error::UnsupportedOption { .. })
+                ),
+                "{unsupported}"
+            );
+        }
+        assert!(matches!(
+            grep(&args("-C todo"), cwd),
+            Err(Error::Usage { .. })
+        ));
+        assert!(matches!(grep(&args("-E ("), cwd), Err(Error::Regex(_))));
+    }
+
+    #[test]
+    fn test_basic_regex() {
+        assert_eq!(from_basic(r"^\(use\|todo\)"), "^(use|todo)");
+        assert_eq!(from_basic(r"a\{2,\}b\+c\?"), "a{2,}b+c?");
+        assert_eq!(from_basic("fn main(a|b)+?{"), r"fn main\(a\|b\)\+\?\{");
+        assert_eq!(from_basic(r"\.\w\$"), r"\.\w\$");
+        assert_eq!(
+            from_basic(r"[]\(|][^]a][[:digit:]x]"),
+            r"[\]\\(|][^\]a][[:digit:]x]"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}\nuse std;\n").unwrap();
+
+        let dir = dir.path();
+
+        // literal parentheses
+        assert_eq!(
+            grep(&args("main() main.rs"), dir).unwrap(),
+            "main.rs:1:fn main() {}"
+        );
+        assert_eq!(
+            grep(&args(r"^\(use\|fn\) main.rs"), dir).unwrap(),
+            "main.rs:1:fn main() {}\nmain.rs:2:use std;"
+        );
+        // an empty group
+        assert_eq!(
+            grep(&args("-E main() main.rs"), dir).unwrap(),
+            "main.rs:1:fn main() {}"
+        );
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = Path::new("/");
+        let path = dir.path().join("many.txt");
+        fs::write(&path, "match\n".repeat(MAX_GREP_LINES * 2)).unwrap();
+
+        let out = grep(&args(&format!("match {}", path.display())), cwd).unwrap();
+        assert_eq!(out.lines().count(), MAX_GREP_LINES + 1);
+        assert!(out.ends_with("narrow the search"));
+    }
+}
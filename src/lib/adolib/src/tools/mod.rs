@@ -0,0 +1,118 @@
+//! File operations the agent runs in-process rather than through the shell, so
+//! agentic loops behave the same on hosts without coreutils: `glob`, `grep`,
+//! `read` and `ls`. They skip what `.gitignore` excludes and bound their
+//! output to keep the conversation small.
+
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+mod fs_ops;
+pub mod gitignore;
+mod grep;
+
+/// Most paths `glob` lists
+pub const MAX_GLOB_RESULTS: usize = 500;
+/// Most lines `grep` outputs
+pub const MAX_GREP_LINES: usize = 200;
+/// Most lines `read` outputs
+pub const MAX_READ_LINES: usize = 2000;
+/// Longest line `grep` and `read` output, in chars
+pub const MAX_LINE_LEN: usize = 500;
+/// Largest file `grep` searches
+pub const MAX_GREP_FILE_SIZE: u64 = 1024 * 1024;
+/// Largest file `read` reads
+pub const MAX_READ_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Names of the built-in operations
+pub const TOOLS: &[&str] = &["glob", "grep", "ls", "read"];
+
+/// Run `args` when it is a built-in operation, `None` otherwise. Relative
+/// paths are relative to `dir`, and shown as such. `ls` and `grep` fail with
+/// [`Error::UnsupportedOption`] on options of the real programs they don't
+/// have, for the caller to run those instead.
+#[must_use]
+pub fn run(args: &[String], dir: &Path) -> Option<Result<String>> {
+    let (name, args) = args.split_first()?;
+
+    let result = match name.as_str() {
+        "glob" => fs_ops::glob(args, dir),
+        "grep" => grep::grep(args, dir),
+        "ls" => fs_ops::ls(args, dir),
+        "read" => fs_ops::read(args, dir),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+fn usage(help: &str) -> Error {
+    Error::Usage {
+        help: help.to_string(),
+    }
+}
+
+fn unsupported(option: &str) -> Error {
+    Error::UnsupportedOption {
+        option: option.to_string(),
+    }
+}
+
+/// `path` as shown in the output: relative to `dir` when below it
+fn shown(path: &Path, dir: &Path) -> String {
+    path.strip_prefix(dir).unwrap_or(path).display().to_string()
+}
+
+/// `line` cut to `MAX_LINE_LEN` chars
+fn truncate_line(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_LEN) {
+        Some((i, _)) => format!("{}…", line.get(..i).unwrap_or(line)),
+        None => line.to_string(),
+    }
+}
+
+/// Whether `bytes` look like the start of a binary file
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(8000).any(|b| *b == 0)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// TESTS
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        let dir = Path::new(".");
+
+        assert!(run(&args("cargo build"), dir).is_none());
+        assert!(run(&[], dir).is_none());
+        assert!(matches!(
+            run(&args("read"), dir),
+            Some(Err(Error::Usage { .. }))
+        ));
+        assert!(matches!(
+            run(&args("grep"), dir),
+            Some(Err(Error::Usage { .. }))
+        ));
+        assert!(matches!(
+            run(&args("ls -R"), dir),
+            Some(Err(Error::UnsupportedOption { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_truncate_line() {
+        assert_eq!(truncate_line("short"), "short");
+
+        let long = "é".repeat(MAX_LINE_LEN + 10);
+        let cut = truncate_line(&long);
+        assert_eq!(cut.chars().count(), MAX_LINE_LEN + 1);
+        assert!(cut.ends_with('…'));
+    }
+}